@@ -1,8 +1,85 @@
 
+use crate::config::{Setting, SettingKey};
+use crate::help::HELP_TOPICS;
+use crate::lexer::{self, reject_additional_tokens, FromTokens, Token};
 use crate::pattern::{BytePattern, StringPattern};
 use std::convert::TryFrom;
 use std::str::FromStr;
 use std::num::{IntErrorKind, ParseIntError};
+use std::path::PathBuf;
+
+/// Identifies one of the commands, without any of its parameters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommandKind {
+    Seek,
+    Find,
+    Print,
+    BadSectors,
+    Config,
+    Source,
+    History,
+    Help,
+    Exit,
+}
+
+/// Every command, paired with the name used to run it.
+pub const COMMANDS: [(&str, CommandKind); 9] = [
+    ("seek", CommandKind::Seek),
+    ("find", CommandKind::Find),
+    ("print", CommandKind::Print),
+    ("bad-sectors", CommandKind::BadSectors),
+    ("config", CommandKind::Config),
+    ("source", CommandKind::Source),
+    ("history", CommandKind::History),
+    ("help", CommandKind::Help),
+    ("exit", CommandKind::Exit),
+];
+
+/// The modes of the `seek` command, paired with their names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeekMode {
+    Absolute,
+    Relative,
+}
+
+pub const SEEK_MODES: [(&str, SeekMode); 2] = [("absolute", SeekMode::Absolute), ("relative", SeekMode::Relative)];
+
+/// The modes of the `find` command, paired with their names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FindMode {
+    NonZero,
+    Bytes,
+    String,
+}
+
+pub const FIND_MODES: [(&str, FindMode); 3] = [
+    ("nonzero", FindMode::NonZero),
+    ("bytes", FindMode::Bytes),
+    ("string", FindMode::String),
+];
+
+/// The actions of the `config` command, paired with their names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigAction {
+    Show,
+    Set,
+    Reset,
+    Save,
+    Load,
+}
+
+pub const CONFIG_ACTIONS: [(&str, ConfigAction); 5] = [
+    ("show", ConfigAction::Show),
+    ("set", ConfigAction::Set),
+    ("reset", ConfigAction::Reset),
+    ("save", ConfigAction::Save),
+    ("load", ConfigAction::Load),
+];
+
+/// Searches `table` for the entry called `name` (ignoring case), and returns its value.
+pub fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|(entry, _)| entry.eq_ignore_ascii_case(name)).map(|(_, value)| *value)
+}
 
 /// TODO
 #[derive(Debug)]
@@ -10,7 +87,10 @@ pub enum Command {
     Seek(Seek),
     Find(Find),
     Print(Print),
+    BadSectors(BadSectors),
     Config(Config),
+    Source(PathBuf),
+    History(History),
     Help(Help),
     Exit,
     None,
@@ -19,25 +99,45 @@ pub enum Command {
 impl FromStr for Command {
     type Err = String;
 
-    /// TODO
+    /// Splits a line of input into tokens, then parses a command from them.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Get the first token in the string; this token specifies the command to run.
-        let Some((command, remainder)) = split_at_first_token(s) else {
+        Command::from_tokens(s, &lexer::tokenize(s)?)
+    }
+}
+
+impl FromTokens for Command {
+    /// TODO
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        // Get the first token in the input; this token specifies the command to run.
+        let Some((command, remainder)) = tokens.split_first() else {
             return Ok(Command::None);
         };
 
-        // Compare the token against a list of commands, then parse the rest of the string accordingly.
-        match command.to_lowercase().as_str() {
-            "seek"   => remainder.parse::<Seek>().map(Command::Seek),
-            "find"   => remainder.parse::<Find>().map(Command::Find),
-            "print"  => remainder.parse::<Print>().map(Command::Print),
-            "config" => remainder.parse::<Config>().map(Command::Config),
-            "help"   => remainder.parse::<Help>().map(Command::Help),
-            "exit"   => {
-                reject_additional_tokens(remainder, "help")?;
+        // Look the token up in the list of commands, then parse the rest of the tokens accordingly.
+        let name = command.text(input)?;
+        let Some(kind) = lookup(&COMMANDS, name) else {
+            return Err(format!("Unknown command: '{}'. Enter 'help' for a list of commands.", name.to_lowercase()));
+        };
+        match kind {
+            CommandKind::Seek   => Seek::from_tokens(input, remainder).map(Command::Seek),
+            CommandKind::Find   => Find::from_tokens(input, remainder).map(Command::Find),
+            CommandKind::Print  => Print::from_tokens(input, remainder).map(Command::Print),
+            CommandKind::BadSectors => BadSectors::from_tokens(input, remainder).map(Command::BadSectors),
+            CommandKind::Config => Config::from_tokens(input, remainder).map(Command::Config),
+            CommandKind::Source => {
+                // The next (and last) token should be the path of the script to run.
+                let Some((path, extra)) = remainder.split_first() else {
+                    return Err("Missing path of the script to run. Enter 'help source' for an example.".to_owned());
+                };
+                reject_additional_tokens(input, extra, "help source")?;
+                Ok(Command::Source(PathBuf::from(path.text(input)?)))
+            }
+            CommandKind::History => History::from_tokens(input, remainder).map(Command::History),
+            CommandKind::Help   => Help::from_tokens(input, remainder).map(Command::Help),
+            CommandKind::Exit   => {
+                reject_additional_tokens(input, remainder, "help")?;
                 Ok(Command::Exit)
             }
-            unknown  => Err(format!("Unknown command: '{unknown}'. Enter 'help' for a list of commands.")),
         }
     }
 }
@@ -49,34 +149,34 @@ pub enum Seek {
     Relative(i64),
 }
 
-impl FromStr for Seek {
-    type Err = String;
-
+impl FromTokens for Seek {
     /// TODO
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // The next token in the string describes the seek mode. Return an error if it's missing.
-        let Some((mode, arguments)) = split_at_first_token(s) else {
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        // The next token in the input describes the seek mode. Return an error if it's missing.
+        let Some((mode, arguments)) = tokens.split_first() else {
             return Err("Missing seek mode: 'absolute' or 'relative'. Enter 'help seek' for an example.".to_owned());
         };
 
-        // The last token in the string should be the offset/position to seek to. We check for the token,
+        // The last token in the input should be the offset/position to seek to. We check for the token,
         // and parse it as an integer if it's present. If it's missing, return an error.
-        let Some((raw_integer, extra)) = split_at_first_token(arguments) else {
+        let Some((raw_integer, extra)) = arguments.split_first() else {
             return Err("Missing offset/position to seek to. Enter 'help seek' for an explanation".to_owned());
         };
+        let raw_integer = raw_integer.text(input)?;
         let integer = raw_integer.parse::<i64>().map_err(|err| {
             format!("invalid offset/position: '{raw_integer}' {}.", get_explanation_for(err))
         })?;
 
-        // Return an error if there's any tokens left in the string.
-        reject_additional_tokens(extra, "help seek")?;
+        // Return an error if there's any tokens left in the input.
+        reject_additional_tokens(input, extra, "help seek")?;
 
         // Construct a `Seek` with the specified mode and offset/position.
         // Or report an error if an invalid seek mode was specified.
-        match mode.to_lowercase().as_str() {
-            "absolute" => Ok(Seek::Absolute(integer)),
-            "relative" => Ok(Seek::Relative(integer)),
-            unknown => Err(format!("Unknown seek mode: '{unknown}'. Enter 'help seek' for a list of seek modes.")),
+        let mode = mode.text(input)?;
+        match lookup(&SEEK_MODES, mode) {
+            Some(SeekMode::Absolute) => Ok(Seek::Absolute(integer)),
+            Some(SeekMode::Relative) => Ok(Seek::Relative(integer)),
+            None => Err(format!("Unknown seek mode: '{}'. Enter 'help seek' for a list of seek modes.", mode.to_lowercase())),
         }
     }
 }
@@ -84,48 +184,50 @@ impl FromStr for Seek {
 /// TODO
 #[derive(Debug)]
 pub enum Find {
-    NonZero,
+    NonZero(Option<PathBuf>),
     Byte(BytePattern),
     String(StringPattern),
 }
 
-impl FromStr for Find {
-    type Err = String;
-
+impl FromTokens for Find {
     /// TODO
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Get the next token in the string; this token specifies the find mode. Return an error if it's missing.
-        let Some((mode, remainder)) = split_at_first_token(s) else {
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        // Get the next token in the input; this token specifies the find mode. Return an error if it's missing.
+        let Some((mode, remainder)) = tokens.split_first() else {
             return Err("Missing find mode: 'nonzero', 'bytes', or 'string'. Enter 'help find' for an example.".to_owned());
         };
 
-        // Compare the token against a list of find modes, then parse the rest of the string accordingly.
-        match mode.to_lowercase().as_str() {
-            "nonzero" => {
-                reject_additional_tokens(remainder, "help find nonzero")?;
-                Ok(Find::NonZero)
+        // Look the token up in the list of find modes, then parse the rest of the tokens accordingly.
+        let mode = mode.text(input)?;
+        match lookup(&FIND_MODES, mode) {
+            Some(FindMode::NonZero) => {
+                // The next token (if present) is the path of a file to save the sector map to.
+                let Some((path, extra)) = remainder.split_first() else {
+                    return Ok(Find::NonZero(None));
+                };
+                reject_additional_tokens(input, extra, "help find nonzero")?;
+                Ok(Find::NonZero(Some(PathBuf::from(path.text(input)?))))
             }
-            "bytes" => remainder.parse::<BytePattern>().map(Find::Byte),
-            "string" => remainder.parse::<StringPattern>().map(Find::String),
-            unknown => Err(format!("unknown find mode: '{unknown}'. Enter 'help find' for a list of find modes.'"))
+            Some(FindMode::Bytes) => BytePattern::from_tokens(input, remainder).map(Find::Byte),
+            Some(FindMode::String) => StringPattern::from_tokens(input, remainder).map(Find::String),
+            None => Err(format!("unknown find mode: '{}'. Enter 'help find' for a list of find modes.'", mode.to_lowercase()))
         }
     }
 }
 
 /// TODO
 #[derive(Debug)]
-pub struct Print(u64);
-
-impl FromStr for Print {
-    type Err = String;
+pub struct Print(pub u64);
 
+impl FromTokens for Print {
     /// TODO
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // The next (and last) token in the string should be the number of bytes to print. We check
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        // The next (and last) token in the input should be the number of bytes to print. We check
         // for the token and parse it as an integer if it's present. If it's missing, return an error.
-        let Some((raw_integer, extra)) = split_at_first_token(s) else {
+        let Some((raw_integer, extra)) = tokens.split_first() else {
             return Err("Missing number of bytes to print. Enter 'help print' for an example.".to_owned());
         };
+        let raw_integer = raw_integer.text(input)?;
         let integer = raw_integer.parse::<i64>().map_err(|err| {
             format!("Invalid number of bytes: '{raw_integer}' {}.", get_explanation_for(err))
         })?;
@@ -133,30 +235,138 @@ impl FromStr for Print {
             "The number of bytes to print must be non-negative.".to_owned()
         })?;
 
-        // Return an error if there's any tokens left in the string.
-        reject_additional_tokens(extra, "help print")?;
+        // Return an error if there's any tokens left in the input.
+        reject_additional_tokens(input, extra, "help print")?;
 
         Ok(Print(positive_integer))
     }
 }
 
-/// TODO
+/// The actions of the `bad-sectors` command.
 #[derive(Debug)]
-// TODO ADD CONFIG OPTIONS.
-pub enum Config {}
+pub enum BadSectors {
+    /// Lists the sectors which couldn't be read, and saves a map of them to the path (if one was provided).
+    List(Option<PathBuf>),
+    /// Loads a map of sectors which are already known to be unreadable, so they're skipped instead of being read.
+    Load(PathBuf),
+}
 
-impl FromStr for Config {
-    type Err = String;
+impl FromTokens for BadSectors {
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        let Some((first, remainder)) = tokens.split_first() else {
+            return Ok(BadSectors::List(None));
+        };
 
-    /// TODO
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // TODO ADD CONFIG OPTIONS.
-        Err("no options".to_owned())
+        // The first token is either '--load' followed by the path of the map to load,
+        // or the path of a file to save the bad-sector map to. Paths which start with dashes have to come after '--',
+        // so that misspelled options aren't mistaken for them.
+        let first = first.text(input)?;
+        if first.eq_ignore_ascii_case("--load") {
+            let Some((path, extra)) = remainder.split_first() else {
+                return Err("Missing path of the sector map to load. Enter 'help bad-sectors' for an example.".to_owned());
+            };
+            reject_additional_tokens(input, extra, "help bad-sectors")?;
+            return Ok(BadSectors::Load(PathBuf::from(path.text(input)?)));
+        }
+        let (path, remainder) = match first {
+            "--" => match remainder.split_first() {
+                Some((path, remainder)) => (path.text(input)?, remainder),
+                None => return Ok(BadSectors::List(None)),
+            },
+            option if option.starts_with("--") => {
+                return Err(format!("Unknown bad-sectors option: '{}'. Enter 'help bad-sectors' for a list of options.", option));
+            }
+            path => (path, remainder),
+        };
+        reject_additional_tokens(input, remainder, "help bad-sectors")?;
+        Ok(BadSectors::List(Some(PathBuf::from(path))))
     }
 }
 
 /// TODO
 #[derive(Debug)]
+pub enum Config {
+    Show,
+    Set(Setting),
+    Reset(Option<SettingKey>),
+    Save(Option<PathBuf>),
+    Load(Option<PathBuf>),
+}
+
+impl FromTokens for Config {
+    /// TODO
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        // Get the next token in the input; this token specifies the config action. Return an error if it's missing.
+        let Some((action, arguments)) = tokens.split_first() else {
+            return Err("Missing config action: 'show', 'set', 'reset', 'save', or 'load'. Enter 'help config' for an example.".to_owned());
+        };
+
+        // Look the token up in the list of actions, then parse the rest of the tokens accordingly.
+        let action = action.text(input)?;
+        match lookup(&CONFIG_ACTIONS, action) {
+            Some(ConfigAction::Show) => {
+                reject_additional_tokens(input, arguments, "help config")?;
+                Ok(Config::Show)
+            }
+            Some(ConfigAction::Set) => {
+                // The next 2 tokens should be the name of the setting, and the value to set it to.
+                let [key, value, extra @ ..] = arguments else {
+                    return Err("Missing setting and value to set it to. Enter 'help config' for an example.".to_owned());
+                };
+                reject_additional_tokens(input, extra, "help config")?;
+                let key = key.text(input)?.parse::<SettingKey>()?;
+                Setting::parse(key, value.text(input)?).map(Config::Set)
+            }
+            Some(ConfigAction::Reset) => {
+                // The next token (if present) is the name of the setting to reset. Otherwise all of them are reset.
+                let Some((key, extra)) = arguments.split_first() else {
+                    return Ok(Config::Reset(None));
+                };
+                reject_additional_tokens(input, extra, "help config")?;
+                key.text(input)?.parse::<SettingKey>().map(|key| Config::Reset(Some(key)))
+            }
+            Some(kind @ (ConfigAction::Save | ConfigAction::Load)) => {
+                // The next token (if present) is the path of the settings file. Otherwise the default file is used.
+                let path = match arguments.split_first() {
+                    Some((path, extra)) => {
+                        reject_additional_tokens(input, extra, "help config")?;
+                        Some(PathBuf::from(path.text(input)?))
+                    }
+                    None => None,
+                };
+                Ok(if kind == ConfigAction::Save { Config::Save(path) } else { Config::Load(path) })
+            }
+            None => Err(format!("Unknown config action: '{}'. Enter 'help config' for a list of actions.", action.to_lowercase())),
+        }
+    }
+}
+
+/// Lists the commands previously entered at the prompt, or re-runs one of them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum History {
+    List,
+    /// Re-runs the command with this number, as shown by `List`. Commands are numbered from 1.
+    Run(usize),
+}
+
+impl FromTokens for History {
+    /// Parses the optional number of the command to re-run.
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        // The next (and last) token, if present, is the number of the command to re-run.
+        let Some((raw_integer, extra)) = tokens.split_first() else {
+            return Ok(History::List);
+        };
+        let raw_integer = raw_integer.text(input)?;
+        let number = raw_integer.parse::<usize>().ok().filter(|&number| number > 0).ok_or_else(|| {
+            format!("Invalid command number: '{raw_integer}'. Enter 'history' to list the commands and their numbers.")
+        })?;
+        reject_additional_tokens(input, extra, "help history")?;
+        Ok(History::Run(number))
+    }
+}
+
+/// TODO
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Help {
     None,
     Seek,
@@ -167,48 +377,33 @@ pub enum Help {
     FindByte,
     FindString,
     Print,
+    BadSectors,
     Config,
+    Source,
+    History,
+    Exit,
 }
 
-impl FromStr for Help {
-    type Err = String;
-
+impl FromTokens for Help {
     /// TODO
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // TODO COMMENT THIS FUNCTION!
-        todo!()
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        // Lowercase each of the tokens, so topics can be entered in any case.
+        let words = tokens.iter()
+            .map(|token| token.text(input).map(str::to_lowercase))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Search the list of help topics for one whose words exactly match the tokens.
+        HELP_TOPICS.iter()
+            .find(|(topic_words, _)| topic_words.iter().eq(words.iter()))
+            .map(|(_, topic)| *topic)
+            .ok_or_else(|| format!("Unknown help topic: '{}'. Enter 'help' for a list of commands.", words.join(" ")))
     }
 }
 
-/// TODO
-fn split_at_first_token(raw_input: &str) -> Option<(&str, &str)> {
-    // Remove any whitespace from the start of the input.
-    let input = raw_input.trim_start();
-
-    // If the input was empty (except whitespace), return `None` since there aren't any tokens.
-    if input.is_empty() {
-        return None;
-    }
-
-    // If the string contains whitespace (after removing any leading whitespace), split the string
-    // into 2 pieces: any characters before that whitespace, and everything after that whitespace.
-    //
-    // If the string doesn't contain whitespace (after removing any leading whitespace), return the
-    // string as-is for the first token, and the empty string as the remainder.
-    Some(if input.contains(char::is_whitespace) {
-        // Safe to unwrap because we know the string contains non-whitespace characters
-        // (because we trimmed it and it wasn't empty), followed by whitespace characters
-        // (because we just checked if it contains whitespace). So this will always succeed.
-        input.split_once(char::is_whitespace).unwrap()
-    } else {
-        (input, "")
-    })
-}
-
 /// TODO
 fn get_explanation_for(error: ParseIntError) -> &'static str {
     match error.kind() {
-        IntErrorKind::Empty => unreachable!("Attempted to parse an empty string!"),
+        IntErrorKind::Empty => "is empty",
         IntErrorKind::InvalidDigit => "is not a valid number",
         IntErrorKind::PosOverflow => "is too large and overflowed",
         IntErrorKind::NegOverflow => "is too small and overflowed",
@@ -217,14 +412,126 @@ fn get_explanation_for(error: ParseIntError) -> &'static str {
     }
 }
 
-/// TODO
-fn reject_additional_tokens(remainder: &str, help: &str) -> Result<(), String> {
-    let extra = remainder.trim();
-    if !extra.is_empty() {
-        Err(format!("Unexpected extra parameter: '{extra}'. Enter '{help}' for an explanation."))
-    } else {
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed_from_tokens() {
+        assert!(matches!("  ".parse::<Command>(), Ok(Command::None)));
+        assert!(matches!("EXIT".parse::<Command>(), Ok(Command::Exit)));
+        assert!(matches!("seek absolute 512".parse::<Command>(), Ok(Command::Seek(Seek::Absolute(512)))));
+        assert!(matches!("seek \"relative\" '-16'".parse::<Command>(), Ok(Command::Seek(Seek::Relative(-16)))));
+        assert!(matches!("print 64".parse::<Command>(), Ok(Command::Print(Print(64)))));
     }
-}
 
-// TODO ADD UNIT TESTS!
\ No newline at end of file
+    #[test]
+    fn config_commands_are_parsed() {
+        assert!(matches!("config show".parse::<Command>(), Ok(Command::Config(Config::Show))));
+        assert!(matches!("config reset".parse::<Command>(), Ok(Command::Config(Config::Reset(None)))));
+        assert!(matches!(
+            "config reset Print-Width".parse::<Command>(),
+            Ok(Command::Config(Config::Reset(Some(SettingKey::PrintWidth)))),
+        ));
+        assert!(matches!(
+            "config set sector-size 4096".parse::<Command>(),
+            Ok(Command::Config(Config::Set(Setting::SectorSize(4096)))),
+        ));
+        assert!(matches!("config save".parse::<Command>(), Ok(Command::Config(Config::Save(None)))));
+        let Ok(Command::Config(Config::Load(Some(path)))) = "config LOAD 'profiles/legacy hdd'".parse::<Command>() else {
+            panic!("failed to parse 'config load'");
+        };
+        assert_eq!(path, PathBuf::from("profiles/legacy hdd"));
+
+        let test_cases = [
+            ("config", "Missing config action"),
+            ("config list", "Unknown config action: 'list'"),
+            ("config show all", "Unexpected extra parameter at column 13: 'all'"),
+            ("config set sector-size", "Missing setting and value"),
+            ("config set sector-size 512 1024", "Unexpected extra parameter at column 28: '1024'"),
+            ("config set sectors 512", "Unknown setting: 'sectors'"),
+            ("config set sector-size 100", "Invalid value for 'sector-size'"),
+            ("config reset sectors", "Unknown setting: 'sectors'"),
+            ("config save a b", "Unexpected extra parameter at column 15: 'b'"),
+        ];
+        for (input, expected_error) in test_cases {
+            let error = input.parse::<Command>().unwrap_err();
+            assert!(error.contains(expected_error), "input '{}' produced the wrong error: {}", input, error);
+        }
+    }
+
+    #[test]
+    fn source_commands_are_parsed() {
+        let Ok(Command::Source(path)) = "source 'scripts/new drive.rr'".parse::<Command>() else {
+            panic!("failed to parse 'source'");
+        };
+        assert_eq!(path, PathBuf::from("scripts/new drive.rr"));
+        assert!("source".parse::<Command>().unwrap_err().starts_with("Missing path of the script to run."));
+        assert!("source a b".parse::<Command>().unwrap_err().contains("Unexpected extra parameter at column 10: 'b'"));
+    }
+
+    #[test]
+    fn bad_sectors_commands_are_parsed() {
+        assert!(matches!("bad-sectors".parse::<Command>(), Ok(Command::BadSectors(BadSectors::List(None)))));
+        let Ok(Command::BadSectors(BadSectors::List(Some(path)))) = "BAD-SECTORS 'maps/bad sectors.smap'".parse::<Command>() else {
+            panic!("failed to parse 'bad-sectors'");
+        };
+        assert_eq!(path, PathBuf::from("maps/bad sectors.smap"));
+        assert!("bad-sectors a b".parse::<Command>().unwrap_err().contains("Unexpected extra parameter at column 15: 'b'"));
+
+        let Ok(Command::BadSectors(BadSectors::Load(path))) = "bad-sectors --LOAD old.smap".parse::<Command>() else {
+            panic!("failed to parse 'bad-sectors --load'");
+        };
+        assert_eq!(path, PathBuf::from("old.smap"));
+        assert!("bad-sectors --load".parse::<Command>().unwrap_err().starts_with("Missing path of the sector map to load."));
+        assert!("bad-sectors --load a b".parse::<Command>().unwrap_err().contains("Unexpected extra parameter at column 22: 'b'"));
+    }
+
+    #[test]
+    fn bad_sectors_rejects_unknown_options_unless_they_follow_a_double_dash() {
+        let error = "bad-sectors --lod old.smap".parse::<Command>().unwrap_err();
+        assert_eq!(error, "Unknown bad-sectors option: '--lod'. Enter 'help bad-sectors' for a list of options.");
+
+        let Ok(Command::BadSectors(BadSectors::List(Some(path)))) = "bad-sectors -- --lod".parse::<Command>() else {
+            panic!("failed to parse 'bad-sectors --'");
+        };
+        assert_eq!(path, PathBuf::from("--lod"));
+        assert!(matches!("bad-sectors --".parse::<Command>(), Ok(Command::BadSectors(BadSectors::List(None)))));
+        assert!("bad-sectors -- a b".parse::<Command>().unwrap_err().contains("Unexpected extra parameter at column 18: 'b'"));
+    }
+
+    #[test]
+    fn history_commands_are_parsed() {
+        assert!(matches!("history".parse::<Command>(), Ok(Command::History(History::List))));
+        assert!(matches!("HISTORY 12".parse::<Command>(), Ok(Command::History(History::Run(12)))));
+        for command in ["history 0", "history -1", "history last"] {
+            let error = command.parse::<Command>().unwrap_err();
+            assert!(error.starts_with("Invalid command number"), "'{}' produced the wrong error: {}", command, error);
+        }
+        assert!("history 1 2".parse::<Command>().unwrap_err().contains("Unexpected extra parameter at column 11: '2'"));
+    }
+
+    #[test]
+    fn quoted_paths_can_contain_whitespace() {
+        let Ok(Command::Find(Find::NonZero(Some(path)))) = r#"find nonzero "my maps/diskA.map""#.parse::<Command>() else {
+            panic!("failed to parse 'find nonzero'");
+        };
+        assert_eq!(path, PathBuf::from("my maps/diskA.map"));
+    }
+
+    #[test]
+    fn errors_report_their_position_in_the_line() {
+        let test_cases = [
+            ("seek absolute 10 20", "Unexpected extra parameter at column 18: '20'"),
+            ("print 8 \"a b\"", "Unexpected extra parameter at column 9: '\"a b\"'"),
+            ("exit now", "Unexpected extra parameter at column 6: 'now'"),
+            ("find bytes FF D8 ZZ", "Invalid byte pattern at column 18: unexpected character 'Z'"),
+            ("find string \"abc", "Invalid input at column 13: missing closing quote"),
+            ("print \"\"", "Invalid number of bytes: '' is empty"),
+        ];
+        for (input, expected_error) in test_cases {
+            let error = input.parse::<Command>().unwrap_err();
+            assert!(error.contains(expected_error), "input '{}' produced the wrong error: {}", input, error);
+        }
+    }
+}
\ No newline at end of file
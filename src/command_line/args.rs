@@ -0,0 +1,242 @@
+use crate::command::{Command, Find};
+use crate::lexer::{FromTokens, Token};
+use std::path::PathBuf;
+
+/// A summary of the command line arguments that raw-reader accepts.
+pub const USAGE: &str = "\
+Usage:
+    raw-reader [OPTIONS] [COMMAND...]
+    raw-reader [OPTIONS] --script PATH
+    raw-reader [OPTIONS] scan-nonzero DEVICE [-o MAP]
+
+Without a COMMAND, raw-reader starts an interactive session. Otherwise COMMAND is run against the device, its
+output is written to stdout, and raw-reader exits. COMMAND is written the same way as it would be at the prompt
+(enter 'help' in an interactive session for a list of commands), except that quoting is left to your shell: each
+argument is one parameter, taken exactly as it was passed, so 'find string \"hello world\"' searches for both words.
+
+With --script, the commands in the script file at PATH are run instead (see 'help source' in an interactive session).
+The scan-nonzero subcommand is shorthand for running 'find nonzero MAP' against DEVICE.
+
+When a COMMAND or script is run, 'find' writes each of its results on a line of its own, as fields separated by tabs,
+and nothing else (messages about unreadable sectors are written to stderr):
+    find bytes, find string    OFFSET<TAB>SECTOR<TAB>LENGTH<TAB>ENCODING for each match ('-' is the encoding of bytes).
+    find nonzero               OFFSET<TAB>SECTOR<TAB>LENGTH for each extent of sectors which contain non-zero data.
+OFFSET and LENGTH are decimal numbers of bytes, and SECTOR is the decimal number of the sector that OFFSET is in.
+
+Options:
+    --device PATH    The file/device to read from, instead of selecting one interactively.
+    --config PATH    The settings file to load, instead of the default one.
+    --script PATH    Run the commands in the script file at PATH.
+    -h, --help       Print this message and exit.
+
+Exit codes:
+    0    The command succeeded.
+    1    The command was a search which didn't find anything.
+    2    The arguments, command, or settings file were invalid.
+    3    The device couldn't be opened or read, or the command (or a line of the script) failed while running.
+    4    The terminal couldn't be read from.
+    130  The command was stopped with Ctrl-C.";
+
+/// The options that raw-reader was started with.
+#[derive(Debug, Default)]
+pub struct Arguments {
+    /// The settings file to load at startup, instead of the default one.
+    pub config: Option<PathBuf>,
+    /// The file/device to read from. If this is missing, the user is prompted to select one.
+    pub device: Option<PathBuf>,
+    /// A command to run non-interactively. If this is missing, an interactive session is started instead.
+    pub command: Option<Command>,
+    /// Whether the usage message was requested.
+    pub help: bool,
+}
+
+impl Arguments {
+    /// Parses the command line arguments (excluding the program name).
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut arguments = Arguments::default();
+        let mut args = args.into_iter().peekable();
+
+        // Options come first. The first argument which isn't an option starts the command.
+        while let Some(arg) = args.next_if(|arg| arg.starts_with('-')) {
+            // Options which take a value accept it either as the next argument, or after an `=`.
+            let (option, inline_value) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option.to_owned(), Some(value.to_owned())),
+                _ => (arg, None),
+            };
+            let mut value = |name: &str| {
+                inline_value.clone().or_else(|| args.next()).ok_or_else(|| format!("Missing value for '{name}'."))
+            };
+
+            match option.as_str() {
+                "--config" => arguments.config = Some(PathBuf::from(value("--config")?)),
+                "--device" => arguments.device = Some(PathBuf::from(value("--device")?)),
+                "--script" => arguments.command = Some(Command::Source(PathBuf::from(value("--script")?))),
+                "-h" | "--help" => arguments.help = true,
+                unknown => return Err(format!("Unknown option: '{unknown}'. Run 'raw-reader --help' for usage.")),
+            }
+        }
+
+        let remainder = args.collect::<Vec<_>>();
+        if !remainder.is_empty() && arguments.command.is_some() {
+            return Err("A command can't be run alongside a script passed with '--script'.".to_owned());
+        }
+        match remainder.split_first() {
+            None => {}
+            Some((subcommand, rest)) if subcommand == "scan-nonzero" => {
+                let (device, output) = parse_scan_nonzero(rest)?;
+                if arguments.device.is_some() {
+                    return Err("'scan-nonzero' already specifies the device, so '--device' can't be used with it.".to_owned());
+                }
+                arguments.device = Some(device);
+                arguments.command = Some(Command::Find(Find::NonZero(output)));
+            }
+            Some(_) => {
+                // The arguments were already split up (and unquoted) by the shell, so each one becomes a single token.
+                let (input, tokens) = tokens_from_args(&remainder);
+                arguments.command = Some(Command::from_tokens(&input, &tokens)?);
+            }
+        }
+
+        if arguments.command.is_some() && arguments.device.is_none() {
+            return Err("A device must be specified with '--device' to run a command or script.".to_owned());
+        }
+        Ok(arguments)
+    }
+}
+
+/// Joins `args` into a line of input (which errors can point into), along with a token for each argument that holds it
+/// exactly as it was passed, without processing any quotes or escapes in it.
+fn tokens_from_args(args: &[String]) -> (String, Vec<Token>) {
+    let mut start = 0;
+    let tokens = args.iter().map(|arg| {
+        let token = Token { value: arg.as_bytes().to_vec(), quoted: false, span: start..start + arg.len() };
+        start += arg.len() + 1;
+        token
+    }).collect();
+    (args.join(" "), tokens)
+}
+
+/// Parses the arguments of the `scan-nonzero` subcommand: `DEVICE [-o MAP]`.
+fn parse_scan_nonzero(args: &[String]) -> Result<(PathBuf, Option<PathBuf>), String> {
+    let mut device = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let path = args.next().ok_or_else(|| format!("Missing value for '{arg}'."))?;
+                output = Some(PathBuf::from(path));
+            }
+            path if device.is_none() => device = Some(PathBuf::from(path)),
+            extra => return Err(format!("Unexpected argument for 'scan-nonzero': '{extra}'.")),
+        }
+    }
+    let device = device.ok_or("Missing device for 'scan-nonzero'. Run 'raw-reader --help' for usage.")?;
+    Ok((device, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Seek;
+    use crate::data::dyn_aligned_buffer::DynAlignedBuffer;
+    use crate::pipeline::{Region, Scanner};
+
+    fn parse(args: &[&str]) -> Result<Arguments, String> {
+        Arguments::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_start_an_interactive_session() {
+        let arguments = parse(&[]).unwrap();
+        assert!(arguments.command.is_none() && arguments.device.is_none() && arguments.config.is_none());
+        assert!(!arguments.help);
+    }
+
+    #[test]
+    fn options_can_be_provided() {
+        let arguments = parse(&["--config", "nvme.conf", "--device=/dev/sdb", "-h"]).unwrap();
+        assert_eq!(arguments.config, Some(PathBuf::from("nvme.conf")));
+        assert_eq!(arguments.device, Some(PathBuf::from("/dev/sdb")));
+        assert!(arguments.help);
+        assert_eq!(parse(&["--config=a=b"]).unwrap().config, Some(PathBuf::from("a=b")));
+    }
+
+    #[test]
+    fn commands_are_parsed_the_same_way_as_at_the_prompt() {
+        let arguments = parse(&["--device", "/dev/sdb", "seek", "absolute", "512"]).unwrap();
+        assert!(matches!(arguments.command, Some(Command::Seek(Seek::Absolute(512)))));
+
+        let arguments = parse(&["--device", "/dev/sdb", "find", "bytes", "FF D8 FF"]).unwrap();
+        let Some(Command::Find(Find::Byte(pattern))) = arguments.command else {
+            panic!("failed to parse 'find bytes'");
+        };
+        assert_eq!(pattern.match_at(&[0xFF, 0xD8, 0xFF], 0), Some(3));
+    }
+
+    #[test]
+    fn each_argument_is_a_single_parameter() {
+        let arguments = parse(&["--device", "img.dd", "find", "string", "hello world"]).unwrap();
+        let Some(Command::Find(Find::String(pattern))) = arguments.command else {
+            panic!("failed to parse 'find string'");
+        };
+        let mut chunk = DynAlignedBuffer::new(32, 16);
+        chunk[..17].copy_from_slice(b"hello hello world");
+        let region = Region { chunk_offset: 0, window_start: 0, start: 0, end: 32, window_end: 32 };
+        let matches = pattern.scan(&chunk, &region);
+        assert_eq!(matches.iter().map(|found| (found.offset, found.length)).collect::<Vec<_>>(), vec![(6, 11)]);
+
+        for path in ["my maps/disk a.smap", "say \"hi\".smap", "it's.smap"] {
+            let arguments = parse(&["--device", "img.dd", "find", "nonzero", path]).unwrap();
+            let Some(Command::Find(Find::NonZero(Some(parsed)))) = arguments.command else {
+                panic!("failed to parse 'find nonzero {}'", path);
+            };
+            assert_eq!(parsed, PathBuf::from(path));
+        }
+
+        let error = parse(&["--device", "img.dd", "find", "nonzero", "a b", "c"]).unwrap_err();
+        assert!(error.contains("Unexpected extra parameter at column 18: 'c'"), "unexpected error: {}", error);
+    }
+
+    #[test]
+    fn scan_nonzero_specifies_the_device_and_map() {
+        let arguments = parse(&["scan-nonzero", "image.dd", "-o", "map.smap"]).unwrap();
+        assert_eq!(arguments.device, Some(PathBuf::from("image.dd")));
+        let Some(Command::Find(Find::NonZero(Some(path)))) = arguments.command else {
+            panic!("failed to parse 'scan-nonzero'");
+        };
+        assert_eq!(path, PathBuf::from("map.smap"));
+
+        let arguments = parse(&["scan-nonzero", "image.dd"]).unwrap();
+        assert!(matches!(arguments.command, Some(Command::Find(Find::NonZero(None)))));
+    }
+
+    #[test]
+    fn scripts_are_run_with_source() {
+        let arguments = parse(&["--device", "image.dd", "--script", "triage.rr"]).unwrap();
+        let Some(Command::Source(path)) = arguments.command else {
+            panic!("failed to parse '--script'");
+        };
+        assert_eq!(path, PathBuf::from("triage.rr"));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        let test_cases = [
+            (&["--config"][..], "Missing value for '--config'."),
+            (&["--verbose"], "Unknown option: '--verbose'."),
+            (&["print", "16"], "A device must be specified with '--device'"),
+            (&["--device", "a", "print"], "Missing number of bytes to print."),
+            (&["--device", "a", "scan-nonzero", "b"], "'scan-nonzero' already specifies the device"),
+            (&["scan-nonzero"], "Missing device for 'scan-nonzero'."),
+            (&["scan-nonzero", "a", "-o"], "Missing value for '-o'."),
+            (&["scan-nonzero", "a", "b"], "Unexpected argument for 'scan-nonzero': 'b'."),
+            (&["--script", "a"], "A device must be specified with '--device'"),
+            (&["--device", "a", "--script", "b", "print", "1"], "A command can't be run alongside a script"),
+        ];
+        for (args, expected_error) in test_cases {
+            let error = parse(args).unwrap_err();
+            assert!(error.starts_with(expected_error), "{:?} produced the wrong error: {}", args, error);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::command::{lookup, CommandKind, ConfigAction, FindMode, COMMANDS, CONFIG_ACTIONS, FIND_MODES, SEEK_MODES};
+use crate::config::SETTINGS;
+use crate::help::HELP_TOPICS;
+use crate::lexer;
+use crate::pattern::{StringOption, ANY_ENCODING, ENCODINGS, STRING_OPTIONS};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::Context;
+
+/// Completes commands entered at the command prompt. Keywords are completed from the same tables that the command
+/// parser uses (so the two can't disagree), and paths are completed from the filesystem.
+///
+/// There are no bookmarks to complete the names of yet, since there's no command to create them.
+pub struct CommandCompleter {
+    paths: FilenameCompleter,
+}
+
+impl Default for CommandCompleter {
+    fn default() -> Self {
+        CommandCompleter { paths: FilenameCompleter::new() }
+    }
+}
+
+impl Completer for CommandCompleter {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        self.complete_at(line, pos)
+    }
+}
+
+impl CommandCompleter {
+    /// Returns the position that completions should be inserted at, and the completions for the token at `pos`.
+    fn complete_at(&self, line: &str, pos: usize) -> rustyline::Result<(usize, Vec<Pair>)> {
+        // Only the input before the cursor affects what can be entered at the cursor. If it can't be tokenized
+        // (because of an unclosed quote, for example), there's nothing sensible to complete.
+        let input = &line[..pos];
+        let Ok(mut tokens) = lexer::tokenize(input) else {
+            return Ok((pos, Vec::new()));
+        };
+
+        // If the cursor is at the end of a token, that token is the one being completed.
+        // Otherwise a new token is being started, and anything can be entered.
+        let partial = match tokens.last() {
+            Some(last) if last.span.end == input.len() => tokens.pop(),
+            _ => None,
+        };
+        let Ok(words) = tokens.iter().map(|token| token.text(input).map(str::to_lowercase)).collect::<Result<Vec<_>, _>>() else {
+            return Ok((pos, Vec::new()));
+        };
+
+        match completions_after(&words) {
+            Completions::Words(candidates) => {
+                let (start, prefix) = match &partial {
+                    // Keywords are never quoted, so there's nothing to complete inside of quotes.
+                    Some(token) if token.quoted => return Ok((pos, Vec::new())),
+                    Some(token) => (token.span.start, token.source(input).to_lowercase()),
+                    None => (pos, String::new()),
+                };
+                let pairs = candidates.into_iter()
+                    .filter(|candidate| candidate.starts_with(&prefix))
+                    .map(|candidate| Pair { display: candidate.to_owned(), replacement: format!("{candidate} ") })
+                    .collect();
+                Ok((start, pairs))
+            }
+            Completions::Path => self.paths.complete_path(line, pos),
+            Completions::Nothing => Ok((pos, Vec::new())),
+        }
+    }
+}
+
+/// What can be entered at a particular point in a command.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Completions {
+    /// One of a fixed set of keywords.
+    Words(Vec<&'static str>),
+    /// The path of a file.
+    Path,
+    /// A value which can't be completed, like a number or a pattern.
+    Nothing,
+}
+
+/// Works out what can be entered after `words`, which are the lowercase tokens that come before the cursor.
+pub fn completions_after(words: &[String]) -> Completions {
+    let Some((command, arguments)) = words.split_first() else {
+        return Completions::Words(names(&COMMANDS));
+    };
+
+    match (lookup(&COMMANDS, command), arguments) {
+        (Some(CommandKind::Seek), []) => Completions::Words(names(&SEEK_MODES)),
+        (Some(CommandKind::Find), []) => Completions::Words(names(&FIND_MODES)),
+        (Some(CommandKind::Find), [mode, options @ ..]) => match lookup(&FIND_MODES, mode) {
+            Some(FindMode::NonZero) if options.is_empty() => Completions::Path,
+            Some(FindMode::String) => string_completions_after(options),
+            _ => Completions::Nothing,
+        },
+        (Some(CommandKind::Config), []) => Completions::Words(names(&CONFIG_ACTIONS)),
+        (Some(CommandKind::Config), [action]) => match lookup(&CONFIG_ACTIONS, action) {
+            Some(ConfigAction::Set | ConfigAction::Reset) => Completions::Words(names(&SETTINGS)),
+            Some(ConfigAction::Save | ConfigAction::Load) => Completions::Path,
+            _ => Completions::Nothing,
+        },
+        (Some(CommandKind::BadSectors | CommandKind::Source), []) => Completions::Path,
+        (Some(CommandKind::BadSectors), [option]) if option == "--load" => Completions::Path,
+        (Some(CommandKind::Help), topic) => {
+            // Offer the next word of every help topic which starts with the words entered so far.
+            let mut next_words = Vec::new();
+            for (topic_words, _) in HELP_TOPICS {
+                if topic_words.len() > topic.len() && topic_words.iter().zip(topic).all(|(word, entered)| word == entered) {
+                    let next_word = topic_words[topic.len()];
+                    if !next_words.contains(&next_word) {
+                        next_words.push(next_word);
+                    }
+                }
+            }
+            Completions::Words(next_words)
+        }
+        _ => Completions::Nothing,
+    }
+}
+
+/// Works out what can be entered after `find string` and `arguments`. Options can be entered up until the regex.
+fn string_completions_after(arguments: &[String]) -> Completions {
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match lookup(&STRING_OPTIONS, argument) {
+            Some(StringOption::IgnoreCase) => {}
+            Some(StringOption::Encoding) => {
+                if arguments.next().is_none() {
+                    let mut encodings = names(&ENCODINGS);
+                    encodings.push(ANY_ENCODING);
+                    return Completions::Words(encodings);
+                }
+            }
+            Some(StringOption::MaxLength) => {
+                if arguments.next().is_none() {
+                    return Completions::Nothing;
+                }
+            }
+            // Anything other than an option is the regex, which must be the last parameter.
+            None => return Completions::Nothing,
+        }
+    }
+    Completions::Words(names(&STRING_OPTIONS))
+}
+
+/// Returns the names of every entry in `table`.
+fn names<T>(table: &[(&'static str, T)]) -> Vec<&'static str> {
+    table.iter().map(|(name, _)| *name).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Completes `line` with the cursor at the end, and returns the start of the completion and the replacements.
+    fn complete(line: &str) -> (usize, Vec<String>) {
+        let (start, pairs) = CommandCompleter::default().complete_at(line, line.len()).unwrap();
+        (start, pairs.into_iter().map(|pair| pair.replacement).collect())
+    }
+
+    #[test]
+    fn commands_and_modes_are_completed() {
+        assert_eq!(complete(""), (0, COMMANDS.iter().map(|(name, _)| format!("{name} ")).collect()));
+        assert_eq!(complete("s"), (0, vec!["seek ".to_owned(), "source ".to_owned()]));
+        assert_eq!(complete("  SE"), (2, vec!["seek ".to_owned()]));
+        assert_eq!(complete("seek "), (5, vec!["absolute ".to_owned(), "relative ".to_owned()]));
+        assert_eq!(complete("find st"), (5, vec!["string ".to_owned()]));
+        assert_eq!(complete("config r"), (7, vec!["reset ".to_owned()]));
+    }
+
+    #[test]
+    fn paths_are_completed_after_bad_sectors_and_its_load_option() {
+        let words = |line: &str| line.split_whitespace().map(str::to_owned).collect::<Vec<_>>();
+        assert_eq!(completions_after(&words("bad-sectors")), Completions::Path);
+        assert_eq!(completions_after(&words("bad-sectors --load")), Completions::Path);
+        assert_eq!(completions_after(&words("bad-sectors bad.smap")), Completions::Nothing);
+    }
+
+    #[test]
+    fn config_keys_are_completed() {
+        assert_eq!(complete("config set se"), (11, vec!["sector-size ".to_owned(), "search-direction ".to_owned()]));
+        assert_eq!(complete("config reset print"), (13, vec!["print-width ".to_owned()]));
+        assert_eq!(complete("config set print-width "), (23, vec![]));
+    }
+
+    #[test]
+    fn help_topics_are_completed_a_word_at_a_time() {
+        assert_eq!(complete("help f"), (5, vec!["find ".to_owned()]));
+        assert_eq!(complete("help b"), (5, vec!["bad-sectors ".to_owned()]));
+        assert_eq!(complete("help find "), (10, vec!["nonzero ".to_owned(), "bytes ".to_owned(), "string ".to_owned()]));
+        assert_eq!(complete("help seek relative "), (19, vec![]));
+    }
+
+    #[test]
+    fn string_options_and_encodings_are_completed() {
+        assert_eq!(complete("find string --e"), (12, vec!["--encoding ".to_owned()]));
+        assert_eq!(complete("find string -i --encoding utf16"), (26, vec!["utf16le ".to_owned(), "utf16be ".to_owned()]));
+        assert_eq!(complete("find string --encoding any -"), (27, vec![
+            "-i ".to_owned(),
+            "--ignore-case ".to_owned(),
+            "--encoding ".to_owned(),
+            "--max-length ".to_owned(),
+        ]));
+        assert_eq!(complete("find string password -"), (22, vec![]));
+        assert_eq!(complete("find string --max-length "), (25, vec![]));
+    }
+
+    #[test]
+    fn values_and_quoted_tokens_are_not_completed() {
+        assert_eq!(complete("seek absolute "), (14, vec![]));
+        assert_eq!(complete("find bytes F"), (12, vec![]));
+        assert_eq!(complete("\"se"), (3, vec![]));
+        assert_eq!(complete("'se'"), (4, vec![]));
+        assert_eq!(complete("unknown "), (8, vec![]));
+    }
+
+    #[test]
+    fn paths_are_completed_from_the_filesystem() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("triage.rr"), "").unwrap();
+        let line = format!("source {}/tri", directory.path().display());
+        let (start, pairs) = CommandCompleter::default().complete_at(&line, line.len()).unwrap();
+        assert_eq!(start, "source ".len());
+        let expected = format!("{}/triage.rr", directory.path().display());
+        assert_eq!(pairs.into_iter().map(|pair| pair.replacement).collect::<Vec<_>>(), [expected]);
+    }
+}
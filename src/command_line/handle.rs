@@ -1,10 +1,18 @@
 
+use super::completion::CommandCompleter;
+use super::highlight::CommandHighlighter;
+use super::history::MAX_HISTORY_LENGTH;
+use super::validation::{CommandHinter, CommandValidator};
+use rustyline::error::ReadlineError;
 use rustyline::Editor;
 use rustyline::{Completer, Helper, Highlighter, Hinter, Validator};
 use rustyline::completion::FilenameCompleter;
 use rustyline::config::Configurer;
 use rustyline::hint::HistoryHinter;
 use rustyline::history::DefaultHistory;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
 /// TODO
 pub type DiskSelectionInputHandler = CommandLineInputHandler<DiskSelectionHelper>;
@@ -15,23 +23,81 @@ pub type CommandInputHandler = CommandLineInputHandler<CommandHelper>;
 /// TODO
 pub struct CommandLineInputHandler<H: Helper + Default> {
     editor: Editor<H, DefaultHistory>,
+    /// The file that the history is saved to whenever an entry is added, if it's being saved.
+    history_path: Option<PathBuf>,
 }
 
 impl<H: Helper + Default> CommandLineInputHandler<H> {
-    /// TODO
-    pub fn new() -> Self {
-        let mut editor = Editor::new().expect("Failed to create terminal handle.");
+    /// Creates a handle to the terminal. Returns an error if the terminal can't be set up for line editing.
+    ///
+    /// If `history_path` is provided, the history is loaded from that file, and saved back to it as entries are added.
+    pub fn new(history_path: Option<PathBuf>) -> rustyline::Result<Self> {
+        let mut editor = Editor::new()?;
         editor.set_helper(Some(H::default()));
-        editor.set_auto_add_history(true);
-        CommandLineInputHandler { editor }
+        editor.set_max_history_size(MAX_HISTORY_LENGTH)?;
+        let mut handler = CommandLineInputHandler { editor, history_path: None };
+        if let Some(path) = history_path {
+            handler.load_history(path);
+        }
+        Ok(handler)
+    }
+
+    /// Loads the history saved at `path`, and saves any new entries to it. A missing file just means that there's no
+    /// history yet, but any other problem produces a warning, and the history isn't saved (to avoid overwriting it).
+    fn load_history(&mut self, path: PathBuf) {
+        match self.editor.load_history(&path) {
+            Ok(()) => {}
+            Err(ReadlineError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                eprintln!("warning: Failed to load the history from '{}': {err}. The history won't be saved.", path.display());
+                return;
+            }
+        }
+        self.history_path = Some(path);
     }
 
-    /// TODO
-    pub fn prompt(&mut self, prompt: &str) -> String {
-        self.editor.readline(prompt).expect("Failed to read from terminal.")
+    /// Adds `line` to the end of the history, and saves it to the history file. Blank lines aren't added.
+    ///
+    /// If the history can't be saved, a warning is printed, and the history stops being saved for the rest of the session.
+    pub fn add_history_entry(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        // Adding an entry to the in-memory history can't fail (duplicates of the previous entry are just skipped),
+        // so the result is ignored.
+        let _ = self.editor.add_history_entry(line);
+
+        if let Some(path) = &self.history_path {
+            if let Err(err) = append_history(&mut self.editor, path) {
+                eprintln!("warning: Failed to save the history to '{}': {err}. The history won't be saved.", path.display());
+                self.history_path = None;
+            }
+        }
+    }
+
+    /// Returns every entry in the history, oldest first.
+    pub fn history(&self) -> &DefaultHistory {
+        self.editor.history()
+    }
+
+    /// Displays `prompt`, and returns the line that the user enters.
+    ///
+    /// Returns `ReadlineError::Interrupted` if the user pressed Ctrl-C, `ReadlineError::Eof` if they pressed Ctrl-D
+    /// on an empty line (or the input was closed), and any other error if the terminal couldn't be read from.
+    pub fn prompt(&mut self, prompt: &str) -> rustyline::Result<String> {
+        self.editor.readline(prompt)
     }
 }
 
+/// Saves any entries which were added to the history of `editor` since it was last saved to `path`.
+/// The directory containing `path` is created if it doesn't exist yet.
+fn append_history<H: Helper>(editor: &mut Editor<H, DefaultHistory>, path: &Path) -> rustyline::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    editor.append_history(path)
+}
+
 /// TODO
 #[derive(Helper, Completer, Hinter, Highlighter, Validator)]
 pub struct DiskSelectionHelper {
@@ -60,22 +126,53 @@ impl Default for DiskSelectionHelper {
 #[derive(Helper, Completer, Hinter, Highlighter, Validator)]
 pub struct CommandHelper {
     #[rustyline(Completer)]
-    completer: (),
+    completer: CommandCompleter,
     #[rustyline(Hinter)]
-    hinter: HistoryHinter,
+    hinter: CommandHinter,
     #[rustyline(Validator)]
-    validator: (),
+    validator: CommandValidator,
     #[rustyline(Highlighter)]
-    highlighter: (),
+    highlighter: CommandHighlighter,
 }
 
 impl Default for CommandHelper {
     fn default() -> Self {
         Self {
-            completer: (),
-            hinter: HistoryHinter {},
-            validator: (),
-            highlighter: (),
+            completer: CommandCompleter::default(),
+            hinter: CommandHinter::default(),
+            validator: CommandValidator,
+            highlighter: CommandHighlighter,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_is_saved_and_loaded_across_sessions() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("state").join("command-history");
+
+        let mut handler = CommandInputHandler::new(Some(path.clone())).unwrap();
+        handler.add_history_entry("seek absolute 512");
+        handler.add_history_entry("   ");
+        handler.add_history_entry("print 16");
+        drop(handler);
+
+        let mut handler = CommandInputHandler::new(Some(path.clone())).unwrap();
+        assert_eq!(handler.history().iter().collect::<Vec<_>>(), ["seek absolute 512", "print 16"]);
+        handler.add_history_entry("find nonzero");
+
+        let handler = CommandInputHandler::new(Some(path)).unwrap();
+        assert_eq!(handler.history().iter().collect::<Vec<_>>(), ["seek absolute 512", "print 16", "find nonzero"]);
+    }
+
+    #[test]
+    fn history_is_kept_in_memory_without_a_file() {
+        let mut handler = DiskSelectionInputHandler::new(None).unwrap();
+        handler.add_history_entry("/dev/sdb");
+        assert_eq!(handler.history().iter().collect::<Vec<_>>(), ["/dev/sdb"]);
+    }
+}
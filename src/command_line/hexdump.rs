@@ -0,0 +1,176 @@
+use crate::config::DisplayBase;
+use std::io::{self, Write};
+
+/// Renders data in the same format as `hexdump -C`: each row starts with the absolute offset of its first byte,
+/// followed by the bytes in hexadecimal (split into groups of 8), and then the bytes as ASCII text.
+/// Runs of identical rows are collapsed into a single `*` line, and the offset just past the end of the data is
+/// printed on its own line once the dump is finished.
+///
+/// Data can be written in pieces of any size, and rows are printed as soon as they're complete.
+pub struct HexDump<W: Write> {
+    out: W,
+    /// The number of bytes in each row, and the base that offsets are displayed in.
+    width: usize,
+    base: DisplayBase,
+    /// The offset of the first byte in `row`.
+    offset: u64,
+    /// The bytes of the row that's currently being filled.
+    row: Vec<u8>,
+    /// The last row which was printed in full, and whether any rows identical to it have been skipped since.
+    previous_row: Option<Vec<u8>>,
+    collapsing: bool,
+}
+
+impl<W: Write> HexDump<W> {
+    /// Creates a hexdump which writes to `out`, where the first byte written to it is at `offset`.
+    /// Each row shows `width` bytes, and offsets are displayed in `base`.
+    pub fn new(out: W, offset: u64, width: usize, base: DisplayBase) -> Self {
+        assert!(width > 0, "hexdump rows must contain at least 1 byte");
+        HexDump { out, width, base, offset, row: Vec::with_capacity(width), previous_row: None, collapsing: false }
+    }
+
+    /// Adds `data` to the dump, printing any rows that it completes.
+    pub fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let taken = (self.width - self.row.len()).min(data.len());
+            self.row.extend_from_slice(&data[..taken]);
+            data = &data[taken..];
+
+            if self.row.len() == self.width {
+                self.flush_row()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints any partially filled row, followed by the offset just past the end of the data.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.row.is_empty() {
+            self.flush_row()?;
+        }
+        writeln!(self.out, "{}", format_offset(self.offset, self.base))
+    }
+
+    /// Prints the current row (or collapses it if it's identical to the previous row), then starts a new row.
+    fn flush_row(&mut self) -> io::Result<()> {
+        if self.previous_row.as_ref() == Some(&self.row) {
+            // Only the first skipped row in a run is marked with a `*`.
+            if !self.collapsing {
+                writeln!(self.out, "*")?;
+                self.collapsing = true;
+            }
+        } else {
+            write_row(&mut self.out, self.offset, &self.row, self.width, self.base)?;
+            self.collapsing = false;
+        }
+
+        self.offset += self.row.len() as u64;
+        self.previous_row = Some(std::mem::replace(&mut self.row, Vec::with_capacity(self.width)));
+        Ok(())
+    }
+}
+
+/// Formats the offset at the start of a row, so that all the offsets line up with each other.
+fn format_offset(offset: u64, base: DisplayBase) -> String {
+    match base {
+        DisplayBase::Hexadecimal => format!("{offset:016x}"),
+        DisplayBase::Decimal => format!("{offset:>16}"),
+    }
+}
+
+/// Writes a single row of the hexdump. Rows with less than `width` bytes are padded with spaces,
+/// so that their ASCII column still lines up with the other rows.
+fn write_row(out: &mut impl Write, offset: u64, row: &[u8], width: usize, base: DisplayBase) -> io::Result<()> {
+    write!(out, "{} ", format_offset(offset, base))?;
+    for index in 0..width {
+        // Put an extra space between the 2 groups of 8 bytes.
+        if index % 8 == 0 {
+            write!(out, " ")?;
+        }
+        match row.get(index) {
+            Some(byte) => write!(out, "{byte:02x} ")?,
+            None => write!(out, "   ")?,
+        }
+    }
+
+    // Only printable ASCII characters are shown as-is, everything else is shown as a '.'.
+    let text = row.iter().map(|&byte| if (0x20..0x7F).contains(&byte) { byte as char } else { '.' });
+    writeln!(out, " |{}|", text.collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dumps `data` in pieces of `piece_size` bytes (16 bytes per row), and returns the output.
+    fn dump(offset: u64, data: &[u8], piece_size: usize) -> String {
+        dump_with(offset, data, piece_size, 16, DisplayBase::Hexadecimal)
+    }
+
+    /// Dumps `data` in pieces of `piece_size` bytes, with the specified row width and base, and returns the output.
+    fn dump_with(offset: u64, data: &[u8], piece_size: usize, width: usize, base: DisplayBase) -> String {
+        let mut output = Vec::new();
+        let mut hexdump = HexDump::new(&mut output, offset, width, base);
+        for piece in data.chunks(piece_size) {
+            hexdump.write(piece).unwrap();
+        }
+        hexdump.finish().unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn rows_show_the_offset_hex_and_ascii() {
+        let data = b"Hello, World!\x00\x01\xFFraw-reader";
+        let expected = "\
+            0000000000001000  48 65 6c 6c 6f 2c 20 57  6f 72 6c 64 21 00 01 ff  |Hello, World!...|\n\
+            0000000000001010  72 61 77 2d 72 65 61 64  65 72                    |raw-reader|\n\
+            000000000000101a\n";
+        assert_eq!(dump(0x1000, data, data.len()), expected);
+    }
+
+    #[test]
+    fn data_can_be_written_in_pieces() {
+        let data = (0..100).collect::<Vec<u8>>();
+        let expected = dump(7, &data, data.len());
+        for piece_size in [1, 5, 16, 17, 99] {
+            assert_eq!(dump(7, &data, piece_size), expected);
+        }
+    }
+
+    #[test]
+    fn identical_rows_are_collapsed() {
+        let mut data = vec![0; 80];
+        data[64] = 1;
+        data.extend_from_slice(&[0; 40]);
+        let expected = "\
+            0000000000000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n\
+            *\n\
+            0000000000000040  01 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n\
+            0000000000000050  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n\
+            *\n\
+            0000000000000070  00 00 00 00 00 00 00 00                           |........|\n\
+            0000000000000078\n";
+        assert_eq!(dump(0, &data, 7), expected);
+    }
+
+    #[test]
+    fn rows_can_have_any_width_and_base() {
+        let data = b"0123456789abcdefghij";
+        let expected = "\
+            \x20           1000  30 31 32 33 34 35 36 37  38 39 61 62  |0123456789ab|\n\
+            \x20           1012  63 64 65 66 67 68 69 6a               |cdefghij|\n\
+            \x20           1020\n";
+        assert_eq!(dump_with(1000, data, 3, 12, DisplayBase::Decimal), expected);
+
+        let expected = "\
+            00000000000003e8  30 31 32  |012|\n\
+            *\n\
+            ";
+        assert!(dump_with(1000, &[b'0', b'1', b'2'].repeat(3), 3, 3, DisplayBase::Hexadecimal).starts_with(expected));
+    }
+
+    #[test]
+    fn empty_dumps_only_print_the_offset() {
+        assert_eq!(dump(42, &[], 1), "000000000000002a\n");
+    }
+}
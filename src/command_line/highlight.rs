@@ -0,0 +1,141 @@
+use super::completion::{completions_after, Completions};
+use crate::command::{lookup, CommandKind, FindMode, COMMANDS, FIND_MODES};
+use crate::lexer::{self, Token};
+use rustyline::highlight::Highlighter;
+use std::borrow::Cow;
+
+/// The ANSI escape sequences used to color each kind of token.
+const KEYWORD_COLOR: &str = "\x1b[1;34m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const HEX_COLOR: &str = "\x1b[35m";
+const PATTERN_COLOR: &str = "\x1b[32m";
+const HINT_COLOR: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Colors the command being entered at the command prompt: keywords (commands, modes, settings, and options) are
+/// blue, numbers are yellow, hexadecimal byte patterns are magenta, and string patterns are green.
+/// Hints are dimmed, so they can't be confused with the input.
+#[derive(Default)]
+pub struct CommandHighlighter;
+
+impl Highlighter for CommandHighlighter {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        // Lines which can't be tokenized (because of an unclosed quote, for example) are left as-is.
+        let Ok(tokens) = lexer::tokenize(line) else {
+            return Cow::Borrowed(line);
+        };
+
+        let mut highlighted = String::with_capacity(line.len() * 2);
+        let mut words = Vec::with_capacity(tokens.len());
+        let mut end_of_previous = 0;
+        for token in &tokens {
+            // Keep the whitespace between the tokens exactly as it was entered.
+            highlighted.push_str(&line[end_of_previous..token.span.start]);
+            match color_of(&words, token, line) {
+                Some(color) => highlighted.push_str(&format!("{color}{}{RESET}", token.source(line))),
+                None => highlighted.push_str(token.source(line)),
+            }
+            words.push(token.text(line).map(str::to_lowercase).unwrap_or_default());
+            end_of_previous = token.span.end;
+        }
+        highlighted.push_str(&line[end_of_previous..]);
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{HINT_COLOR}{hint}{RESET}"))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        // The color of a token can depend on every token before it, so the whole line is redrawn on every change.
+        true
+    }
+}
+
+/// Returns the color of `token`, which comes after `previous` (the lowercase text of the tokens before it).
+fn color_of(previous: &[String], token: &Token, input: &str) -> Option<&'static str> {
+    let text = token.text(input).ok()?.to_lowercase();
+
+    // Any keyword which is valid at this point in the command is highlighted as a keyword.
+    if !token.quoted {
+        if let Completions::Words(keywords) = completions_after(previous) {
+            if keywords.contains(&text.as_str()) {
+                return Some(KEYWORD_COLOR);
+            }
+        }
+    }
+
+    // Everything after `find bytes` is part of the byte pattern, and everything after the options of `find string`
+    // is part of the regex.
+    let find_mode = match previous {
+        [command, mode, ..] if lookup(&COMMANDS, command) == Some(CommandKind::Find) => lookup(&FIND_MODES, mode),
+        _ => None,
+    };
+    match find_mode {
+        Some(FindMode::Bytes) => return Some(if token.quoted { PATTERN_COLOR } else { HEX_COLOR }),
+        Some(FindMode::String) if token.quoted || (!is_number(&text) && previous.last().map(String::as_str) != Some("--encoding")) => {
+            return Some(PATTERN_COLOR);
+        }
+        _ => {}
+    }
+
+    if is_number(&text) {
+        Some(NUMBER_COLOR)
+    } else if is_hex_literal(&text) {
+        Some(HEX_COLOR)
+    } else {
+        None
+    }
+}
+
+/// Checks whether `text` is a decimal integer, which may be negative.
+fn is_number(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Checks whether `text` is a hexadecimal literal with a `0x` prefix.
+fn is_hex_literal(text: &str) -> bool {
+    text.strip_prefix("0x").is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Highlights `line`, replacing each color with a short tag so the expected output is readable.
+    fn highlight(line: &str) -> String {
+        CommandHighlighter.highlight(line, 0)
+            .replace(KEYWORD_COLOR, "<k>")
+            .replace(NUMBER_COLOR, "<n>")
+            .replace(HEX_COLOR, "<h>")
+            .replace(PATTERN_COLOR, "<p>")
+            .replace(RESET, "</>")
+    }
+
+    #[test]
+    fn keywords_and_numbers_are_highlighted() {
+        assert_eq!(highlight("  SEEK absolute -16"), "  <k>SEEK</> <k>absolute</> <n>-16</>");
+        assert_eq!(highlight("print 0x200"), "<k>print</> <h>0x200</>");
+        assert_eq!(highlight("config set print-width 8"), "<k>config</> <k>set</> <k>print-width</> <n>8</>");
+        assert_eq!(highlight("help find bytes"), "<k>help</> <k>find</> <k>bytes</>");
+    }
+
+    #[test]
+    fn patterns_are_highlighted() {
+        assert_eq!(highlight("find bytes FF \"PK\" ??"), "<k>find</> <k>bytes</> <h>FF</> <p>\"PK\"</> <h>??</>");
+        assert_eq!(
+            highlight("find string -i --encoding utf16le --max-length 64 'pass(word)?'"),
+            "<k>find</> <k>string</> <k>-i</> <k>--encoding</> <k>utf16le</> <k>--max-length</> <n>64</> <p>'pass(word)?'</>",
+        );
+        assert_eq!(highlight("find string password"), "<k>find</> <k>string</> <p>password</>");
+    }
+
+    #[test]
+    fn unknown_words_and_invalid_lines_are_left_alone() {
+        assert_eq!(highlight("seek absolute"), "<k>seek</> <k>absolute</>");
+        assert_eq!(highlight("seak absolute 5"), "seak absolute <n>5</>");
+        assert_eq!(highlight("source 'seek'"), "<k>source</> 'seek'");
+        assert_eq!(highlight("find string \"abc"), "find string \"abc");
+    }
+}
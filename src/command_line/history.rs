@@ -0,0 +1,65 @@
+use crate::config;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// The names of the files that the history of each prompt is saved in, inside the state directory.
+/// Each prompt accepts different input, so they're kept separate.
+pub const DISK_SELECTION_HISTORY_FILE: &str = "disk-history";
+pub const COMMAND_HISTORY_FILE: &str = "command-history";
+
+/// The most entries that are kept in each history. Once a history is full, its oldest entries are discarded.
+pub const MAX_HISTORY_LENGTH: usize = 1000;
+
+/// Returns the path of the history file called `file_name`, or `None` if the state directory couldn't be found.
+pub fn history_path(file_name: &str) -> Option<PathBuf> {
+    config::default_state_dir().map(|dir| dir.join(file_name))
+}
+
+/// Prints every entry in `entries`, alongside its number. Entries are numbered from 1, oldest first.
+pub fn print_history<'a>(entries: impl IntoIterator<Item = &'a String>, out: &mut impl Write) -> io::Result<()> {
+    for (index, entry) in entries.into_iter().enumerate() {
+        writeln!(out, "{:>5}  {entry}", index + 1)?;
+    }
+    Ok(())
+}
+
+/// Returns the entry in `entries` with the specified number, as printed by `print_history`.
+pub fn entry_numbered<'a, I>(entries: I, number: usize) -> Result<&'a String, String>
+where
+    I: IntoIterator<Item = &'a String>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let mut entries = entries.into_iter();
+    let count = entries.len();
+    number.checked_sub(1).and_then(|index| entries.nth(index)).ok_or_else(|| match count {
+        0 => "The history is empty.".to_owned(),
+        _ => format!("There's no command numbered {number}. Enter a number between 1 and {count} (inclusive)."),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<String> {
+        vec!["seek absolute 512".to_owned(), "print 16".to_owned()]
+    }
+
+    #[test]
+    fn entries_are_numbered_from_1() {
+        let mut output = Vec::new();
+        print_history(&entries(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "    1  seek absolute 512\n    2  print 16\n");
+    }
+
+    #[test]
+    fn entries_are_looked_up_by_number() {
+        let entries = entries();
+        assert_eq!(entry_numbered(&entries, 2), Ok(&entries[1]));
+        assert_eq!(
+            entry_numbered(&entries, 3),
+            Err("There's no command numbered 3. Enter a number between 1 and 2 (inclusive).".to_owned()),
+        );
+        assert_eq!(entry_numbered(&[], 1), Err("The history is empty.".to_owned()));
+    }
+}
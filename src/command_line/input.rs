@@ -1,41 +1,93 @@
 
 use super::handle::DiskSelectionInputHandler;
-use std::fs::File;
+use super::history::{history_path, DISK_SELECTION_HISTORY_FILE};
+use crate::device::ReadOnlyDevice;
+use crate::disk_info::Disk;
 use std::io::{self, Error, ErrorKind};
+use std::path::PathBuf;
 
-/// TODO
-pub fn get_user_disk_selection(disk_paths: &[String]) -> File {
-    let mut input_handler = DiskSelectionInputHandler::new();
+/// Prompts the user to select a file/device until they select one that can be opened, and returns a read-only handle
+/// to it.
+/// The user can either enter the number of one of `disks`, or the path of any file/device.
+///
+/// Returns an error if the user cancelled the selection with Ctrl-C or Ctrl-D, or if the terminal couldn't be read.
+pub fn get_user_disk_selection(disks: &[Disk]) -> rustyline::Result<ReadOnlyDevice> {
+    let mut input_handler = DiskSelectionInputHandler::new(history_path(DISK_SELECTION_HISTORY_FILE))?;
     loop {
         // If the user's selection was valid, return it, otherwise print why it was invalid.
-        match get_user_disk_selection_impl(&mut input_handler, disk_paths) {
-            Ok(file) => return file,
-            Err(err) => eprintln!("error: {err}"),
+        let selection = input_handler.prompt("\n> ")?;
+        input_handler.add_history_entry(&selection);
+        let path = match resolve_disk_selection(&selection, disks) {
+            Ok(path) => path,
+            Err(err) => {
+                eprintln!("error: {err}");
+                continue;
+            }
+        };
+        match ReadOnlyDevice::open(&path) {
+            Ok(device) => return Ok(device),
+            Err(err) => eprintln!("error: Failed to open '{}': {err}", path.display()),
         }
     }
 }
 
-/// TODO
-fn get_user_disk_selection_impl(input_handler: &mut DiskSelectionInputHandler, disk_paths: &[String]) -> io::Result<File> {
-    // Attempt to read a line from `stdin` into the provided string buffer.
-    let mut selection = input_handler.prompt("\n> ");
-
-    // If the user typed an integer, replace it with the corresponding disk's path.
-    if let Ok(index) = selection.trim().parse::<usize>() {
-        // Ensure the provided integer corresponds to a disk, otherwise return an error.
-        let disk_path = disk_paths.get(index).ok_or_else(|| {
-            let message = format!(
-                "'{index}' does not correspond to a disk. Enter a number between 0 and {} (inclusive).",
-                disk_paths.len() - 1,
-            );
-            Error::new(ErrorKind::NotFound, message)
-        })?;
-
-        // Replace the integer they typed with that disk's path.
-        selection = disk_path.clone();
+/// Works out the path of the file/device that `selection` refers to. If it's the number of one of the `disks`, this is
+/// the disk's device node (since mount points are directories, which can't be read as raw data). Otherwise
+/// `selection` is the path itself.
+fn resolve_disk_selection(selection: &str, disks: &[Disk]) -> io::Result<PathBuf> {
+    let selection = selection.trim();
+    let Ok(index) = selection.parse::<usize>() else {
+        return Ok(PathBuf::from(selection));
+    };
+
+    // Ensure the provided integer corresponds to a disk, otherwise return an error.
+    let disk = disks.get(index).ok_or_else(|| {
+        let message = match disks.len() {
+            0 => format!("'{index}' does not correspond to a disk, since none were found. Enter the path of a file/device instead."),
+            count => format!("'{index}' does not correspond to a disk. Enter a number between 0 and {} (inclusive).", count - 1),
+        };
+        Error::new(ErrorKind::NotFound, message)
+    })?;
+    disk.device_node.clone().ok_or_else(|| {
+        let message = format!("The device underlying '{}' is unknown. Enter the path of its device instead.", disk.name);
+        Error::new(ErrorKind::NotFound, message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_info::MediaType;
+
+    fn disk(name: &str, device_node: Option<&str>, mount_point: &str) -> Disk {
+        Disk {
+            name: name.to_owned(),
+            device_node: device_node.map(PathBuf::from),
+            mount_point: Some(PathBuf::from(mount_point)),
+            parent: None,
+            size: 0,
+            used_space: None,
+            sector_sizes: None,
+            file_system: None,
+            media_type: MediaType::Unknown,
+            removable: false,
+            read_only: false,
+            model: None,
+            serial: None,
+        }
     }
 
-    // Obtain a handle to the file/device at the specified path.
-    // Returns an error if no file/device exists at that path or if it's unreadable.
-    File::open(selection.trim())
+    #[test]
+    fn numbers_select_the_device_node_instead_of_the_mount_point() {
+        let disks = [disk("sda1", Some("/dev/sda1"), "/"), disk("overlay", None, "/var/lib/docker")];
+        assert_eq!(resolve_disk_selection(" 0 ", &disks).unwrap(), PathBuf::from("/dev/sda1"));
+        assert_eq!(resolve_disk_selection("image.dd", &disks).unwrap(), PathBuf::from("image.dd"));
+
+        let error = resolve_disk_selection("1", &disks).unwrap_err().to_string();
+        assert!(error.starts_with("The device underlying 'overlay' is unknown."), "wrong error: {}", error);
+        let error = resolve_disk_selection("2", &disks).unwrap_err().to_string();
+        assert!(error.ends_with("Enter a number between 0 and 1 (inclusive)."), "wrong error: {}", error);
+        let error = resolve_disk_selection("0", &[]).unwrap_err().to_string();
+        assert!(error.contains("since none were found"), "wrong error: {}", error);
+    }
 }
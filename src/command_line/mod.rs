@@ -1,4 +1,9 @@
-
+pub mod args;
+pub mod completion;
 pub mod handle;
+pub mod hexdump;
+pub mod highlight;
+pub mod history;
 pub mod input;
 pub mod output;
+pub mod validation;
@@ -1,5 +1,8 @@
 
-use crate::disk_info::DiskInfo;
+use crate::device::{DirectIo, WriteProtection};
+use crate::disk_info::{format_byte_count, Disk, MediaType};
+use std::io::{self, Write};
+use std::path::PathBuf;
 
 /// TODO
 pub fn print_disk_selection_introduction() {
@@ -9,37 +12,152 @@ pub fn print_disk_selection_introduction() {
     println!();
 }
 
-/// TODO
-pub fn print_disk_info(disk_info: &DiskInfo) {
-    // Iterate through each column of disk info, and find the length of the longest string in that column.
-    let column_widths = disk_info.iter().map(|disk_info_column| {
-        disk_info_column.iter().map(|s| s.len()).max().unwrap_or(0)
-    }).collect::<Vec<_>>();
-
-    // Print column headers that are spaced to match the column widths.
-    println!(
-        "        {name:^n$}    {path:^p$}    {space:^s$}    {fs:^f$}    {media:^m$}",
-        n = column_widths[0], name  = "NAME",
-        p = column_widths[1], path  = " MOUNT POINT",
-        s = column_widths[2], space = " USED / TOTAL",
-        f = column_widths[3], fs    = "FS",
-        m = column_widths[4], media = "MEDIA TYPE",
-    );
-
-    // Iterate through each disk and print it's information in nicely formatted columns.
-    for i in 0..disk_info[0].len() {
-        println!(
-            "    [{i}] {name:<n$}    {path:<p$}    {space:<s$}    {fs:<f$}    {media:<m$}",
-            n = column_widths[0], name  = disk_info[0][i],
-            p = column_widths[1], path  = disk_info[1][i],
-            s = column_widths[2], space = disk_info[2][i],
-            f = column_widths[3], fs    = disk_info[3][i],
-            m = column_widths[4], media = disk_info[4][i],
-        )
+/// The headers of the columns in the table of disks.
+const DISK_TABLE_HEADERS: [&str; 6] = ["NAME", "DEVICE", "SIZE", "USED", "FS", "MOUNT POINT"];
+
+/// Prints a table describing each disk in `disks`, numbered from 0 so they can be selected by number.
+pub fn print_disks(disks: &[Disk], out: &mut impl Write) -> io::Result<()> {
+    if disks.is_empty() {
+        return writeln!(out, "    No devices were found.");
+    }
+    let rows = disks.iter().map(format_disk_row).collect::<Vec<_>>();
+
+    // Find the length of the longest string in each column (including its header), so the columns can be aligned.
+    let mut column_widths = DISK_TABLE_HEADERS.map(str::len);
+    for row in &rows {
+        for (width, cell) in column_widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    // The headers are indented past the numbers of the rows, and the details are left unaligned since they're last.
+    let index_width = (disks.len() - 1).to_string().len() + 2;
+    write!(out, "    {:index_width$} ", "")?;
+    for (header, width) in DISK_TABLE_HEADERS.iter().zip(column_widths) {
+        write!(out, "{header:<width$}    ")?;
+    }
+    writeln!(out, "DETAILS")?;
+    for (index, (row, disk)) in rows.iter().zip(disks).enumerate() {
+        write!(out, "    {:<index_width$} ", format!("[{index}]"))?;
+        for (cell, width) in row.iter().zip(column_widths) {
+            write!(out, "{cell:<width$}    ")?;
+        }
+        writeln!(out, "{}", format_disk_details(disk))?;
+    }
+    Ok(())
+}
+
+/// Formats the cells of each column in the table of disks, for `disk`. Unknown values are shown as `-`.
+fn format_disk_row(disk: &Disk) -> [String; 6] {
+    let display_path = |path: &Option<PathBuf>| path.as_ref().map_or("-".to_owned(), |path| path.display().to_string());
+
+    // Partitions are indented underneath the disk they're part of.
+    let name = match disk.parent {
+        Some(_) => format!("  {}", disk.name),
+        None => disk.name.clone(),
+    };
+    [
+        name,
+        display_path(&disk.device_node),
+        format_byte_count(disk.size),
+        disk.used_space.map_or("-".to_owned(), format_byte_count),
+        disk.file_system.clone().unwrap_or_else(|| "-".to_owned()),
+        display_path(&disk.mount_point),
+    ]
+}
+
+/// Formats a summary of the hardware details of `disk`: its media type, sector sizes, and model,
+/// and whether it's removable or read-only.
+fn format_disk_details(disk: &Disk) -> String {
+    let mut details = vec![match disk.media_type {
+        MediaType::Ssd => "SSD",
+        MediaType::Hdd => "HDD",
+        MediaType::Unknown => "unknown media",
+    }.to_owned()];
+    details.push(if disk.removable { "external" } else { "internal" }.to_owned());
+    if let Some((logical, physical)) = disk.sector_sizes {
+        details.push(format!("{logical}/{physical} B sectors"));
+    }
+    if disk.read_only {
+        details.push("read-only".to_owned());
+    }
+    match (&disk.model, &disk.serial) {
+        (Some(model), Some(serial)) => details.push(format!("{model} (S/N {serial})")),
+        (Some(model), None) => details.push(model.clone()),
+        (None, Some(serial)) => details.push(format!("S/N {serial}")),
+        (None, None) => {}
     }
+    details.join(", ")
 }
 
-pub fn print_disk_selection_complete() {
-    // TODO print something here!
+/// Prints a confirmation that the selected file/device was opened, along with its length,
+/// whether the kernel is also preventing writes to it, and whether reads bypass the page cache.
+pub fn print_disk_selection_complete(device_length: u64, write_protection: WriteProtection, direct_io: DirectIo) {
+    let protection = match write_protection {
+        WriteProtection::ReadOnly => ", write-protected by the kernel",
+        WriteProtection::Writable | WriteProtection::Unknown => "",
+    };
+    println!("Opened the selected file/device read-only ({device_length} bytes{protection}).");
+    match direct_io {
+        DirectIo::On => println!("Reads bypass the page cache."),
+        DirectIo::Unsupported => println!("It doesn't support direct I/O, so reads go through the page cache."),
+        DirectIo::Off => {}
+    }
     println!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disks() -> Vec<Disk> {
+        let disk = Disk {
+            name: "sda".to_owned(),
+            device_node: Some(PathBuf::from("/dev/sda")),
+            mount_point: None,
+            parent: None,
+            size: 500 << 30,
+            used_space: None,
+            sector_sizes: Some((512, 4096)),
+            file_system: None,
+            media_type: MediaType::Ssd,
+            removable: false,
+            read_only: false,
+            model: Some("Samsung SSD 860".to_owned()),
+            serial: Some("S3Z9NB0K".to_owned()),
+        };
+        let partition = Disk {
+            name: "sda1".to_owned(),
+            device_node: Some(PathBuf::from("/dev/sda1")),
+            mount_point: Some(PathBuf::from("/")),
+            parent: Some("sda".to_owned()),
+            size: 100 << 30,
+            used_space: Some(25 << 30),
+            file_system: Some("ext4".to_owned()),
+            read_only: true,
+            model: None,
+            serial: None,
+            ..disk.clone()
+        };
+        vec![disk, partition]
+    }
+
+    #[test]
+    fn disks_are_printed_in_aligned_columns() {
+        let mut output = Vec::new();
+        print_disks(&disks(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), [
+            "        NAME      DEVICE       SIZE        USED       FS      MOUNT POINT    DETAILS",
+            "    [0] sda       /dev/sda     500.0 GB    -          -       -              SSD, internal, 512/4096 B sectors, Samsung SSD 860 (S/N S3Z9NB0K)",
+            "    [1]   sda1    /dev/sda1    100.0 GB    25.0 GB    ext4    /              SSD, internal, 512/4096 B sectors, read-only",
+        ]);
+    }
+
+    #[test]
+    fn an_empty_list_of_disks_is_reported() {
+        let mut output = Vec::new();
+        print_disks(&[], &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "    No devices were found.\n");
+    }
+}
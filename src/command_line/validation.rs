@@ -0,0 +1,118 @@
+use crate::command::Command;
+use rustyline::hint::{Hint, Hinter, HistoryHinter};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::Context;
+
+/// Checks that commands entered at the command prompt can be parsed before they're submitted.
+/// Invalid commands aren't submitted; instead the parse error is shown underneath them, so they can be corrected.
+#[derive(Default)]
+pub struct CommandValidator;
+
+impl Validator for CommandValidator {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(match parse_error(ctx.input()) {
+            Some(err) => ValidationResult::Invalid(Some(format!("\nerror: {err}"))),
+            None => ValidationResult::Valid(None),
+        })
+    }
+}
+
+/// The hints shown after the cursor at the command prompt.
+pub enum CommandHint {
+    /// The rest of a previously entered command, which can be accepted to complete the line.
+    History(String),
+    /// The reason that the command entered so far is invalid. This is only shown, never inserted into the line.
+    Error(String),
+}
+
+impl Hint for CommandHint {
+    fn display(&self) -> &str {
+        match self {
+            CommandHint::History(hint) | CommandHint::Error(hint) => hint,
+        }
+    }
+
+    fn completion(&self) -> Option<&str> {
+        match self {
+            CommandHint::History(hint) => Some(hint),
+            CommandHint::Error(_) => None,
+        }
+    }
+}
+
+/// Suggests previously entered commands which start with the current line. If there aren't any, and the user has
+/// finished typing a parameter, then the reason that the line is invalid (if it is) is shown instead.
+pub struct CommandHinter {
+    history: HistoryHinter,
+}
+
+impl Default for CommandHinter {
+    fn default() -> Self {
+        CommandHinter { history: HistoryHinter {} }
+    }
+}
+
+impl Hinter for CommandHinter {
+    type Hint = CommandHint;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<CommandHint> {
+        if let Some(hint) = self.history.hint(line, pos, ctx) {
+            return Some(CommandHint::History(hint));
+        }
+        error_hint(line, pos).map(CommandHint::Error)
+    }
+}
+
+/// Returns a hint describing why `line` is invalid, but only once the user has finished typing a parameter (so that
+/// partially typed words aren't reported as errors) and only when the cursor is at the end of the line.
+fn error_hint(line: &str, pos: usize) -> Option<String> {
+    if pos < line.len() || line.trim().is_empty() || !line.ends_with(char::is_whitespace) {
+        return None;
+    }
+    parse_error(line).map(|err| format!("  <- {err}"))
+}
+
+/// Parses `line` as a command, and returns the error if it's invalid.
+///
+/// The pointer to the help command is removed from the error, since the error is shown while the line is being edited,
+/// and so there's no way to follow it anyway.
+fn parse_error(line: &str) -> Option<String> {
+    let err = line.parse::<Command>().err()?;
+    let concise = match err.find(" Enter 'help") {
+        Some(index) => err[..index].to_owned(),
+        None => err,
+    };
+    Some(concise)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_commands_are_reported_without_the_help_pointer() {
+        assert_eq!(parse_error("seek absolute 512"), None);
+        assert_eq!(parse_error(""), None);
+        assert_eq!(parse_error("seak absolute 512"), Some("Unknown command: 'seak'.".to_owned()));
+        assert_eq!(
+            parse_error("find"),
+            Some("Missing find mode: 'nonzero', 'bytes', or 'string'.".to_owned()),
+        );
+        assert_eq!(parse_error("print 1 2"), Some("Unexpected extra parameter at column 9: '2'.".to_owned()));
+    }
+
+    #[test]
+    fn errors_are_only_hinted_after_a_parameter_is_finished() {
+        assert_eq!(error_hint("seek absolute ", 14), Some("  <- Missing offset/position to seek to.".to_owned()));
+        assert_eq!(error_hint("seek absolute", 13), None);
+        assert_eq!(error_hint("seek absolute ", 5), None);
+        assert_eq!(error_hint("seek absolute 5 ", 16), None);
+        assert_eq!(error_hint("   ", 3), None);
+    }
+
+    #[test]
+    fn error_hints_are_never_inserted() {
+        assert_eq!(CommandHint::Error("  <- error".to_owned()).completion(), None);
+        assert_eq!(CommandHint::History("ute 512".to_owned()).completion(), Some("ute 512"));
+    }
+}
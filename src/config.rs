@@ -0,0 +1,650 @@
+use crate::command::Config;
+use crate::device::{self, FileId};
+use crate::disk_info::format_byte_count;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The default number of bytes in a single sector.
+pub const DEFAULT_SECTOR_SIZE: u64 = 512;
+
+/// The default number of bytes read from the device at once while scanning.
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// The default number of bytes shown in each row of a hexdump.
+pub const DEFAULT_PRINT_WIDTH: usize = 16;
+
+/// The path of the settings file, relative to the user's config directory.
+const CONFIG_FILE_PATH: &str = "raw-reader/config";
+
+/// The path of the directory that the command history is saved in, relative to the user's state directory.
+const STATE_DIR_PATH: &str = "raw-reader";
+
+/// The limits on each of the numeric settings (inclusive).
+const SECTOR_SIZE_RANGE: (u64, u64) = (512, 64 * 1024);
+const WORKER_THREADS_RANGE: (usize, usize) = (1, 256);
+const PRINT_WIDTH_RANGE: (usize, usize) = (1, 64);
+const CHUNK_SIZE_RANGE: (usize, usize) = (64 * 1024, 64 * 1024 * 1024);
+
+/// Chunk sizes must be a multiple of this, so that every chunk is a whole number of sectors (for any sector size).
+const CHUNK_SIZE_GRANULARITY: usize = SECTOR_SIZE_RANGE.1 as usize;
+
+/// The base that offsets are displayed in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DisplayBase {
+    Hexadecimal,
+    Decimal,
+}
+
+impl DisplayBase {
+    /// Formats an offset for use in a column of output, so that offsets line up with each other.
+    pub fn format_offset(self, offset: u64) -> String {
+        match self {
+            DisplayBase::Hexadecimal => format!("{offset:#014x}"),
+            DisplayBase::Decimal => format!("{offset:>14}"),
+        }
+    }
+}
+
+impl FromStr for DisplayBase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hex" | "16" => Ok(DisplayBase::Hexadecimal),
+            "dec" | "10" => Ok(DisplayBase::Decimal),
+            _ => Err(format!("Invalid display base: '{s}'. It must be 'hex' or 'dec'.")),
+        }
+    }
+}
+
+impl fmt::Display for DisplayBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DisplayBase::Hexadecimal => "hex",
+            DisplayBase::Decimal => "dec",
+        })
+    }
+}
+
+/// The direction that searches move through the device in, starting from the cursor.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+impl FromStr for SearchDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "forward" => Ok(SearchDirection::Forward),
+            "backward" => Ok(SearchDirection::Backward),
+            _ => Err(format!("Invalid search direction: '{s}'. It must be 'forward' or 'backward'.")),
+        }
+    }
+}
+
+impl fmt::Display for SearchDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SearchDirection::Forward => "forward",
+            SearchDirection::Backward => "backward",
+        })
+    }
+}
+
+/// What happens when a line of a script fails.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorMode {
+    /// The script stops at the first line which fails.
+    Stop,
+    /// Errors are reported, and the script carries on with the next line.
+    Continue,
+}
+
+impl FromStr for ErrorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stop" => Ok(ErrorMode::Stop),
+            "continue" => Ok(ErrorMode::Continue),
+            _ => Err(format!("Invalid error mode: '{s}'. It must be 'stop' or 'continue'.")),
+        }
+    }
+}
+
+impl fmt::Display for ErrorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorMode::Stop => "stop",
+            ErrorMode::Continue => "continue",
+        })
+    }
+}
+
+/// How the device is read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadMode {
+    /// Reads bypass the page cache (with `O_DIRECT`), if the device supports it.
+    Direct,
+    /// Reads go through the page cache.
+    Buffered,
+}
+
+impl FromStr for ReadMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "direct" => Ok(ReadMode::Direct),
+            "buffered" => Ok(ReadMode::Buffered),
+            _ => Err(format!("Invalid read mode: '{s}'. It must be 'direct' or 'buffered'.")),
+        }
+    }
+}
+
+impl fmt::Display for ReadMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReadMode::Direct => "direct",
+            ReadMode::Buffered => "buffered",
+        })
+    }
+}
+
+/// Identifies one of the settings.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SettingKey {
+    SectorSize,
+    ChunkSize,
+    WorkerThreads,
+    PrintWidth,
+    DisplayBase,
+    SearchDirection,
+    ScriptErrors,
+    ReadMode,
+}
+
+/// A new value for one of the settings.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Setting {
+    SectorSize(u64),
+    ChunkSize(usize),
+    WorkerThreads(usize),
+    PrintWidth(usize),
+    DisplayBase(DisplayBase),
+    SearchDirection(SearchDirection),
+    ScriptErrors(ErrorMode),
+    ReadMode(ReadMode),
+}
+
+/// Every setting, paired with the name used to refer to it. Settings are always listed in this order.
+pub const SETTINGS: [(&str, SettingKey); 8] = [
+    ("sector-size", SettingKey::SectorSize),
+    ("chunk-size", SettingKey::ChunkSize),
+    ("worker-threads", SettingKey::WorkerThreads),
+    ("print-width", SettingKey::PrintWidth),
+    ("display-base", SettingKey::DisplayBase),
+    ("search-direction", SettingKey::SearchDirection),
+    ("script-errors", SettingKey::ScriptErrors),
+    ("read-mode", SettingKey::ReadMode),
+];
+
+impl SettingKey {
+    /// Returns the name used to refer to this setting.
+    pub fn name(self) -> &'static str {
+        SETTINGS.iter().find(|(_, key)| *key == self).map(|(name, _)| *name).unwrap()
+    }
+}
+
+impl FromStr for SettingKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        SETTINGS.iter().find(|(name, _)| *name == lowercase).map(|(_, key)| *key).ok_or_else(|| {
+            format!("Unknown setting: '{s}'. Enter 'help config' for a list of settings.")
+        })
+    }
+}
+
+impl Setting {
+    /// Parses `value` as a new value for the setting identified by `key`.
+    pub fn parse(key: SettingKey, value: &str) -> Result<Self, String> {
+        let name = key.name();
+        match key {
+            SettingKey::SectorSize => parse_power_of_two(name, value, SECTOR_SIZE_RANGE).map(Setting::SectorSize),
+            SettingKey::ChunkSize => {
+                let value = parse_in_range(name, value, CHUNK_SIZE_RANGE)?;
+                if !value.is_multiple_of(CHUNK_SIZE_GRANULARITY) {
+                    return Err(format!("Invalid value for '{name}': {value}. It must be a multiple of {CHUNK_SIZE_GRANULARITY}."));
+                }
+                Ok(Setting::ChunkSize(value))
+            }
+            SettingKey::WorkerThreads => parse_in_range(name, value, WORKER_THREADS_RANGE).map(Setting::WorkerThreads),
+            SettingKey::PrintWidth => parse_in_range(name, value, PRINT_WIDTH_RANGE).map(Setting::PrintWidth),
+            SettingKey::DisplayBase => value.parse().map(Setting::DisplayBase),
+            SettingKey::SearchDirection => value.parse().map(Setting::SearchDirection),
+            SettingKey::ScriptErrors => value.parse().map(Setting::ScriptErrors),
+            SettingKey::ReadMode => value.parse().map(Setting::ReadMode),
+        }
+    }
+}
+
+/// The settings which control how the device is read, scanned, and displayed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Settings {
+    pub sector_size: u64,
+    pub chunk_size: usize,
+    pub worker_threads: usize,
+    pub print_width: usize,
+    pub display_base: DisplayBase,
+    pub search_direction: SearchDirection,
+    pub script_errors: ErrorMode,
+    pub read_mode: ReadMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            sector_size: DEFAULT_SECTOR_SIZE,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            worker_threads: default_worker_threads(),
+            print_width: DEFAULT_PRINT_WIDTH,
+            display_base: DisplayBase::Hexadecimal,
+            search_direction: SearchDirection::Forward,
+            script_errors: ErrorMode::Stop,
+            read_mode: ReadMode::Direct,
+        }
+    }
+}
+
+impl Settings {
+    /// Changes a single setting to its new value.
+    pub fn apply(&mut self, setting: Setting) {
+        match setting {
+            Setting::SectorSize(value) => self.sector_size = value,
+            Setting::ChunkSize(value) => self.chunk_size = value,
+            Setting::WorkerThreads(value) => self.worker_threads = value,
+            Setting::PrintWidth(value) => self.print_width = value,
+            Setting::DisplayBase(value) => self.display_base = value,
+            Setting::SearchDirection(value) => self.search_direction = value,
+            Setting::ScriptErrors(value) => self.script_errors = value,
+            Setting::ReadMode(value) => self.read_mode = value,
+        }
+    }
+
+    /// Resets the setting identified by `key` to its default value, or resets every setting if `key` is `None`.
+    pub fn reset(&mut self, key: Option<SettingKey>) {
+        let defaults = Settings::default();
+        match key {
+            Some(SettingKey::SectorSize) => self.sector_size = defaults.sector_size,
+            Some(SettingKey::ChunkSize) => self.chunk_size = defaults.chunk_size,
+            Some(SettingKey::WorkerThreads) => self.worker_threads = defaults.worker_threads,
+            Some(SettingKey::PrintWidth) => self.print_width = defaults.print_width,
+            Some(SettingKey::DisplayBase) => self.display_base = defaults.display_base,
+            Some(SettingKey::SearchDirection) => self.search_direction = defaults.search_direction,
+            Some(SettingKey::ScriptErrors) => self.script_errors = defaults.script_errors,
+            Some(SettingKey::ReadMode) => self.read_mode = defaults.read_mode,
+            None => *self = defaults,
+        }
+    }
+
+    /// Returns the current value of the setting identified by `key`, formatted the same way it's entered.
+    pub fn value_of(&self, key: SettingKey) -> String {
+        match key {
+            SettingKey::SectorSize => self.sector_size.to_string(),
+            SettingKey::ChunkSize => self.chunk_size.to_string(),
+            SettingKey::WorkerThreads => self.worker_threads.to_string(),
+            SettingKey::PrintWidth => self.print_width.to_string(),
+            SettingKey::DisplayBase => self.display_base.to_string(),
+            SettingKey::SearchDirection => self.search_direction.to_string(),
+            SettingKey::ScriptErrors => self.script_errors.to_string(),
+            SettingKey::ReadMode => self.read_mode.to_string(),
+        }
+    }
+
+    /// Executes a `config` command against these settings, printing any output to `out`. Settings are never saved to
+    /// the file/device identified by `device`.
+    pub fn configure(&mut self, config: &Config, device: Option<&FileId>, out: &mut impl Write) -> Result<(), String> {
+        match config {
+            Config::Show => self.print(out),
+            Config::Set(setting) => {
+                self.apply(*setting);
+                Ok(())
+            }
+            Config::Reset(name) => {
+                self.reset(*name);
+                Ok(())
+            }
+            Config::Save(path) => {
+                let path = path_or_default(path.as_deref())?;
+                self.save(&path, device)?;
+                writeln!(out, "Saved the settings to '{}'.", path.display()).map_err(|err| err.to_string())
+            }
+            Config::Load(path) => {
+                let path = path_or_default(path.as_deref())?;
+                *self = Settings::load(&path)?;
+                writeln!(out, "Loaded the settings from '{}'.", path.display()).map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    /// Reads settings from the file at `path`. Any settings which aren't listed in the file keep their default value.
+    pub fn load(path: &Path) -> Result<Settings, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read the settings file '{}': {err}", path.display()))?;
+        Settings::parse_file(&contents).map_err(|err| format!("Invalid settings file '{}': {err}", path.display()))
+    }
+
+    /// Writes every setting to the file at `path`, creating its parent directories if necessary. Refuses to write to
+    /// the file/device identified by `device`, or any other device.
+    pub fn save(&self, path: &Path, device: Option<&FileId>) -> Result<(), String> {
+        let result = device::check_output_path(path, device).and_then(|_| match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+            _ => Ok(()),
+        });
+        result.and_then(|_| fs::write(path, self.to_file_contents()))
+            .map_err(|err| format!("Failed to save the settings file '{}': {err}", path.display()))
+    }
+
+    /// Parses the contents of a settings file. Each non-empty line is either a comment starting with `#`,
+    /// or a setting written as `name = value`.
+    fn parse_file(contents: &str) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Errors are prefixed with the line they occurred on, so they can be found in the file.
+            let parse_line = || {
+                let (name, value) = line.split_once('=')
+                    .ok_or_else(|| format!("Expected 'name = value', but found '{line}'."))?;
+                Setting::parse(name.trim().parse()?, value.trim())
+            };
+            settings.apply(parse_line().map_err(|err| format!("line {}: {err}", index + 1))?);
+        }
+        Ok(settings)
+    }
+
+    /// Formats every setting in the same format that's read by `parse_file`.
+    fn to_file_contents(&self) -> String {
+        let mut contents = String::from("# Settings for raw-reader. Enter 'help config' in raw-reader for an explanation of each one.\n");
+        for (name, key) in SETTINGS {
+            contents.push_str(&format!("{name} = {}\n", self.value_of(key)));
+        }
+        contents
+    }
+
+    /// Prints the current value of every setting.
+    fn print(&self, out: &mut impl Write) -> Result<(), String> {
+        let width = SETTINGS.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, key) in SETTINGS {
+            let mut value = self.value_of(key);
+            if key == SettingKey::ChunkSize {
+                value = format!("{value} ({})", format_byte_count(self.chunk_size as u64));
+            }
+            writeln!(out, "    {name:<width$}    {value}").map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of the settings file that's loaded at startup: `raw-reader/config` in `$XDG_CONFIG_HOME`,
+/// or in `$HOME/.config` if that isn't set. Returns `None` if neither environment variable is set.
+pub fn default_config_path() -> Option<PathBuf> {
+    config_path_from(std::env::var_os("XDG_CONFIG_HOME"), std::env::var_os("HOME"))
+}
+
+/// Works out the path of the settings file from the values of `$XDG_CONFIG_HOME` and `$HOME`.
+fn config_path_from(xdg_config_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    Some(base_dir_from(xdg_config_home, home, ".config")?.join(CONFIG_FILE_PATH))
+}
+
+/// Returns the path of the directory that state which isn't worth backing up (like the command history) is saved in:
+/// `raw-reader` in `$XDG_STATE_HOME`, or in `$HOME/.local/state` if that isn't set. Returns `None` if neither
+/// environment variable is set.
+pub fn default_state_dir() -> Option<PathBuf> {
+    state_dir_from(std::env::var_os("XDG_STATE_HOME"), std::env::var_os("HOME"))
+}
+
+/// Works out the path of the state directory from the values of `$XDG_STATE_HOME` and `$HOME`.
+fn state_dir_from(xdg_state_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    Some(base_dir_from(xdg_state_home, home, ".local/state")?.join(STATE_DIR_PATH))
+}
+
+/// Returns the XDG base directory stored in `xdg_dir`, or `default` in the home directory if that isn't set.
+/// Relative paths in `xdg_dir` are ignored, as required by the XDG base directory specification.
+fn base_dir_from(xdg_dir: Option<OsString>, home: Option<OsString>, default: &str) -> Option<PathBuf> {
+    match xdg_dir.map(PathBuf::from) {
+        Some(path) if path.is_absolute() => Some(path),
+        _ => Some(PathBuf::from(home.filter(|home| !home.is_empty())?).join(default)),
+    }
+}
+
+/// Returns `path`, or the default settings file path if one wasn't provided.
+fn path_or_default(path: Option<&Path>) -> Result<PathBuf, String> {
+    path.map(Path::to_owned).or_else(default_config_path).ok_or_else(|| {
+        "Couldn't find the config directory, since neither $XDG_CONFIG_HOME nor $HOME are set. Provide a path instead.".to_owned()
+    })
+}
+
+/// Returns the number of worker threads to use by default: 1 per available CPU core.
+fn default_worker_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |count| count.get()).min(WORKER_THREADS_RANGE.1)
+}
+
+/// Parses `value` as a non-negative number, for the setting called `name`.
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("Invalid value for '{name}': '{value}' is not a valid number."))
+}
+
+/// Parses `value` as a number within `range` (inclusive), for the setting called `name`.
+fn parse_in_range<T: FromStr + PartialOrd + fmt::Display>(name: &str, value: &str, range: (T, T)) -> Result<T, String> {
+    let value = parse_number::<T>(name, value)?;
+    if value < range.0 || value > range.1 {
+        return Err(format!("Invalid value for '{name}': {value}. It must be between {} and {}.", range.0, range.1));
+    }
+    Ok(value)
+}
+
+/// Parses `value` as a power of 2 within `range` (inclusive), for the setting called `name`.
+fn parse_power_of_two(name: &str, value: &str, range: (u64, u64)) -> Result<u64, String> {
+    let value = parse_in_range(name, value, range)?;
+    if !value.is_power_of_two() {
+        return Err(format!("Invalid value for '{name}': {value}. It must be a power of 2."));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_can_be_set_and_reset() {
+        let mut settings = Settings::default();
+        for (name, value) in [
+            ("sector-size", "4096"),
+            ("Chunk-Size", "196608"),
+            ("worker-threads", "3"),
+            ("print-width", "8"),
+            ("display-base", "DEC"),
+            ("search-direction", "backward"),
+            ("script-errors", "Continue"),
+            ("read-mode", "Buffered"),
+        ] {
+            settings.apply(Setting::parse(name.parse().unwrap(), value).unwrap());
+        }
+        assert_eq!(settings, Settings {
+            sector_size: 4096,
+            chunk_size: 196608,
+            worker_threads: 3,
+            print_width: 8,
+            display_base: DisplayBase::Decimal,
+            search_direction: SearchDirection::Backward,
+            script_errors: ErrorMode::Continue,
+            read_mode: ReadMode::Buffered,
+        });
+
+        settings.reset(Some(SettingKey::PrintWidth));
+        assert_eq!(settings.print_width, DEFAULT_PRINT_WIDTH);
+        assert_eq!(settings.sector_size, 4096);
+
+        settings.reset(None);
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let test_cases = [
+            ("sector-size", "1000", "It must be a power of 2"),
+            ("sector-size", "256", "It must be between 512 and 65536"),
+            ("sector-size", "-512", "'-512' is not a valid number"),
+            ("chunk-size", "1000", "It must be between 65536 and 67108864"),
+            ("chunk-size", "100000", "It must be a multiple of 65536"),
+            ("chunk-size", "134217728", "It must be between 65536 and 67108864"),
+            ("worker-threads", "0", "It must be between 1 and 256"),
+            ("print-width", "65", "It must be between 1 and 64"),
+            ("display-base", "8", "Invalid display base"),
+            ("search-direction", "up", "Invalid search direction"),
+            ("script-errors", "ignore", "Invalid error mode"),
+            ("read-mode", "cached", "Invalid read mode"),
+        ];
+        for (name, value, expected_error) in test_cases {
+            let error = Setting::parse(name.parse().unwrap(), value).unwrap_err();
+            assert!(error.contains(expected_error), "'{} {}' produced the wrong error: {}", name, value, error);
+        }
+    }
+
+    #[test]
+    fn every_setting_round_trips_through_its_value() {
+        let settings = Settings::default();
+        for (name, key) in SETTINGS {
+            assert_eq!(name.parse::<SettingKey>(), Ok(key));
+            let mut parsed = Settings { print_width: 1, worker_threads: 1, ..settings.clone() };
+            parsed.apply(Setting::parse(key, &settings.value_of(key)).unwrap());
+            assert_eq!(parsed.value_of(key), settings.value_of(key));
+        }
+        assert!("sector-sizes".parse::<SettingKey>().unwrap_err().contains("Unknown setting: 'sector-sizes'"));
+    }
+
+    #[test]
+    fn settings_files_round_trip() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("profiles/nvme");
+        let settings = Settings {
+            sector_size: 4096,
+            display_base: DisplayBase::Decimal,
+            ..Settings::default()
+        };
+
+        let mut output = Vec::new();
+        settings.clone().configure(&Config::Save(Some(path.clone())), None, &mut output).unwrap();
+        let mut loaded = Settings::default();
+        loaded.configure(&Config::Load(Some(path.clone())), None, &mut output).unwrap();
+        assert_eq!(loaded, settings);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("Saved the settings to '{0}'.\nLoaded the settings from '{0}'.\n", path.display()),
+        );
+    }
+
+    #[test]
+    fn settings_are_never_saved_to_the_device_being_inspected() {
+        let image = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(image.path(), b"evidence").unwrap();
+        let device = FileId::of(image.path()).unwrap();
+
+        let save = Config::Save(Some(image.path().to_owned()));
+        let error = Settings::default().configure(&save, Some(&device), &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            error,
+            format!(
+                "Failed to save the settings file '{}': it's the file/device being inspected, which is never written to",
+                image.path().display(),
+            ),
+        );
+        assert_eq!(std::fs::read(image.path()).unwrap(), b"evidence");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn settings_are_never_saved_to_a_device() {
+        let error = Settings::default().save(Path::new("/dev/null"), None).unwrap_err();
+        assert_eq!(error, "Failed to save the settings file '/dev/null': it's a device, which is never written to");
+    }
+
+    #[test]
+    fn settings_files_can_contain_comments_and_partial_settings() {
+        let contents = "\
+            # 4K-sector NVMe drives\n\
+            \n\
+            sector-size = 4096\n\
+            \x20   Search-Direction=backward   \n";
+        assert_eq!(Settings::parse_file(contents), Ok(Settings {
+            sector_size: 4096,
+            search_direction: SearchDirection::Backward,
+            ..Settings::default()
+        }));
+
+        let test_cases = [
+            ("sector-size 4096", "line 1: Expected 'name = value', but found 'sector-size 4096'."),
+            ("# comment\nsectors = 512", "line 2: Unknown setting: 'sectors'"),
+            ("print-width = 8\nprint-width = 0", "line 2: Invalid value for 'print-width': 0"),
+        ];
+        for (contents, expected_error) in test_cases {
+            let error = Settings::parse_file(contents).unwrap_err();
+            assert!(error.starts_with(expected_error), "'{}' produced the wrong error: {}", contents, error);
+        }
+    }
+
+    #[test]
+    fn missing_settings_files_are_reported() {
+        let directory = tempfile::tempdir().unwrap();
+        let error = Settings::load(&directory.path().join("missing")).unwrap_err();
+        assert!(error.starts_with("Failed to read the settings file"), "wrong error: {}", error);
+    }
+
+    #[test]
+    fn config_path_follows_the_xdg_specification() {
+        let path = |xdg: Option<&str>, home: Option<&str>| config_path_from(xdg.map(OsString::from), home.map(OsString::from));
+        assert_eq!(path(Some("/xdg"), Some("/home/a")), Some(PathBuf::from("/xdg/raw-reader/config")));
+        assert_eq!(path(None, Some("/home/a")), Some(PathBuf::from("/home/a/.config/raw-reader/config")));
+        assert_eq!(path(Some("relative"), Some("/home/a")), Some(PathBuf::from("/home/a/.config/raw-reader/config")));
+        assert_eq!(path(Some(""), Some("")), None);
+        assert_eq!(path(None, None), None);
+
+        let state_dir = |xdg: Option<&str>, home: Option<&str>| state_dir_from(xdg.map(OsString::from), home.map(OsString::from));
+        assert_eq!(state_dir(Some("/xdg"), Some("/home/a")), Some(PathBuf::from("/xdg/raw-reader")));
+        assert_eq!(state_dir(Some("state"), Some("/home/a")), Some(PathBuf::from("/home/a/.local/state/raw-reader")));
+        assert_eq!(state_dir(None, Some("")), None);
+    }
+
+    #[test]
+    fn show_lists_every_setting() {
+        let mut settings = Settings { worker_threads: 4, ..Settings::default() };
+
+        let mut output = Vec::new();
+        settings.configure(&Config::Show, None, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "    sector-size         512\n\
+             \x20   chunk-size          4194304 (4.0 MB)\n\
+             \x20   worker-threads      4\n\
+             \x20   print-width         16\n\
+             \x20   display-base        hex\n\
+             \x20   search-direction    forward\n\
+             \x20   script-errors       stop\n\
+             \x20   read-mode           direct\n",
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! A record of the parts of a device which couldn't be read.
+//!
+//! Unreadable data is recorded as byte ranges rather than sectors, so the record stays accurate if the sector size
+//! is changed after it was made. It can be turned into a `SectorMap` for any sector size, in which every sector that
+//! overlaps an unreadable range is marked as occupied.
+
+use crate::data::sector_map::{SectorMap, SectorMapBuilder};
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Range;
+
+/// The byte ranges of a device which couldn't be read, along with the most recent error that was encountered.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BadSectorLog {
+    /// Maps the start of each unreadable range to its end. Ranges never overlap or touch, since they're merged as
+    /// they're recorded.
+    ranges: BTreeMap<u64, u64>,
+    last_error: Option<String>,
+}
+
+impl BadSectorLog {
+    /// Records that the bytes in `range` couldn't be read, because of `error`.
+    pub fn record(&mut self, range: Range<u64>, error: &io::Error) {
+        self.last_error = Some(error.to_string());
+        if range.is_empty() {
+            return;
+        }
+
+        // Extend the range to cover the range before it, if the two overlap or touch.
+        let (mut start, mut end) = (range.start, range.end);
+        if let Some((&previous_start, &previous_end)) = self.ranges.range(..=start).next_back() {
+            if previous_end >= start {
+                start = previous_start;
+                end = end.max(previous_end);
+            }
+        }
+
+        // Then absorb every range which starts inside of it (or right at its end).
+        let absorbed = self.ranges.range(start..=end).map(|(&start, _)| start).collect::<Vec<_>>();
+        for absorbed_start in absorbed {
+            let absorbed_end = self.ranges.remove(&absorbed_start).expect("range was just found");
+            end = end.max(absorbed_end);
+        }
+        self.ranges.insert(start, end);
+    }
+
+    /// Returns whether nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns the total number of bytes which couldn't be read.
+    pub fn byte_count(&self) -> u64 {
+        self.ranges().map(|range| range.end - range.start).sum()
+    }
+
+    /// Returns every unreadable byte range, in ascending order.
+    pub fn ranges(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.ranges.iter().map(|(&start, &end)| start..end)
+    }
+
+    /// Returns a description of the most recent error that was recorded, if any.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Builds a map with the specified sector size, in which every sector containing unreadable bytes is occupied.
+    pub fn to_sector_map(&self, sector_size: u32) -> SectorMap {
+        let mut builder = SectorMapBuilder::new(sector_size);
+        let sector_size = u64::from(sector_size);
+        let mut next_sector = 0;
+        for range in self.ranges() {
+            // Ranges that were recorded with a smaller sector size can share a sector, which is only pushed once.
+            let start = (range.start / sector_size).max(next_sector);
+            let end = ceil_divide!(range.end, sector_size);
+            if end <= start {
+                continue;
+            }
+            builder.push_run(start - next_sector, false);
+            builder.push_run(end - start, true);
+            next_sector = end;
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records each of `ranges` in a new log.
+    fn record_all(ranges: &[Range<u64>]) -> BadSectorLog {
+        let mut log = BadSectorLog::default();
+        for range in ranges {
+            log.record(range.clone(), &io::Error::other("bad sector"));
+        }
+        log
+    }
+
+    #[test]
+    fn overlapping_and_touching_ranges_are_merged() {
+        let log = record_all(&[1024..1536, 0..512, 4096..4608, 512..1024, 3000..4200, 8192..8704, 4500..5000]);
+        assert_eq!(log.ranges().collect::<Vec<_>>(), vec![0..1536, 3000..5000, 8192..8704]);
+        assert_eq!(log.byte_count(), 1536 + 2000 + 512);
+        assert_eq!(log.last_error(), Some("bad sector"));
+
+        // Ranges which are already covered don't change anything.
+        let mut covered = log.clone();
+        covered.record(3500..3600, &io::Error::other("bad sector"));
+        assert_eq!(covered, log);
+    }
+
+    #[test]
+    fn sector_maps_can_be_built_with_any_sector_size() {
+        let log = record_all(&[512..1024, 2048..2560, 8192..12288]);
+        assert_eq!(log.to_sector_map(512).extents(), vec![1..2, 4..5, 16..24]);
+
+        // With larger sectors, the first 2 ranges share a sector.
+        let map = log.to_sector_map(4096);
+        assert_eq!(map.sector_size(), 4096);
+        assert_eq!(map.extents(), vec![0..1, 2..3]);
+    }
+
+    #[test]
+    fn empty_logs_produce_empty_maps() {
+        let log = BadSectorLog::default();
+        assert!(log.is_empty());
+        assert_eq!(log.last_error(), None);
+        assert!(log.to_sector_map(512).extents().is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::data::pod::{self, ByteOrder, EndianView, Pod};
+use std::alloc::{self, Layout};
+use std::hash::{Hash, Hasher};
+use std::ptr::NonNull;
+
+/// The size of a memory page on almost every platform. Buffers aligned to this can be used as the destination of
+/// direct (`O_DIRECT`) reads, since it's a multiple of every logical block size that direct I/O requires.
+pub const PAGE_SIZE: usize = 4096;
+
+/// An array of bytes on the heap, whose length and alignment are both chosen at runtime. The first byte of the
+/// buffer is guaranteed to be aligned to the buffer's alignment, which lets it be viewed as a buffer of other
+/// primitive types, or read into directly by direct I/O (when aligned to `PAGE_SIZE`).
+///
+/// The buffer never lives on the stack, so it can be as large as necessary.
+///
+/// # Examples
+///
+/// ```
+/// # use raw_reader::data::dyn_aligned_buffer::{DynAlignedBuffer, PAGE_SIZE};
+/// // Allocate a 10 KiB buffer on the heap, aligned to a page boundary.
+/// let mut buffer = DynAlignedBuffer::new(10 * 1024, PAGE_SIZE);
+/// assert_eq!(buffer.as_ptr() as usize % PAGE_SIZE, 0);
+///
+/// // Set the 17th byte in the buffer to a non-zero value.
+/// buffer[16] = 5;
+///
+/// // View the buffer as u128s instead of u8s.
+/// let u128_view = buffer.view_as::<u128>();
+/// assert_eq!(u128_view.len(), 640);
+/// assert_eq!(u128_view[0], 0); // Corresponds to bytes 0~15
+/// assert_eq!(u128_view[1], 5); // Corresponds to bytes 16~31
+/// ```
+pub struct DynAlignedBuffer {
+    pointer: NonNull<u8>,
+    length: usize,
+    alignment: usize,
+}
+
+// The buffer owns its allocation exclusively, just like a `Box<[u8]>` does.
+unsafe impl Send for DynAlignedBuffer {}
+unsafe impl Sync for DynAlignedBuffer {}
+
+impl DynAlignedBuffer {
+    /// Allocates a new buffer that is `length` bytes long, and aligned to `alignment` bytes, with every byte set to 0.
+    ///
+    /// # Panics
+    ///
+    /// If `alignment` isn't a power of 2, the allocation fails, or `length` is too large to be allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use raw_reader::data::dyn_aligned_buffer::DynAlignedBuffer;
+    /// // Allocates a buffer that is 64 MB long, and aligned to 16 bytes.
+    /// let buffer = DynAlignedBuffer::new(0x4000000, 16);
+    /// ```
+    pub fn new(length: usize, alignment: usize) -> Self {
+        let layout = Self::layout(length, alignment);
+        // This is safe because the layout is never zero-sized.
+        let pointer = unsafe { alloc::alloc_zeroed(layout) };
+        let pointer = NonNull::new(pointer).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        DynAlignedBuffer { pointer, length, alignment }
+    }
+
+    /// Returns the layout of the allocation behind a buffer with the provided length and alignment. Allocations can't
+    /// be empty, so empty buffers still allocate a single byte (which gives them a properly aligned address).
+    fn layout(length: usize, alignment: usize) -> Layout {
+        Layout::from_size_align(length.max(1), alignment).expect("buffer alignment must be a power of 2")
+    }
+
+    /// Returns a view into the buffer as a slice of the specified type. This slice spans the entire buffer, except for
+    /// any trailing bytes which don't fill a whole `T`. Only plain integer and floating point types (`Pod` types) can
+    /// be specified, and only ones with an alignment that divides the buffer's alignment.
+    ///
+    /// The values are read in the host's native byte order, so the same bytes produce different values on
+    /// little-endian and big-endian hosts. To decode values stored in a specific byte order (like the fields of an
+    /// on-disk structure), use `view_as_le` or `view_as_be` instead.
+    ///
+    /// # Panics
+    ///
+    /// If the specified viewing type has an alignment that doesn't divide the buffer's alignment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use raw_reader::data::dyn_aligned_buffer::DynAlignedBuffer;
+    /// let buffer = DynAlignedBuffer::new(64, 16);
+    ///
+    /// let i16_view = buffer.view_as::<i16>();     // View the buffer as &[i16]
+    /// assert_eq!(i16_view.len(), 64 / 2);
+    ///
+    /// let u64_view: &[u64] = buffer.view_as();    // Alternate syntax. Views the buffer as &[u64]
+    /// assert_eq!(u64_view.len(), 64 / 8);
+    /// ```
+    pub fn view_as<T: Pod>(&self) -> &[T] {
+        self.check_alignment_of::<T>();
+        pod::cast_slice(self)
+    }
+
+    /// Checks that the buffer can be viewed as `T`, which is true of every type for buffers aligned to 16 bytes or more.
+    fn check_alignment_of<T>(&self) {
+        assert!(self.alignment.is_multiple_of(std::mem::align_of::<T>()), "type must have an alignment that divides the buffer's alignment");
+    }
+}
+
+// Reads only ever view buffers as native-endian slices so far, but the mutable and endian-aware views are kept as part
+// of the buffer's API.
+#[allow(dead_code)]
+impl DynAlignedBuffer {
+    /// Returns a mutable view into the buffer as a slice of the specified type, in the same way as `view_as`.
+    /// Values written through the view are stored in the host's native byte order.
+    ///
+    /// # Panics
+    ///
+    /// If the specified viewing type has an alignment that doesn't divide the buffer's alignment.
+    pub fn view_as_mut<T: Pod>(&mut self) -> &mut [T] {
+        self.check_alignment_of::<T>();
+        pod::cast_slice_mut(self)
+    }
+
+    /// Returns a view into the buffer as values of the specified type, which are stored in little-endian order.
+    /// The values are the same on every host.
+    ///
+    /// # Panics
+    ///
+    /// If the specified viewing type has an alignment that doesn't divide the buffer's alignment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use raw_reader::data::dyn_aligned_buffer::DynAlignedBuffer;
+    /// let mut buffer = DynAlignedBuffer::new(16, 16);
+    /// buffer[..4].copy_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+    ///
+    /// assert_eq!(buffer.view_as_le::<u32>().get(0), Some(0x04030201));
+    /// assert_eq!(buffer.view_as_be::<u32>().get(0), Some(0x01020304));
+    /// ```
+    pub fn view_as_le<T: Pod>(&self) -> EndianView<'_, T> {
+        EndianView::new(self.view_as(), ByteOrder::LittleEndian)
+    }
+
+    /// Returns a view into the buffer as values of the specified type, which are stored in big-endian order.
+    /// The values are the same on every host.
+    ///
+    /// # Panics
+    ///
+    /// If the specified viewing type has an alignment that doesn't divide the buffer's alignment.
+    pub fn view_as_be<T: Pod>(&self) -> EndianView<'_, T> {
+        EndianView::new(self.view_as(), ByteOrder::BigEndian)
+    }
+}
+
+impl Drop for DynAlignedBuffer {
+    fn drop(&mut self) {
+        // This is safe because the pointer was allocated by `new` with exactly this layout.
+        unsafe { alloc::dealloc(self.pointer.as_ptr(), Self::layout(self.length, self.alignment)) }
+    }
+}
+
+// Allows the compiler to implicitly convert this to an `&[u8]`.
+impl std::ops::Deref for DynAlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // This is safe because the first `length` bytes of the allocation were initialized by `new`.
+        unsafe { std::slice::from_raw_parts(self.pointer.as_ptr(), self.length) }
+    }
+}
+
+// Allows the compiler to implicitly convert this to an `&mut [u8]`.
+impl std::ops::DerefMut for DynAlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // This is safe because the buffer is borrowed mutably, so nothing else can be viewing it.
+        unsafe { std::slice::from_raw_parts_mut(self.pointer.as_ptr(), self.length) }
+    }
+}
+
+impl Clone for DynAlignedBuffer {
+    fn clone(&self) -> Self {
+        let mut clone = DynAlignedBuffer::new(self.length, self.alignment);
+        clone.copy_from_slice(self);
+        clone
+    }
+}
+
+// Buffers are compared and hashed by their contents, the same way a `Vec<u8>` is.
+impl PartialEq for DynAlignedBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for DynAlignedBuffer {}
+
+impl Hash for DynAlignedBuffer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl std::fmt::Debug for DynAlignedBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DynAlignedBuffer").field("alignment", &self.alignment).field("data", &&**self).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The buffer lengths that each view and alignment test is performed with.
+    const TEST_LENGTHS: [usize; 10] = [16, 32, 64, 80, 240, 256, 272, 0x0fff0, 0x10000, 0x10010];
+
+    /// The alignments that each view and alignment test is performed with.
+    const TEST_ALIGNMENTS: [usize; 3] = [16, 64, PAGE_SIZE];
+
+    /// Checks that views of every test buffer as `T` contain the expected number of elements.
+    fn test_buffer_view_lengths<T: Pod>() {
+        for alignment in TEST_ALIGNMENTS {
+            for length in TEST_LENGTHS {
+                // Allocate a new buffer with the specified length, and view it as the specified type.
+                let buffer = DynAlignedBuffer::new(length, alignment);
+                let view = buffer.view_as::<T>();
+
+                // Assert that the expected number of elements is contained in the view.
+                assert_eq!(view.len(), length / std::mem::size_of::<T>(), "{} byte buffer aligned to {}", length, alignment);
+            }
+        }
+    }
+
+    /// Checks that every test buffer is already aligned to `T`, and so can be viewed as `T` without any leftover bytes.
+    fn test_buffer_alignment<T>() {
+        for alignment in TEST_ALIGNMENTS {
+            for length in TEST_LENGTHS {
+                // Allocate a new buffer with the specified length, and align it to the specified type.
+                let buffer = DynAlignedBuffer::new(length, alignment);
+                let (prefix, aligned, suffix) = unsafe { buffer.align_to::<T>() };
+
+                // Assert that the buffer was already aligned; meaning the prefix and suffix were empty.
+                assert_eq!(prefix.len(), 0, "{} byte buffer aligned to {}", length, alignment);
+                assert_eq!(suffix.len(), 0, "{} byte buffer aligned to {}", length, alignment);
+                assert_eq!(aligned.len(), length / std::mem::size_of::<T>());
+            }
+        }
+    }
+
+    #[test]
+    fn ensure_views_have_correct_lengths() {
+        test_buffer_view_lengths::<u16>();
+        test_buffer_view_lengths::<u32>();
+        test_buffer_view_lengths::<u64>();
+        test_buffer_view_lengths::<u128>();
+        test_buffer_view_lengths::<usize>();
+    }
+
+    #[test]
+    fn ensure_slices_are_aligned() {
+        test_buffer_alignment::<u16>();
+        test_buffer_alignment::<u32>();
+        test_buffer_alignment::<u64>();
+        test_buffer_alignment::<u128>();
+        test_buffer_alignment::<usize>();
+    }
+
+    #[test]
+    fn ensure_primitive_types_are_correctly_aligned() {
+        let buffer = DynAlignedBuffer::new(64, 16);
+
+        // These methods will panic if the types are not correctly aligned to 16 bytes.
+        buffer.view_as::<u8>();
+        buffer.view_as::<i8>();
+        buffer.view_as::<u16>();
+        buffer.view_as::<i16>();
+        buffer.view_as::<u32>();
+        buffer.view_as::<i32>();
+        buffer.view_as::<u64>();
+        buffer.view_as::<i64>();
+        buffer.view_as::<u128>();
+        buffer.view_as::<i128>();
+        buffer.view_as::<usize>();
+        buffer.view_as::<isize>();
+        buffer.view_as::<f32>();
+        buffer.view_as::<f64>();
+    }
+
+    #[test]
+    fn endian_views_are_the_same_on_every_host() {
+        let mut buffer = DynAlignedBuffer::new(20, PAGE_SIZE);
+        buffer[16..20].copy_from_slice(&[0x3F, 0x80, 0x00, 0x00]);
+
+        assert_eq!(buffer.view_as_be::<f32>().get(4), Some(1.0));
+        assert_eq!(buffer.view_as_le::<u32>().get(4), Some(0x0000_803F));
+        assert_eq!(buffer.view_as_be::<u32>().iter().next_back(), Some(0x3F80_0000));
+        assert_eq!(buffer.view_as_le::<u64>().len(), 2);
+    }
+
+    #[test]
+    fn mutable_views_write_through_to_the_buffer() {
+        let mut buffer = DynAlignedBuffer::new(64, 16);
+        buffer.view_as_mut::<u16>()[3] = 0xABCD_u16.to_le();
+        buffer.view_as_mut::<i64>()[7] = -1;
+
+        assert_eq!(&buffer[6..8], &[0xCD, 0xAB]);
+        assert_eq!(buffer.view_as_le::<u16>().get(3), Some(0xABCD));
+        assert!(buffer[56..].iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    #[should_panic(expected = "type must have an alignment that divides the buffer's alignment")]
+    fn views_of_types_with_a_larger_alignment_are_rejected() {
+        DynAlignedBuffer::new(64, 2).view_as::<u64>();
+    }
+
+    #[test]
+    fn buffers_of_any_length_are_aligned_and_zeroed() {
+        for length in [0, 1, 512, PAGE_SIZE - 1, PAGE_SIZE, PAGE_SIZE + 1, 3 * PAGE_SIZE, 1 << 22] {
+            let buffer = DynAlignedBuffer::new(length, PAGE_SIZE);
+            assert_eq!(buffer.len(), length);
+            assert_eq!(buffer.as_ptr() as usize % PAGE_SIZE, 0, "{} byte buffer isn't aligned", length);
+            assert!(buffer.iter().all(|&byte| byte == 0), "{} byte buffer isn't zeroed", length);
+        }
+    }
+
+    #[test]
+    fn clones_keep_their_contents_and_alignment() {
+        let mut buffer = DynAlignedBuffer::new(100, PAGE_SIZE);
+        buffer[99] = 7;
+
+        let clone = buffer.clone();
+        assert_eq!(clone, buffer);
+        assert_eq!(clone.as_ptr() as usize % PAGE_SIZE, 0);
+        assert_ne!(clone.as_ptr(), buffer.as_ptr());
+    }
+}
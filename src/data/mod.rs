@@ -1,3 +1,7 @@
-
-pub mod aligned_buffer;
+pub mod bad_sectors;
+pub mod dyn_aligned_buffer;
+// Plain old data types provide byte order conversions for every buffer view, not all of which are used by this binary
+// yet.
+#[allow(dead_code)]
+pub mod pod;
 pub mod sector_map;
@@ -0,0 +1,189 @@
+//! Plain old data types, which buffers of raw bytes can be safely viewed as.
+//!
+//! Viewing arbitrary bytes from a device as a type is only sound if every possible bit pattern is a valid value of
+//! that type. This is true of the primitive integer and floating point types, but not of types like `bool` or `char`.
+//! The `Pod` trait is sealed, so it can't be implemented for any other types.
+
+/// Prevents `Pod` from being implemented outside of this module.
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A primitive integer or floating point type, which any sequence of bytes with the right length and alignment is
+/// a valid value of. Buffers can only be viewed as slices of these types.
+///
+/// The trait is only implemented for `u8` through `u128`, `i8` through `i128`, `usize`, `isize`, `f32` and `f64`, and
+/// it's sealed, so views of any other type (like `view_as::<bool>()`) are rejected when they're compiled.
+pub trait Pod: sealed::Sealed + Copy {
+    /// Converts a value whose bytes are stored in little-endian order to the host's byte order.
+    fn from_le(value: Self) -> Self;
+
+    /// Converts a value whose bytes are stored in big-endian order to the host's byte order.
+    fn from_be(value: Self) -> Self;
+
+    /// Converts a value in the host's byte order to one whose bytes are stored in little-endian order.
+    fn to_le(self) -> Self;
+
+    /// Converts a value in the host's byte order to one whose bytes are stored in big-endian order.
+    fn to_be(self) -> Self;
+}
+
+macro_rules! impl_pod_for_integers {
+    ($($type:ty),*) => {$(
+        impl sealed::Sealed for $type {}
+
+        impl Pod for $type {
+            fn from_le(value: Self) -> Self { <$type>::from_le(value) }
+            fn from_be(value: Self) -> Self { <$type>::from_be(value) }
+            fn to_le(self) -> Self { <$type>::to_le(self) }
+            fn to_be(self) -> Self { <$type>::to_be(self) }
+        }
+    )*};
+}
+
+// Floats don't have byte order conversions of their own, so their bits are converted as an integer instead.
+macro_rules! impl_pod_for_floats {
+    ($($type:ty => $bits:ty),*) => {$(
+        impl sealed::Sealed for $type {}
+
+        impl Pod for $type {
+            fn from_le(value: Self) -> Self { <$type>::from_bits(<$bits>::from_le(value.to_bits())) }
+            fn from_be(value: Self) -> Self { <$type>::from_bits(<$bits>::from_be(value.to_bits())) }
+            fn to_le(self) -> Self { <$type>::from_bits(self.to_bits().to_le()) }
+            fn to_be(self) -> Self { <$type>::from_bits(self.to_bits().to_be()) }
+        }
+    )*};
+}
+
+impl_pod_for_integers!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize);
+impl_pod_for_floats!(f32 => u32, f64 => u64);
+
+/// Views `bytes` as a slice of `T`. Any trailing bytes which don't fill a whole `T` are left out of the view.
+///
+/// # Panics
+///
+/// If `bytes` isn't aligned to `T`.
+pub fn cast_slice<T: Pod>(bytes: &[u8]) -> &[T] {
+    assert!((bytes.as_ptr() as usize).is_multiple_of(std::mem::align_of::<T>()), "bytes must be aligned to the viewing type");
+
+    // This is safe because the bytes are aligned to `T` (which was just checked), every bit pattern is a valid `T`,
+    // and the view never extends past the end of the bytes.
+    unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const T, bytes.len() / std::mem::size_of::<T>()) }
+}
+
+/// Views `bytes` as a mutable slice of `T`. Any trailing bytes which don't fill a whole `T` are left out of the view.
+///
+/// # Panics
+///
+/// If `bytes` isn't aligned to `T`.
+pub fn cast_slice_mut<T: Pod>(bytes: &mut [u8]) -> &mut [T] {
+    assert!((bytes.as_ptr() as usize).is_multiple_of(std::mem::align_of::<T>()), "bytes must be aligned to the viewing type");
+
+    // This is safe for the same reasons as `cast_slice`. `T` has no padding either, so any value written through the
+    // view leaves every byte initialized.
+    unsafe { std::slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut T, bytes.len() / std::mem::size_of::<T>()) }
+}
+
+/// The order that the bytes of a multi-byte value are stored in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl ByteOrder {
+    /// Converts a value whose bytes are stored in this order to the host's byte order.
+    pub fn decode<T: Pod>(self, value: T) -> T {
+        match self {
+            ByteOrder::LittleEndian => T::from_le(value),
+            ByteOrder::BigEndian => T::from_be(value),
+        }
+    }
+}
+
+/// A view into a buffer as a slice of `T`, whose values are stored in a specific byte order. Values are converted to
+/// the host's byte order as they're read, so they're the same on every platform.
+#[derive(Clone, Copy, Debug)]
+pub struct EndianView<'a, T> {
+    values: &'a [T],
+    order: ByteOrder,
+}
+
+impl<'a, T: Pod> EndianView<'a, T> {
+    /// Creates a view of `values`, whose bytes are stored in `order`.
+    pub fn new(values: &'a [T], order: ByteOrder) -> Self {
+        EndianView { values, order }
+    }
+
+    /// Returns the number of values in the view.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether the view doesn't contain any values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the value at `index`, or `None` if it's past the end of the view.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.values.get(index).map(|&value| self.order.decode(value))
+    }
+
+    /// Returns an iterator over every value in the view.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = T> + ExactSizeIterator + 'a {
+        let order = self.order;
+        self.values.iter().map(move |&value| order.decode(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes which are aligned to 16 bytes, so they can be viewed as any primitive type.
+    #[repr(C, align(16))]
+    struct Aligned([u8; 16]);
+
+    #[test]
+    fn views_decode_values_in_their_byte_order() {
+        let bytes = Aligned([0x01, 0x02, 0x03, 0x04, 0x3F, 0x80, 0x00, 0x00, 0, 0, 0, 0, 0, 0, 0, 0xFF]);
+
+        let values = cast_slice::<u32>(&bytes.0);
+        let little_endian = EndianView::new(values, ByteOrder::LittleEndian);
+        let big_endian = EndianView::new(values, ByteOrder::BigEndian);
+        assert_eq!(little_endian.len(), 4);
+        assert_eq!(little_endian.get(0), Some(0x0403_0201));
+        assert_eq!(big_endian.get(0), Some(0x0102_0304));
+        assert_eq!(big_endian.get(4), None);
+        assert_eq!(big_endian.iter().rev().collect::<Vec<_>>(), [0xFF, 0, 0x3F80_0000, 0x0102_0304]);
+
+        let floats = EndianView::new(cast_slice::<f32>(&bytes.0), ByteOrder::BigEndian);
+        assert_eq!(floats.get(1), Some(1.0));
+        assert_eq!(EndianView::new(cast_slice::<i128>(&bytes.0), ByteOrder::LittleEndian).get(0), Some(i128::from_le_bytes(bytes.0)));
+    }
+
+    #[test]
+    fn values_can_be_written_in_a_specific_byte_order() {
+        let mut bytes = Aligned([0; 16]);
+        let values = cast_slice_mut::<u16>(&mut bytes.0);
+        values[0] = 0xAABB_u16.to_be();
+        values[1] = Pod::to_le(0xCCDD_u16);
+        values[7] = 0x1234_u16.to_le();
+        assert_eq!(bytes.0, [0xAA, 0xBB, 0xDD, 0xCC, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn trailing_bytes_are_left_out_of_views() {
+        let bytes = Aligned([0; 16]);
+        assert_eq!(cast_slice::<u64>(&bytes.0[..15]).len(), 1);
+        assert_eq!(cast_slice::<u32>(&bytes.0[..3]).len(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "bytes must be aligned to the viewing type")]
+    fn misaligned_views_are_rejected() {
+        let bytes = Aligned([0; 16]);
+        cast_slice::<u32>(&bytes.0[1..]);
+    }
+}
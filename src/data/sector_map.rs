@@ -0,0 +1,575 @@
+//! A compact record of which sectors in a device are occupied.
+//!
+//! Sector maps are stored as a short header, followed by any number of segments:
+//! ```text
+//! [magic: "SMAP"][sector_size: 4 bytes]
+//! [address][segment_length][segment_map]
+//! [address][segment_length][segment_map]
+//! ...
+//! ```
+//! Where `address` is the compressed sector address that the segment starts at, `segment_length` is the
+//! number of bytes stored in `segment_map`, and `segment_map` is a bit vector storing whether each sector
+//! (starting at `address`) was occupied or not. The bits of each byte correspond to 8 consecutive sectors,
+//! starting from the least significant bit. All multi-byte values are stored in big-endian order.
+//!
+//! # Address Compression
+//!
+//! Addresses are stored as sector addresses, so the exact byte location they point to depends on the sector
+//! size stored in the header. These addresses use a compressed format, where the first 2 bits specify the
+//! number of bytes the address is encoded on (including those 2 bits):
+//! ```text
+//! 00 => 4 bytes    lets us store addresses up to 2^30 sectors    (4 TB for 4K sectors)
+//! 01 => 5 bytes    lets us store addresses up to 2^38 sectors    (1 PB for 4K sectors)
+//! 10 => 6 bytes    lets us store addresses up to 2^46 sectors    (256 PB for 4K sectors)
+//! 11 => 8 bytes    lets us store addresses up to 2^62 sectors    (16384 EB for 4K sectors)
+//! ```
+//!
+//! # Segment Lengths
+//!
+//! Segment lengths are always stored on 3 bytes. If a segment grows past the largest length we can store,
+//! we have to open a new segment, even if we theoretically didn't need to. This still lets us store
+//! 134,217,712 sectors per segment (512 GB for 4K sectors). The largest value (`0xFFFFFF`) is reserved
+//! to mean "this segment runs to the end of the file", which is used for the final segment of a map.
+//!
+//! When building a map, we try to keep both the number of segments small (which makes it easier to search
+//! through), and the file itself small (which makes it easier to store). So a run of unoccupied sectors is
+//! only split into a new segment if it's longer than the cost of storing another address and length.
+
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+/// The bytes every sector map file starts with.
+const MAGIC: &[u8; 4] = b"SMAP";
+
+/// The number of bytes that segment lengths are stored on.
+const SEGMENT_LENGTH_SIZE: usize = 3;
+
+/// The segment length which marks a segment as running to the end of the file.
+const RUNS_TO_EOF: u32 = 0xFF_FFFF;
+
+/// The largest number of bytes a segment's map can have (any larger and its length would collide with `RUNS_TO_EOF`).
+pub const MAX_SEGMENT_BYTES: usize = (RUNS_TO_EOF - 1) as usize;
+
+/// The largest sector address that can be stored in a sector map.
+pub const MAX_ADDRESS: u64 = (1 << 62) - 1;
+
+/// A contiguous range of sectors, along with a bit vector storing whether each of them is occupied.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Segment {
+    start: u64,
+    map: Vec<u8>,
+}
+
+impl Segment {
+    /// Returns the address of the first sector covered by this segment.
+    #[cfg(test)]
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the number of sectors covered by this segment. Since the map is stored as whole bytes,
+    /// this is always a multiple of 8.
+    pub fn sector_count(&self) -> u64 {
+        (self.map.len() as u64) * 8
+    }
+
+    /// Returns the raw bit vector storing whether each sector in this segment is occupied.
+    #[cfg(test)]
+    pub fn map(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Returns whether the specified sector (relative to the start of this segment) is occupied.
+    fn is_occupied(&self, index: u64) -> bool {
+        self.map.get((index / 8) as usize).is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+    }
+}
+
+/// A record of which sectors in a device are occupied. See the module documentation for the file format.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectorMap {
+    sector_size: u32,
+    segments: Vec<Segment>,
+}
+
+impl SectorMap {
+    /// Returns the size of the sectors (in bytes) that this map's addresses refer to.
+    pub fn sector_size(&self) -> u32 {
+        self.sector_size
+    }
+
+    /// Returns the segments stored in this map, sorted by their starting addresses.
+    #[cfg(test)]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Returns whether the specified sector is marked as occupied.
+    pub fn contains(&self, sector: u64) -> bool {
+        // Find the last segment which starts at or before the sector, and check whether the sector is in it.
+        let index = self.segments.partition_point(|segment| segment.start <= sector);
+        index > 0 && self.segments[index - 1].is_occupied(sector - self.segments[index - 1].start)
+    }
+
+    /// Returns the total number of occupied sectors in this map.
+    pub fn occupied_count(&self) -> u64 {
+        self.segments.iter()
+            .flat_map(|segment| &segment.map)
+            .map(|byte| byte.count_ones() as u64)
+            .sum()
+    }
+
+    /// Returns the ranges of consecutive occupied sectors stored in this map, in ascending order.
+    /// Ranges which span multiple segments are merged together.
+    pub fn extents(&self) -> Vec<Range<u64>> {
+        let mut extents: Vec<Range<u64>> = Vec::new();
+        for segment in &self.segments {
+            for (byte_index, &byte) in segment.map.iter().enumerate() {
+                // Skip over empty bytes without checking each of their bits, and handle full bytes all at once.
+                let first_sector = segment.start + (byte_index as u64) * 8;
+                if byte == 0 {
+                    continue;
+                }
+                if byte == 0xFF {
+                    match extents.last_mut() {
+                        Some(extent) if extent.end == first_sector => extent.end += 8,
+                        _ => extents.push(first_sector..first_sector + 8),
+                    }
+                    continue;
+                }
+
+                for bit in 0..8 {
+                    if byte & (1 << bit) == 0 {
+                        continue;
+                    }
+
+                    // Either extend the previous extent (if this sector directly follows it), or start a new one.
+                    let sector = first_sector + bit;
+                    match extents.last_mut() {
+                        Some(extent) if extent.end == sector => extent.end += 1,
+                        _ => extents.push(sector..sector + 1),
+                    }
+                }
+            }
+        }
+        extents
+    }
+
+    /// Writes this map to `writer` in the sector map file format. The final segment is always written with
+    /// the "runs to EOF" marker instead of its length.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.sector_size.to_be_bytes())?;
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            let length = if index + 1 == self.segments.len() { RUNS_TO_EOF } else { segment.map.len() as u32 };
+            write_address(writer, segment.start)?;
+            writer.write_all(&length.to_be_bytes()[4 - SEGMENT_LENGTH_SIZE..])?;
+            writer.write_all(&segment.map)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a map from `reader`, which must contain data in the sector map file format.
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        // Read the header, and check that this is actually a sector map.
+        let mut header = [0; 8];
+        reader.read_exact(&mut header).map_err(|_| invalid_data("file is too short to be a sector map"))?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("file is not a sector map"));
+        }
+        let sector_size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        if sector_size == 0 {
+            return Err(invalid_data("sector map has a sector size of 0"));
+        }
+
+        // Keep reading segments until we reach the end of the file, or a segment that runs to the end of it.
+        let mut segments = Vec::new();
+        while let Some(start) = read_address(reader)? {
+            let mut length_bytes = [0; 4];
+            reader.read_exact(&mut length_bytes[4 - SEGMENT_LENGTH_SIZE..])
+                .map_err(|_| invalid_data("sector map ended in the middle of a segment length"))?;
+            let length = u32::from_be_bytes(length_bytes);
+
+            let mut map = Vec::new();
+            if length == RUNS_TO_EOF {
+                reader.read_to_end(&mut map)?;
+                segments.push(Segment { start, map });
+                break;
+            }
+
+            map.resize(length as usize, 0);
+            reader.read_exact(&mut map).map_err(|_| invalid_data("sector map ended in the middle of a segment"))?;
+            segments.push(Segment { start, map });
+        }
+
+        // Segments must be sorted and non-overlapping, otherwise lookups into the map won't work.
+        let is_sorted = segments.windows(2).all(|pair| pair[0].start + pair[0].sector_count() <= pair[1].start);
+        if !is_sorted {
+            return Err(invalid_data("sector map contains overlapping or out-of-order segments"));
+        }
+
+        Ok(SectorMap { sector_size, segments })
+    }
+}
+
+/// Builds a `SectorMap` from a sequence of sectors, which are pushed one run at a time in ascending order.
+///
+/// # Examples
+///
+/// ```
+/// # use raw_reader_lib::sector_map::SectorMapBuilder;
+/// let mut builder = SectorMapBuilder::new(512);
+/// builder.push_run(100, false); // Sectors 0~99 are empty.
+/// builder.push_run(20, true);   // Sectors 100~119 are occupied.
+/// builder.push(false);          // Sector 120 is empty.
+/// builder.push(true);           // Sector 121 is occupied.
+///
+/// let map = builder.build();
+/// assert_eq!(map.extents(), vec![100..120, 121..122]);
+/// ```
+#[derive(Debug)]
+pub struct SectorMapBuilder {
+    sector_size: u32,
+    segments: Vec<Segment>,
+    /// The segment that occupied sectors are currently being added to, if any.
+    current: Option<Segment>,
+    /// The number of sectors stored in the current segment's map (this is the number of bits, not bytes).
+    current_length: u64,
+    /// The number of empty sectors that have been pushed since the last occupied sector. These aren't
+    /// added to the current segment until we know whether another occupied sector follows them.
+    pending_empty: u64,
+    /// The address of the next sector that will be pushed.
+    next_sector: u64,
+    /// The largest number of sectors any one segment can store.
+    max_segment_sectors: u64,
+}
+
+impl SectorMapBuilder {
+    /// Creates a new builder for a map with the specified sector size, starting at sector 0.
+    pub fn new(sector_size: u32) -> Self {
+        SectorMapBuilder {
+            sector_size,
+            segments: Vec::new(),
+            current: None,
+            current_length: 0,
+            pending_empty: 0,
+            next_sector: 0,
+            max_segment_sectors: (MAX_SEGMENT_BYTES as u64) * 8,
+        }
+    }
+
+    /// Adds the next sector to the map.
+    #[cfg(test)]
+    pub fn push(&mut self, occupied: bool) {
+        self.push_run(1, occupied);
+    }
+
+    /// Adds the next `count` sectors to the map, which are all either occupied or empty.
+    ///
+    /// # Panics
+    ///
+    /// If this would push sectors past `MAX_ADDRESS`.
+    pub fn push_run(&mut self, count: u64, occupied: bool) {
+        let end = self.next_sector.checked_add(count).filter(|&end| end <= MAX_ADDRESS + 1);
+        assert!(end.is_some(), "cannot push sectors past the largest sector address");
+
+        if !occupied {
+            self.next_sector += count;
+            if self.current.is_some() {
+                self.pending_empty += count;
+                // If the gap is longer than it would take to store a new segment header, close the segment.
+                let header_bits = ((address_width(self.next_sector) + SEGMENT_LENGTH_SIZE) * 8) as u64;
+                if self.pending_empty > header_bits {
+                    self.close_segment();
+                }
+            }
+            return;
+        }
+
+        let mut remaining = count;
+        while remaining > 0 {
+            if self.current.is_none() {
+                self.current = Some(Segment { start: self.next_sector, map: Vec::new() });
+                self.current_length = 0;
+                self.pending_empty = 0;
+            }
+
+            // Close the segment if there isn't room in it for the pending empty sectors and at least 1 more sector.
+            let available = self.max_segment_sectors - self.current_length;
+            if available <= self.pending_empty {
+                self.close_segment();
+                continue;
+            }
+
+            // Add the pending empty sectors, then as many of the occupied sectors as will fit in this segment.
+            let added = remaining.min(available - self.pending_empty);
+            let segment = self.current.as_mut().expect("a segment was just opened");
+            let start_bit = self.current_length + self.pending_empty;
+            set_bits(&mut segment.map, start_bit, added);
+
+            self.current_length = start_bit + added;
+            self.pending_empty = 0;
+            self.next_sector += added;
+            remaining -= added;
+        }
+    }
+
+    /// Finishes building the map.
+    pub fn build(mut self) -> SectorMap {
+        self.close_segment();
+        SectorMap { sector_size: self.sector_size, segments: self.segments }
+    }
+
+    /// Closes the current segment (if there is one), dropping any pending empty sectors from the end of it.
+    fn close_segment(&mut self) {
+        if let Some(segment) = self.current.take() {
+            self.segments.push(segment);
+        }
+        self.current_length = 0;
+        self.pending_empty = 0;
+    }
+}
+
+/// Sets `count` bits in `map` to 1, starting at the bit with index `start`, growing the map as necessary.
+fn set_bits(map: &mut Vec<u8>, start: u64, count: u64) {
+    let end = start + count;
+    map.resize(ceil_divide!(end, 8) as usize, 0);
+
+    let mut bit = start;
+    while bit < end {
+        // Set entire bytes at once when possible, otherwise fall back to setting bits 1 at a time.
+        if bit.is_multiple_of(8) && end - bit >= 8 {
+            let full_bytes = ((end - bit) / 8) as usize;
+            let first_byte = (bit / 8) as usize;
+            map[first_byte..first_byte + full_bytes].fill(0xFF);
+            bit += (full_bytes as u64) * 8;
+        } else {
+            map[(bit / 8) as usize] |= 1 << (bit % 8);
+            bit += 1;
+        }
+    }
+}
+
+/// Returns the number of bytes that the specified address would be encoded on.
+fn address_width(address: u64) -> usize {
+    match address {
+        a if a < (1 << 30) => 4,
+        a if a < (1 << 38) => 5,
+        a if a < (1 << 46) => 6,
+        _ => 8,
+    }
+}
+
+/// Writes `address` to `writer` in the compressed address format.
+fn write_address(writer: &mut impl Write, address: u64) -> io::Result<()> {
+    debug_assert!(address <= MAX_ADDRESS, "address is too large to be stored in a sector map");
+
+    // Store the width's 2 bit header in the top bits of the address, then only write the bytes we need.
+    let width = address_width(address);
+    let header = match width {
+        4 => 0b00,
+        5 => 0b01,
+        6 => 0b10,
+        _ => 0b11,
+    };
+    let encoded = address | (header << (width * 8 - 2));
+    writer.write_all(&encoded.to_be_bytes()[8 - width..])
+}
+
+/// Reads an address from `reader` in the compressed address format.
+/// Returns `None` if `reader` was already at the end of the file.
+fn read_address(reader: &mut impl Read) -> io::Result<Option<u64>> {
+    // Read the first byte of the address, which contains the 2 bit header that tells us the address's width.
+    let mut bytes = [0; 8];
+    if reader.read(&mut bytes[..1])? == 0 {
+        return Ok(None);
+    }
+    let width = match bytes[0] >> 6 {
+        0b00 => 4,
+        0b01 => 5,
+        0b10 => 6,
+        _ => 8,
+    };
+
+    // Read the rest of the address, then strip the header from it.
+    reader.read_exact(&mut bytes[1..width]).map_err(|_| invalid_data("sector map ended in the middle of an address"))?;
+    bytes[0] &= 0b0011_1111;
+    let mut address_bytes = [0; 8];
+    address_bytes[8 - width..].copy_from_slice(&bytes[..width]);
+    Ok(Some(u64::from_be_bytes(address_bytes)))
+}
+
+/// Creates an error describing malformed sector map data.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a map to a buffer, then reads it back out.
+    fn round_trip(map: &SectorMap) -> SectorMap {
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        SectorMap::read_from(&mut bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn addresses_use_the_smallest_width_possible() {
+        let test_cases = [
+            (0, 4), ((1 << 30) - 1, 4),
+            (1 << 30, 5), ((1 << 38) - 1, 5),
+            (1 << 38, 6), ((1 << 46) - 1, 6),
+            (1 << 46, 8), (MAX_ADDRESS, 8),
+        ];
+        for (address, width) in test_cases {
+            let mut bytes = Vec::new();
+            write_address(&mut bytes, address).unwrap();
+            assert_eq!(bytes.len(), width, "address {address:#x} was encoded on the wrong number of bytes");
+            assert_eq!(read_address(&mut bytes.as_slice()).unwrap(), Some(address));
+        }
+    }
+
+    #[test]
+    fn address_headers_are_stored_in_the_top_bits() {
+        let mut bytes = Vec::new();
+        write_address(&mut bytes, 1 << 30).unwrap();
+        assert_eq!(bytes, [0b0100_0000, 0x40, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn empty_maps_round_trip() {
+        let map = SectorMapBuilder::new(4096).build();
+        assert!(map.segments().is_empty());
+        assert_eq!(map.extents(), vec![]);
+        assert_eq!(round_trip(&map), map);
+    }
+
+    #[test]
+    fn short_gaps_stay_in_the_same_segment() {
+        let mut builder = SectorMapBuilder::new(512);
+        builder.push_run(10, true);
+        builder.push_run(20, false);
+        builder.push_run(5, true);
+        let map = builder.build();
+
+        assert_eq!(map.segments().len(), 1);
+        assert_eq!(map.extents(), vec![0..10, 30..35]);
+        assert_eq!(map.occupied_count(), 15);
+        assert_eq!(round_trip(&map), map);
+    }
+
+    #[test]
+    fn long_gaps_start_a_new_segment() {
+        let mut builder = SectorMapBuilder::new(512);
+        builder.push_run(1000, false);
+        builder.push_run(3, true);
+        builder.push_run(1000, false);
+        builder.push(true);
+        builder.push_run(1000, false);
+        let map = builder.build();
+
+        assert_eq!(map.segments().len(), 2);
+        assert_eq!(map.segments()[0].start(), 1000);
+        assert_eq!(map.segments()[1].start(), 2003);
+        assert_eq!(map.extents(), vec![1000..1003, 2003..2004]);
+        assert_eq!(round_trip(&map), map);
+    }
+
+    #[test]
+    fn contains_checks_the_correct_segment() {
+        let mut builder = SectorMapBuilder::new(512);
+        builder.push_run(100, false);
+        builder.push_run(2, true);
+        builder.push_run(500, false);
+        builder.push_run(2, true);
+        let map = builder.build();
+
+        let occupied = [100, 101, 602, 603];
+        for sector in 0..700 {
+            assert_eq!(map.contains(sector), occupied.contains(&sector), "sector {sector}");
+        }
+    }
+
+    #[test]
+    fn large_addresses_round_trip() {
+        let mut builder = SectorMapBuilder::new(4096);
+        builder.push_run(1 << 40, false);
+        builder.push_run(9, true);
+        builder.push_run(1 << 50, false);
+        builder.push(true);
+        let map = builder.build();
+
+        assert_eq!(map.extents(), vec![(1 << 40)..(1 << 40) + 9, (1 << 40) + 9 + (1 << 50)..(1 << 40) + 10 + (1 << 50)]);
+        assert_eq!(round_trip(&map), map);
+    }
+
+    #[test]
+    fn segment_length_overflow_forces_a_new_segment() {
+        let max_sectors = (MAX_SEGMENT_BYTES as u64) * 8;
+        let mut builder = SectorMapBuilder::new(4096);
+        builder.push_run(7, false);
+        builder.push_run(max_sectors + 20, true);
+        let map = builder.build();
+
+        // The first segment should be completely full, and the second should start directly after it.
+        assert_eq!(map.segments().len(), 2);
+        assert_eq!(map.segments()[0].start(), 7);
+        assert_eq!(map.segments()[0].map().len(), MAX_SEGMENT_BYTES);
+        assert_eq!(map.segments()[1].start(), 7 + max_sectors);
+        assert_eq!(map.extents(), vec![7..7 + max_sectors + 20]);
+        assert_eq!(map.occupied_count(), max_sectors + 20);
+
+        // The full segment must be written with an explicit length, which can't collide with the EOF marker.
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        assert_eq!(&bytes[12..15], &[0xFF, 0xFF, 0xFE]);
+        assert_eq!(SectorMap::read_from(&mut bytes.as_slice()).unwrap(), map);
+    }
+
+    #[test]
+    fn only_the_final_segment_runs_to_eof() {
+        let mut builder = SectorMapBuilder::new(512);
+        builder.push(true);
+        builder.push_run(1000, false);
+        builder.push_run(9, true);
+        let map = builder.build();
+
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, [
+            b'S', b'M', b'A', b'P', 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0b0000_0001,
+            0x00, 0x00, 0x03, 0xE9, 0xFF, 0xFF, 0xFF, 0b1111_1111, 0b0000_0001,
+        ]);
+        assert_eq!(SectorMap::read_from(&mut bytes.as_slice()).unwrap(), map);
+    }
+
+    #[test]
+    fn segments_with_explicit_lengths_can_end_the_file() {
+        let bytes = [
+            b'S', b'M', b'A', b'P', 0x00, 0x00, 0x10, 0x00,
+            0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x02, 0b1000_0000, 0b0000_0001,
+        ];
+        let map = SectorMap::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(map.sector_size(), 4096);
+        assert_eq!(map.extents(), vec![23..25]);
+    }
+
+    #[test]
+    fn malformed_maps_are_rejected() {
+        let truncated_header = b"SMA".to_vec();
+        let wrong_magic = b"PAMS\x00\x00\x02\x00".to_vec();
+        let zero_sector_size = b"SMAP\x00\x00\x00\x00".to_vec();
+        let truncated_address = b"SMAP\x00\x00\x02\x00\x00\x00".to_vec();
+        let truncated_segment = b"SMAP\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x05\xFF".to_vec();
+        let overlapping_segments = b"SMAP\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x02\xFF\xFF\x00\x00\x00\x08\xFF\xFF\xFF".to_vec();
+
+        for bytes in [truncated_header, wrong_magic, zero_sector_size, truncated_address, truncated_segment, overlapping_segments] {
+            let error = SectorMap::read_from(&mut bytes.as_slice()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,596 @@
+//! Opens the files/devices being inspected in a way that guarantees they can't be modified.
+//!
+//! Evidence must never be written to, so the only way to open a file/device for inspection is `ReadOnlyDevice::open`,
+//! which always opens it read-only. The handle it holds is never exposed, and `ReadOnlyDevice` only implements `Read`,
+//! `ReadAt` and `Seek`, so no code in the crate can write through it (or obtain a handle which can).
+//!
+//! Devices can also be read with direct I/O (`O_DIRECT` on Linux), which bypasses the page cache. This means reads
+//! always hit the device itself (instead of data the kernel cached earlier), and scanning a whole disk doesn't evict
+//! everything else from the cache. Direct reads must be aligned to the device's logical block size, which
+//! `ReadOnlyDevice::read_at` takes care of.
+//!
+//! Everything which reads a device does so through the `ReadAt` trait, so that devices with unreadable sectors can be
+//! stood in for by the tests.
+//!
+//! Commands which save files (like sector maps and settings) check their path with `check_output_path` first, so that
+//! a mistyped path can't overwrite the file/device being inspected, or any other device.
+
+use crate::data::dyn_aligned_buffer::DynAlignedBuffer;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Whether the kernel itself prevents writes to a block device, independently of how it was opened.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WriteProtection {
+    /// The kernel has marked the device as read-only, either because of a hardware write blocker, or because it was
+    /// set with `blockdev --setro`.
+    ReadOnly,
+    /// The device can be written to by any program which opens it for writing.
+    Writable,
+    /// The file isn't a block device, or the platform doesn't report it.
+    Unknown,
+}
+
+/// The block size that direct reads are aligned to when the device's logical block size can't be determined (which
+/// includes every regular file). Filesystems never require more than this in practice.
+pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+/// Whether reads from a file/device bypass the page cache.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DirectIo {
+    /// Reads go through the page cache.
+    Off,
+    /// Reads bypass the page cache.
+    On,
+    /// Direct I/O was requested, but the file/device (or platform) doesn't support it, so reads go through the page
+    /// cache instead. Files on some filesystems (like `tmpfs` on older kernels) reject `O_DIRECT`, for example.
+    Unsupported,
+}
+
+/// A file/device which data can be read from at any offset.
+pub trait ReadAt {
+    /// Reads the data starting at `offset` into `buffer`.
+    ///
+    /// Returns the number of bytes that were read. This is always the full length of the buffer, unless the end of
+    /// the file/device was reached first.
+    fn read_at(&mut self, offset: u64, buffer: &mut [u8]) -> io::Result<usize>;
+
+    /// Returns whether reads currently bypass the page cache.
+    fn direct_io(&self) -> DirectIo {
+        DirectIo::Off
+    }
+
+    /// Turns direct I/O on or off, if the file/device supports it.
+    fn set_direct_io(&mut self, _enabled: bool) {}
+
+    /// Returns the size of the blocks that reads are widened to while direct I/O is on. A direct read of fewer bytes
+    /// than this can still fail because of a bad sector in the same block.
+    fn block_size(&self) -> usize {
+        1
+    }
+
+    /// Returns the identity of the file/device, so that it can be recognized through other paths. Returns `None` if
+    /// it couldn't be determined.
+    fn file_id(&self) -> Option<&FileId> {
+        None
+    }
+}
+
+/// The OS error codes which mean that the device couldn't read the requested data because its media is damaged (like a
+/// bad sector), rather than because the read itself was invalid.
+#[cfg(target_os = "linux")]
+pub const MEDIA_ERRORS: &[i32] = &[libc::EIO, libc::ENXIO, libc::ENODATA];
+
+/// The OS error codes which mean that the device couldn't read the requested data because its media is damaged (like a
+/// bad sector), rather than because the read itself was invalid.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub const MEDIA_ERRORS: &[i32] = &[libc::EIO, libc::ENXIO];
+
+/// The OS error codes which mean that the device couldn't read the requested data because its media is damaged (like a
+/// bad sector), rather than because the read itself was invalid: `ERROR_CRC`, `ERROR_SECTOR_NOT_FOUND`,
+/// `ERROR_READ_FAULT` and `ERROR_IO_DEVICE`.
+#[cfg(windows)]
+pub const MEDIA_ERRORS: &[i32] = &[23, 27, 30, 1117];
+
+/// Returns whether `err` means that the device's media is damaged, in which case the rest of the data may still be
+/// readable if it's read in smaller pieces. Any other error would fail again however the data was read.
+pub fn is_media_error(err: &io::Error) -> bool {
+    err.raw_os_error().is_some_and(|code| MEDIA_ERRORS.contains(&code))
+}
+
+/// Identifies a file independently of the path it was opened from, so that other paths which lead to it (like links)
+/// can be recognized. On Unix, files are identified by their device and inode numbers, and elsewhere by their
+/// canonical path.
+#[cfg(unix)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileId {
+    device: u64,
+    inode: u64,
+}
+
+/// Identifies a file independently of the path it was opened from, so that other paths which lead to it (like links)
+/// can be recognized. On Unix, files are identified by their device and inode numbers, and elsewhere by their
+/// canonical path.
+#[cfg(not(unix))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileId {
+    path: PathBuf,
+}
+
+impl FileId {
+    /// Returns the identity of the file at `path`, following any links.
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> io::Result<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(path)?;
+        Ok(FileId { device: metadata.dev(), inode: metadata.ino() })
+    }
+
+    /// Returns the identity of the file at `path`, following any links.
+    #[cfg(not(unix))]
+    pub fn of(path: &Path) -> io::Result<Self> {
+        Ok(FileId { path: fs::canonicalize(path)? })
+    }
+}
+
+/// Checks that a file can be saved to `path` without overwriting any evidence. Returns an error if `path` leads to the
+/// file/device identified by `device`, or to any block or character device. Paths which don't exist yet are allowed.
+pub fn check_output_path(path: &Path, device: Option<&FileId>) -> io::Result<()> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let reason = if is_device(&metadata) {
+        "it's a device"
+    } else if device.is_some_and(|device| FileId::of(path).is_ok_and(|id| id == *device)) {
+        "it's the file/device being inspected"
+    } else {
+        return Ok(());
+    };
+    Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{reason}, which is never written to")))
+}
+
+/// Returns whether `metadata` describes a block or character device.
+#[cfg(unix)]
+fn is_device(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    let file_type = metadata.file_type();
+    file_type.is_block_device() || file_type.is_char_device()
+}
+
+/// Returns whether `metadata` describes a device. Devices can't be opened through the filesystem outside of Unix.
+#[cfg(not(unix))]
+fn is_device(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// A read-only handle to a file/device.
+pub struct ReadOnlyDevice {
+    file: File,
+    path: PathBuf,
+    id: Option<FileId>,
+    write_protection: WriteProtection,
+    /// The alignment that the offset, length, and buffer of every direct read must have. This is the device's logical
+    /// block size, or `DEFAULT_BLOCK_SIZE` if it isn't a block device.
+    block_size: usize,
+    direct_io: DirectIo,
+    /// Holds the aligned data around unaligned direct reads. This is only allocated once it's needed.
+    bounce_buffer: Option<DynAlignedBuffer>,
+}
+
+impl ReadOnlyDevice {
+    /// Opens the file/device at `path` for reading only.
+    ///
+    /// On Linux, the file is also opened with `O_NOATIME` (so reading it doesn't update its access time) if the user
+    /// is allowed to, since that's only permitted for the owner of the file.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = open_read_only(path)?;
+        let write_protection = query_write_protection(&file);
+        let block_size = query_logical_block_size(&file).unwrap_or(DEFAULT_BLOCK_SIZE);
+        Ok(ReadOnlyDevice {
+            file,
+            path: path.to_owned(),
+            id: FileId::of(path).ok(),
+            write_protection,
+            block_size,
+            direct_io: DirectIo::Off,
+            bounce_buffer: None,
+        })
+    }
+
+    /// Returns the path that the file/device was opened from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether the kernel prevents writes to the device, as checked when it was opened.
+    pub fn write_protection(&self) -> WriteProtection {
+        self.write_protection
+    }
+
+    /// Reads the data starting at `offset` into `buffer` with direct I/O. If the request isn't aligned, the aligned
+    /// blocks which cover it are read into the bounce buffer instead, and the requested bytes are copied out of it.
+    fn read_direct(&mut self, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+        let block_size = self.block_size;
+        let skip = (offset % block_size as u64) as usize;
+        let is_aligned = skip == 0
+            && buffer.len().is_multiple_of(block_size)
+            && (buffer.as_ptr() as usize).is_multiple_of(block_size);
+        if is_aligned {
+            return read_fully_at(&self.file, offset, buffer, block_size);
+        }
+
+        let length = (skip + buffer.len()).next_multiple_of(block_size);
+        if self.bounce_buffer.as_ref().is_none_or(|bounce| bounce.len() < length) {
+            self.bounce_buffer = Some(DynAlignedBuffer::new(length, block_size));
+        }
+        let bounce = self.bounce_buffer.as_mut().expect("bounce buffer was just allocated");
+        let filled = read_fully_at(&self.file, offset - skip as u64, &mut bounce[..length], block_size)?;
+
+        let copied = filled.saturating_sub(skip).min(buffer.len());
+        buffer[..copied].copy_from_slice(&bounce[skip..skip + copied]);
+        Ok(copied)
+    }
+}
+
+impl ReadAt for ReadOnlyDevice {
+    /// Returns whether reads currently bypass the page cache.
+    fn direct_io(&self) -> DirectIo {
+        self.direct_io
+    }
+
+    /// Turns direct I/O on or off. If the file/device doesn't support it, reads quietly carry on going through the
+    /// page cache, and `direct_io` reports it as `Unsupported`.
+    ///
+    /// Only `read_at` takes care of the alignment that direct reads require, so it should be used for every read
+    /// while direct I/O is on.
+    fn set_direct_io(&mut self, enabled: bool) {
+        self.direct_io = match (enabled, self.direct_io) {
+            (true, DirectIo::Off) => match set_o_direct(&self.file, true) {
+                Ok(()) => DirectIo::On,
+                Err(_) => DirectIo::Unsupported,
+            },
+            // If the flag can't be cleared, reads still work, they just carry on bypassing the page cache.
+            (false, DirectIo::On) => match set_o_direct(&self.file, false) {
+                Ok(()) => DirectIo::Off,
+                Err(_) => DirectIo::On,
+            },
+            (false, _) => DirectIo::Off,
+            (true, state) => state,
+        };
+    }
+
+    /// Returns the device's logical block size, which direct reads are aligned to.
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the identity of the file/device, as determined when it was opened.
+    fn file_id(&self) -> Option<&FileId> {
+        self.id.as_ref()
+    }
+
+    /// Reads the data starting at `offset` into `buffer`, without moving the position used by `Read` and `Seek`.
+    fn read_at(&mut self, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.direct_io == DirectIo::On {
+            match self.read_direct(offset, buffer) {
+                // Some files accept `O_DIRECT` being set, but then reject the reads themselves.
+                Err(err) if err.kind() == io::ErrorKind::InvalidInput => {
+                    set_o_direct(&self.file, false)?;
+                    self.direct_io = DirectIo::Unsupported;
+                }
+                result => return result,
+            }
+        }
+        read_fully_at(&self.file, offset, buffer, 1)
+    }
+}
+
+impl Read for ReadOnlyDevice {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buffer)
+    }
+}
+
+impl Seek for ReadOnlyDevice {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.file.seek(position)
+    }
+}
+
+/// Reads from `file`, starting at `offset`, until either `buffer` is full or the end of the file is reached.
+/// Returns the number of bytes that were read.
+///
+/// Reads can only carry on from a multiple of `block_size`, so a read which stops anywhere else is taken to mean that
+/// the end of the file was reached. This is always the case for direct reads, which only stop mid-block at the end.
+fn read_fully_at(file: &File, offset: u64, buffer: &mut [u8], block_size: usize) -> io::Result<usize> {
+    // A single read is allowed to return less data than requested, so we keep reading until either the buffer is
+    // full, or the read returns no data (meaning we've hit the end of the file).
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match read_once_at(file, offset + filled as u64, &mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(count) => {
+                filled += count;
+                if count % block_size != 0 {
+                    break;
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// Performs a single read from `file` at `offset`, without moving its position.
+#[cfg(unix)]
+fn read_once_at(file: &File, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buffer, offset)
+}
+
+/// Performs a single read from `file` at `offset`. On Windows, this moves the file's position.
+#[cfg(windows)]
+fn read_once_at(file: &File, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buffer, offset)
+}
+
+/// Sets or clears the `O_DIRECT` flag of `file`, which makes reads bypass the page cache.
+#[cfg(target_os = "linux")]
+fn set_o_direct(file: &File, enabled: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let descriptor = file.as_raw_fd();
+    // SAFETY: `F_GETFL` and `F_SETFL` only read and change the flags of the descriptor, which stays open for the
+    // duration of the calls.
+    unsafe {
+        let flags = libc::fcntl(descriptor, libc::F_GETFL);
+        if flags == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = if enabled { flags | libc::O_DIRECT } else { flags & !libc::O_DIRECT };
+        if libc::fcntl(descriptor, libc::F_SETFL, flags) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Sets or clears the `O_DIRECT` flag of `file`. Direct I/O is only supported on Linux, so it can only be cleared.
+#[cfg(not(target_os = "linux"))]
+fn set_o_direct(_file: &File, enabled: bool) -> io::Result<()> {
+    match enabled {
+        true => Err(io::Error::new(io::ErrorKind::Unsupported, "direct I/O is only supported on Linux")),
+        false => Ok(()),
+    }
+}
+
+/// Opens the file at `path` with `O_RDONLY | O_NOATIME`, falling back to just `O_RDONLY` if that isn't allowed.
+#[cfg(target_os = "linux")]
+fn open_read_only(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    match OpenOptions::new().read(true).custom_flags(libc::O_NOATIME).open(path) {
+        Err(err) if err.raw_os_error() == Some(libc::EPERM) => OpenOptions::new().read(true).open(path),
+        result => result,
+    }
+}
+
+/// Opens the file at `path` for reading only.
+#[cfg(not(target_os = "linux"))]
+fn open_read_only(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).open(path)
+}
+
+/// Checks whether the kernel has marked `file` as read-only, using the `BLKROGET` ioctl, or the device's `ro`
+/// attribute in sysfs if that fails.
+#[cfg(target_os = "linux")]
+fn query_write_protection(file: &File) -> WriteProtection {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    use std::os::unix::io::AsRawFd;
+
+    /// The ioctl which reads the read-only flag of a block device: `_IO(0x12, 94)` in `linux/fs.h`.
+    const BLKROGET: u32 = 0x125e;
+
+    let Ok(metadata) = file.metadata() else {
+        return WriteProtection::Unknown;
+    };
+    if !metadata.file_type().is_block_device() {
+        return WriteProtection::Unknown;
+    }
+
+    let mut read_only: libc::c_int = 0;
+    // SAFETY: `BLKROGET` writes a single `int` through the pointer, which points to a live `c_int`.
+    // The request parameter's type differs between libc implementations, hence the cast.
+    let read_only = match unsafe { libc::ioctl(file.as_raw_fd(), BLKROGET as _, &mut read_only) } {
+        0 => Some(read_only != 0),
+        _ => {
+            let device = metadata.rdev();
+            crate::disk_info::sysfs::is_read_only(Path::new("/"), libc::major(device), libc::minor(device))
+        }
+    };
+    match read_only {
+        Some(true) => WriteProtection::ReadOnly,
+        Some(false) => WriteProtection::Writable,
+        None => WriteProtection::Unknown,
+    }
+}
+
+/// Checks whether the kernel has marked `file` as read-only. This is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+fn query_write_protection(_file: &File) -> WriteProtection {
+    WriteProtection::Unknown
+}
+
+/// Looks up the logical block size of `file` using the `BLKSSZGET` ioctl, or the device's `logical_block_size`
+/// attribute in sysfs if that fails. Returns `None` if `file` isn't a block device, or neither reports a valid size.
+#[cfg(target_os = "linux")]
+fn query_logical_block_size(file: &File) -> Option<usize> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    use std::os::unix::io::AsRawFd;
+
+    /// The ioctl which reads the logical block size of a block device: `_IO(0x12, 104)` in `linux/fs.h`.
+    const BLKSSZGET: u32 = 0x1268;
+
+    let metadata = file.metadata().ok()?;
+    if !metadata.file_type().is_block_device() {
+        return None;
+    }
+
+    let mut block_size: libc::c_int = 0;
+    // SAFETY: `BLKSSZGET` writes a single `int` through the pointer, which points to a live `c_int`.
+    // The request parameter's type differs between libc implementations, hence the cast.
+    let block_size = match unsafe { libc::ioctl(file.as_raw_fd(), BLKSSZGET as _, &mut block_size) } {
+        0 => Some(block_size.max(0) as usize),
+        _ => {
+            let device = metadata.rdev();
+            crate::disk_info::sysfs::logical_block_size(Path::new("/"), libc::major(device), libc::minor(device))
+                .map(|block_size| block_size as usize)
+        }
+    };
+    // Buffers can only be aligned to powers of 2, which every real block size is.
+    block_size.filter(|block_size| block_size.is_power_of_two())
+}
+
+/// Looks up the logical block size of `file`. This is only supported on Linux, which is the only platform where
+/// direct reads are used.
+#[cfg(not(target_os = "linux"))]
+fn query_logical_block_size(_file: &File) -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{create_image, test_data};
+
+    #[test]
+    fn files_can_be_read_and_seeked() {
+        let image = create_image(b"raw-reader");
+
+        let mut device = ReadOnlyDevice::open(image.path()).unwrap();
+        assert_eq!(device.path(), image.path());
+        assert_eq!(device.seek(SeekFrom::Start(4)).unwrap(), 4);
+        let mut contents = String::new();
+        device.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "reader");
+
+        // Regular files aren't block devices, so the kernel doesn't track whether they're read-only, or their block size.
+        assert_eq!(device.write_protection(), WriteProtection::Unknown);
+        assert_eq!(device.block_size(), DEFAULT_BLOCK_SIZE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files_are_opened_for_reading_only() {
+        use std::os::unix::io::AsRawFd;
+
+        let image = tempfile::NamedTempFile::new().unwrap();
+        let device = ReadOnlyDevice::open(image.path()).unwrap();
+        // SAFETY: `F_GETFL` only reads the flags of the descriptor, which stays open for the duration of the call.
+        let flags = unsafe { libc::fcntl(device.file.as_raw_fd(), libc::F_GETFL) };
+        assert_eq!(flags & libc::O_ACCMODE, libc::O_RDONLY);
+    }
+
+    #[test]
+    fn read_at_handles_aligned_and_unaligned_requests() {
+        let length = 5 * DEFAULT_BLOCK_SIZE + 700;
+        let data = test_data(length);
+        let image = create_image(&data);
+
+        for direct_io in [false, true] {
+            let mut device = ReadOnlyDevice::open(image.path()).unwrap();
+            device.set_direct_io(direct_io);
+
+            // Aligned reads go straight into the buffer, unaligned ones go through the bounce buffer, and both stop
+            // at the end of the image.
+            let mut aligned = DynAlignedBuffer::new(2 * DEFAULT_BLOCK_SIZE, DEFAULT_BLOCK_SIZE);
+            assert_eq!(device.read_at(DEFAULT_BLOCK_SIZE as u64, &mut aligned).unwrap(), aligned.len());
+            assert_eq!(&aligned[..], &data[DEFAULT_BLOCK_SIZE..3 * DEFAULT_BLOCK_SIZE]);
+            assert_eq!(device.read_at(4 * DEFAULT_BLOCK_SIZE as u64, &mut aligned).unwrap(), DEFAULT_BLOCK_SIZE + 700);
+            assert_eq!(&aligned[..DEFAULT_BLOCK_SIZE + 700], &data[4 * DEFAULT_BLOCK_SIZE..]);
+
+            for (offset, count) in [(0, 100), (513, 3 * DEFAULT_BLOCK_SIZE), (length - 10, 100), (length, 10)] {
+                let mut buffer = vec![0; count];
+                let filled = device.read_at(offset as u64, &mut buffer).unwrap();
+                assert_eq!(&buffer[..filled], &data[offset..(offset + count).min(length)], "read of {} at {}", count, offset);
+            }
+        }
+    }
+
+    #[test]
+    fn direct_io_can_be_turned_on_and_off() {
+        let image = create_image(&test_data(DEFAULT_BLOCK_SIZE));
+        let mut device = ReadOnlyDevice::open(image.path()).unwrap();
+        assert_eq!(device.direct_io(), DirectIo::Off);
+
+        device.set_direct_io(true);
+        if cfg!(target_os = "linux") {
+            // The image lives in the temporary directory, whose filesystem may or may not support direct I/O.
+            assert!(matches!(device.direct_io(), DirectIo::On | DirectIo::Unsupported));
+        } else {
+            assert_eq!(device.direct_io(), DirectIo::Unsupported);
+        }
+        device.set_direct_io(false);
+        assert_eq!(device.direct_io(), DirectIo::Off);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn files_which_reject_direct_io_are_read_through_the_page_cache() {
+        // Files in procfs can't be opened with `O_DIRECT`, but can still be read.
+        let mut device = ReadOnlyDevice::open(Path::new("/proc/self/stat")).unwrap();
+        device.set_direct_io(true);
+        assert_eq!(device.direct_io(), DirectIo::Unsupported);
+
+        let mut buffer = [0; 64];
+        let filled = device.read_at(0, &mut buffer).unwrap();
+        assert!(filled > 0);
+        assert!(buffer[..filled].starts_with(std::process::id().to_string().as_bytes()));
+    }
+
+    #[test]
+    fn output_paths_which_lead_to_the_device_are_rejected() {
+        let image = create_image(&test_data(DEFAULT_BLOCK_SIZE));
+        let device = ReadOnlyDevice::open(image.path()).unwrap();
+        let directory = tempfile::tempdir().unwrap();
+        let link = directory.path().join("link.dd");
+        fs::hard_link(image.path(), &link).unwrap();
+
+        for path in [image.path(), &link] {
+            let error = check_output_path(path, device.file_id()).unwrap_err();
+            assert_eq!(error.to_string(), "it's the file/device being inspected, which is never written to");
+        }
+        check_output_path(&directory.path().join("new.smap"), device.file_id()).unwrap();
+        let other_file = tempfile::NamedTempFile::new().unwrap();
+        check_output_path(other_file.path(), device.file_id()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_paths_which_lead_to_any_device_are_rejected() {
+        let error = check_output_path(Path::new("/dev/null"), None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(error.to_string(), "it's a device, which is never written to");
+    }
+
+    #[test]
+    fn only_errors_caused_by_damaged_media_are_media_errors() {
+        for &code in MEDIA_ERRORS {
+            assert!(is_media_error(&io::Error::from_raw_os_error(code)));
+        }
+        assert!(!is_media_error(&io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert!(!is_media_error(&io::Error::other("Input/output error")));
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let directory = tempfile::tempdir().unwrap();
+        let error = ReadOnlyDevice::open(&directory.path().join("missing.dd")).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
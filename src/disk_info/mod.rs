@@ -0,0 +1,232 @@
+
+#[cfg(target_os = "linux")]
+pub mod sysfs;
+
+use std::path::{Path, PathBuf};
+use sysinfo::{DiskExt, DiskType, System, SystemExt};
+
+/// A list of metric unit suffixes to describe quantities of bytes.
+/// Each unit in the vector, is 1024 times larger than the unit before it.
+pub const UNIT_SUFFIXES: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+
+/// Formats a quantity of bytes as a human readable string, using the largest unit that doesn't exceed it.
+pub fn format_byte_count(bytes: u64) -> String {
+    // Find the largest power of 1024 that's less than or equal to the number of bytes, ensuring that we don't
+    // exceed the number of unit suffixes hardcoded in this program.
+    let unit_order = std::cmp::min(UNIT_SUFFIXES.len() as u32 - 1, bytes.checked_ilog(1024).unwrap_or(0));
+    let unit_suffix = UNIT_SUFFIXES[unit_order as usize];
+    if unit_order == 0 {
+        format!("{bytes} {unit_suffix}")
+    } else {
+        format!("{:.1} {unit_suffix}", (bytes as f64) / (1024u64.pow(unit_order) as f64))
+    }
+}
+
+/// The kind of storage hardware that a disk uses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MediaType {
+    Ssd,
+    Hdd,
+    Unknown,
+}
+
+/// A disk, partition, or mounted volume that can be selected for reading.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Disk {
+    /// The name that the operating system uses for the disk, like `sda1` or `C:`.
+    pub name: String,
+    /// The path of the device node that the raw data of the disk can be read through, like `/dev/sda1`.
+    /// This is `None` for volumes whose underlying device couldn't be determined.
+    pub device_node: Option<PathBuf>,
+    /// The path where the disk's filesystem is mounted, if it is.
+    pub mount_point: Option<PathBuf>,
+    /// The name of the disk that this is a partition of, or `None` if it's a whole disk (or its disk is unknown).
+    pub parent: Option<String>,
+    /// The length of the disk in bytes.
+    pub size: u64,
+    /// The number of bytes used by the disk's filesystem, if it's mounted.
+    pub used_space: Option<u64>,
+    /// The logical and physical sector sizes of the disk in bytes, if they're known.
+    pub sector_sizes: Option<(u64, u64)>,
+    /// The filesystem that the disk is formatted with, if it's mounted.
+    pub file_system: Option<String>,
+    pub media_type: MediaType,
+    /// Whether the disk (or its media) can be removed from the system.
+    pub removable: bool,
+    /// Whether the operating system has marked the disk as read-only.
+    pub read_only: bool,
+    /// The model name and serial number reported by the disk's hardware, if any.
+    pub model: Option<String>,
+    pub serial: Option<String>,
+}
+
+/// Returns every disk that's currently available for scanning. On Linux, this is every block device (including
+/// ones which aren't mounted), with details of any mounted filesystems attached to them. Elsewhere, only mounted
+/// volumes are listed.
+pub fn get_disks() -> Vec<Disk> {
+    #[cfg(target_os = "linux")]
+    let mut disks = get_block_devices();
+    #[cfg(not(target_os = "linux"))]
+    let mut disks = Vec::new();
+
+    merge_volumes(&mut disks, get_mounted_volumes());
+    disks
+}
+
+/// Lists every whole disk and partition that the kernel knows about.
+/// If the devices can't be listed, a warning is printed and no devices are returned.
+#[cfg(target_os = "linux")]
+fn get_block_devices() -> Vec<Disk> {
+    let devices = sysfs::list_block_devices(Path::new("/")).unwrap_or_else(|err| {
+        eprintln!("warning: {err}");
+        Vec::new()
+    });
+
+    devices.into_iter().map(|device| Disk {
+        name: device.name,
+        device_node: Some(device.path),
+        mount_point: None,
+        parent: device.parent,
+        size: device.size,
+        used_space: None,
+        sector_sizes: Some((device.logical_sector_size, device.physical_sector_size)),
+        file_system: None,
+        media_type: match device.rotational {
+            Some(true) => MediaType::Hdd,
+            Some(false) => MediaType::Ssd,
+            None => MediaType::Unknown,
+        },
+        removable: device.removable,
+        read_only: device.read_only,
+        model: device.model,
+        serial: device.serial,
+    }).collect()
+}
+
+/// Lists the volumes which are currently mounted.
+fn get_mounted_volumes() -> Vec<Disk> {
+    let mut system_info = System::new();
+    system_info.refresh_disks_list();
+
+    system_info.disks().iter().map(|disk| {
+        let name = disk.name().to_string_lossy().into_owned();
+        let mount_point = disk.mount_point().to_owned();
+
+        // If the description of the file system is valid utf8, format it as a string, otherwise display the raw bytes.
+        let file_system = match std::str::from_utf8(disk.file_system()) {
+            Ok(s) => s.to_owned(),
+            Err(_) => format!("{:?}", disk.file_system()),
+        };
+
+        Disk {
+            device_node: volume_device_node(&name, &mount_point),
+            name,
+            mount_point: Some(mount_point),
+            parent: None,
+            size: disk.total_space(),
+            used_space: Some(disk.total_space().saturating_sub(disk.available_space())),
+            sector_sizes: None,
+            file_system: Some(file_system),
+            media_type: match disk.type_() {
+                DiskType::HDD => MediaType::Hdd,
+                DiskType::SSD => MediaType::Ssd,
+                DiskType::Unknown(_) => MediaType::Unknown,
+            },
+            removable: disk.is_removable(),
+            read_only: false,
+            model: None,
+            serial: None,
+        }
+    }).collect()
+}
+
+/// Works out the path of the device node underlying a mounted volume, from its name and mount point.
+fn volume_device_node(name: &str, mount_point: &Path) -> Option<PathBuf> {
+    // On Windows, volumes are named after their label, but the raw data of a drive letter can be read through
+    // `\\.\X:`. Everywhere else, mounted volumes are named after their device node.
+    if cfg!(windows) {
+        let drive = mount_point.to_str()?.trim_end_matches('\\');
+        return (drive.len() == 2 && drive.ends_with(':')).then(|| PathBuf::from(format!(r"\\.\{drive}")));
+    }
+    name.starts_with("/dev/").then(|| PathBuf::from(name))
+}
+
+/// Adds the details of each mounted volume in `volumes` to the disk in `disks` with the same device node.
+/// Volumes which don't match a disk are added to the end of `disks` instead. If a disk is mounted in several places,
+/// only its first mount point is kept.
+fn merge_volumes(disks: &mut Vec<Disk>, volumes: Vec<Disk>) {
+    for volume in volumes {
+        let existing = disks.iter_mut().find(|disk| volume.device_node.is_some() && disk.device_node == volume.device_node);
+        match existing {
+            Some(disk) if disk.mount_point.is_some() => {}
+            Some(disk) => {
+                disk.mount_point = volume.mount_point;
+                disk.used_space = volume.used_space;
+                disk.file_system = volume.file_system;
+            }
+            None => disks.push(volume),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a disk called `name` with the provided device node, and nothing else known about it.
+    fn disk(name: &str, device_node: Option<&str>) -> Disk {
+        Disk {
+            name: name.to_owned(),
+            device_node: device_node.map(PathBuf::from),
+            mount_point: None,
+            parent: None,
+            size: 1 << 30,
+            used_space: None,
+            sector_sizes: None,
+            file_system: None,
+            media_type: MediaType::Unknown,
+            removable: false,
+            read_only: false,
+            model: None,
+            serial: None,
+        }
+    }
+
+    /// Creates a volume mounted at `mount_point`, which uses the provided device node.
+    fn volume(device_node: Option<&str>, mount_point: &str) -> Disk {
+        Disk {
+            mount_point: Some(PathBuf::from(mount_point)),
+            used_space: Some(1 << 20),
+            file_system: Some("ext4".to_owned()),
+            ..disk(device_node.unwrap_or("overlay"), device_node)
+        }
+    }
+
+    #[test]
+    fn volumes_are_merged_into_their_block_device() {
+        let mut disks = vec![disk("sda", Some("/dev/sda")), disk("sda1", Some("/dev/sda1"))];
+        merge_volumes(&mut disks, vec![
+            volume(Some("/dev/sda1"), "/"),
+            volume(Some("/dev/sda1"), "/mnt/bind"),
+            volume(None, "/var/lib/docker"),
+        ]);
+
+        assert_eq!(disks.len(), 3);
+        assert_eq!(disks[0].mount_point, None);
+        assert_eq!(disks[1].mount_point, Some(PathBuf::from("/")));
+        assert_eq!(disks[1].file_system.as_deref(), Some("ext4"));
+        assert_eq!(disks[1].used_space, Some(1 << 20));
+        assert_eq!(disks[2].mount_point, Some(PathBuf::from("/var/lib/docker")));
+        assert_eq!(disks[2].device_node, None);
+    }
+
+    #[test]
+    fn volumes_are_read_through_their_device_node() {
+        if cfg!(windows) {
+            assert_eq!(volume_device_node("Data", Path::new("D:\\")), Some(PathBuf::from(r"\\.\D:")));
+        } else {
+            assert_eq!(volume_device_node("/dev/sdb1", Path::new("/media/usb")), Some(PathBuf::from("/dev/sdb1")));
+            assert_eq!(volume_device_node("tmpfs", Path::new("/tmp")), None);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Enumerates the block devices (whole disks and their partitions) that the Linux kernel knows about, using the
+//! information it exposes in sysfs and procfs. Unlike the list of mounted filesystems, this includes devices which
+//! are unmounted, unformatted, or too corrupted to mount.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The size of the units that the `size` attribute of a block device is measured in, regardless of its sector size.
+const SYSFS_SECTOR_SIZE: u64 = 512;
+
+/// The size of the units that `/proc/partitions` measures devices in.
+const PROC_BLOCK_SIZE: u64 = 1024;
+
+/// A whole disk or a partition, as described by the kernel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockDevice {
+    /// The kernel's name for the device, like `sda` or `nvme0n1p2`.
+    pub name: String,
+    /// The path of the device node that the device can be read through, like `/dev/sda`.
+    pub path: PathBuf,
+    /// The name of the disk that the device is a partition of, or `None` if it's a whole disk (or its disk is unknown).
+    pub parent: Option<String>,
+    /// The length of the device in bytes.
+    pub size: u64,
+    /// The smallest unit that the device can address, in bytes.
+    pub logical_sector_size: u64,
+    /// The smallest unit that the device can write without a read-modify-write cycle, in bytes.
+    pub physical_sector_size: u64,
+    /// The model name reported by the disk, if any.
+    pub model: Option<String>,
+    /// The serial number reported by the disk, if any.
+    pub serial: Option<String>,
+    /// Whether the disk has spinning platters, or `None` if the kernel doesn't say.
+    pub rotational: Option<bool>,
+    /// Whether the disk reports that its media can be removed.
+    pub removable: bool,
+    /// Whether the kernel has marked the device as read-only.
+    pub read_only: bool,
+}
+
+/// Lists every whole disk (from `/sys/block`) followed by its partitions, along with any devices that only appear
+/// in `/proc/partitions`. Devices with a size of 0 (like unused loop devices and empty card readers) are skipped.
+///
+/// `root` is the directory that contains `sys` and `proc`, which is `/` outside of tests.
+/// Returns an error if neither `/sys/block` nor `/proc/partitions` can be read.
+pub fn list_block_devices(root: &Path) -> io::Result<Vec<BlockDevice>> {
+    let mut devices = Vec::new();
+    let sysfs_result = list_sysfs_devices(&root.join("sys/block"), &mut devices);
+    let proc_result = fs::read_to_string(root.join("proc/partitions"));
+    if let (Err(err), Err(_)) = (&sysfs_result, &proc_result) {
+        return Err(io::Error::new(err.kind(), format!("Failed to read the list of block devices: {err}")));
+    }
+
+    // sysfs describes devices in more detail, so `/proc/partitions` is only used to fill in anything it's missing.
+    for (name, size) in proc_result.as_deref().map(parse_proc_partitions).unwrap_or_default() {
+        if size > 0 && !devices.iter().any(|device: &BlockDevice| device.name == name) {
+            devices.push(BlockDevice {
+                path: device_node_path(&name),
+                name,
+                parent: None,
+                size,
+                logical_sector_size: SYSFS_SECTOR_SIZE,
+                physical_sector_size: SYSFS_SECTOR_SIZE,
+                model: None,
+                serial: None,
+                rotational: None,
+                removable: false,
+                read_only: false,
+            });
+        }
+    }
+    Ok(devices)
+}
+
+/// Adds every disk in `block_dir` (which is `/sys/block`), and each of its partitions, to `devices`.
+fn list_sysfs_devices(block_dir: &Path, devices: &mut Vec<BlockDevice>) -> io::Result<()> {
+    let mut disk_names = read_dir_names(block_dir)?;
+    disk_names.sort();
+    for name in disk_names {
+        let disk_dir = block_dir.join(&name);
+        let Some(disk) = read_device(&disk_dir, &disk_dir, name.clone(), None) else {
+            continue;
+        };
+        devices.push(disk);
+
+        // Partitions are the subdirectories of the disk which contain a `partition` attribute.
+        let mut partition_names = read_dir_names(&disk_dir).unwrap_or_default();
+        partition_names.retain(|entry| disk_dir.join(entry).join("partition").is_file());
+        partition_names.sort_by_key(|entry| read_attribute::<u64>(&disk_dir.join(entry), "partition"));
+        for partition_name in partition_names {
+            let partition_dir = disk_dir.join(&partition_name);
+            devices.extend(read_device(&partition_dir, &disk_dir, partition_name, Some(name.clone())));
+        }
+    }
+    Ok(())
+}
+
+/// Reads the device described by `device_dir`. Attributes of the hardware (like its model and sector sizes) are read
+/// from `disk_dir`, which is the directory of the whole disk. Returns `None` if the device is empty.
+fn read_device(device_dir: &Path, disk_dir: &Path, name: String, parent: Option<String>) -> Option<BlockDevice> {
+    let size = read_attribute::<u64>(device_dir, "size")? * SYSFS_SECTOR_SIZE;
+    if size == 0 {
+        return None;
+    }
+
+    let queue_dir = disk_dir.join("queue");
+    let logical_sector_size = read_attribute(&queue_dir, "logical_block_size").unwrap_or(SYSFS_SECTOR_SIZE);
+    Some(BlockDevice {
+        path: device_node_path(&name),
+        name,
+        parent,
+        size,
+        logical_sector_size,
+        physical_sector_size: read_attribute(&queue_dir, "physical_block_size").unwrap_or(logical_sector_size),
+        // Only some drivers (like NVMe) put the serial number next to the model, others put it on the disk itself.
+        model: read_attribute(&disk_dir.join("device"), "model"),
+        serial: read_attribute(&disk_dir.join("device"), "serial").or_else(|| read_attribute(disk_dir, "serial")),
+        rotational: read_attribute::<u8>(&queue_dir, "rotational").map(|flag| flag != 0),
+        removable: read_attribute::<u8>(disk_dir, "removable").is_some_and(|flag| flag != 0),
+        read_only: read_attribute::<u8>(device_dir, "ro").is_some_and(|flag| flag != 0),
+    })
+}
+
+/// Checks whether the kernel has marked the block device with the provided major and minor numbers as read-only.
+/// Returns `None` if sysfs doesn't describe the device.
+pub fn is_read_only(root: &Path, major: u32, minor: u32) -> Option<bool> {
+    let device_dir = root.join("sys/dev/block").join(format!("{major}:{minor}"));
+    read_attribute::<u8>(&device_dir, "ro").map(|flag| flag != 0)
+}
+
+/// Looks up the logical block size of the block device with the provided major and minor numbers. Partitions don't
+/// have a `queue` directory of their own, so theirs is read from their disk. Returns `None` if sysfs doesn't describe
+/// the device.
+pub fn logical_block_size(root: &Path, major: u32, minor: u32) -> Option<u64> {
+    let device_dir = root.join("sys/dev/block").join(format!("{major}:{minor}"));
+    read_attribute(&device_dir.join("queue"), "logical_block_size")
+        .or_else(|| read_attribute(&device_dir.join("../queue"), "logical_block_size"))
+}
+
+/// Parses the contents of `/proc/partitions` into the name and size (in bytes) of each device.
+/// The first line is a header, and each line after it has 4 columns: major, minor, size in KiB, and name.
+fn parse_proc_partitions(contents: &str) -> Vec<(String, u64)> {
+    contents.lines().skip(1).filter_map(|line| {
+        let [_, _, blocks, name] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        Some((name.to_owned(), blocks.parse::<u64>().ok()? * PROC_BLOCK_SIZE))
+    }).collect()
+}
+
+/// Returns the path of the device node for the device called `name`.
+/// Names containing `/` (like `cciss/c0d0`) have it replaced with `!` in sysfs.
+fn device_node_path(name: &str) -> PathBuf {
+    Path::new("/dev").join(name.replace('!', "/"))
+}
+
+/// Reads the attribute called `name` from the sysfs directory `dir`, and parses it. Returns `None` if the attribute
+/// doesn't exist, is empty, or can't be parsed.
+fn read_attribute<T: std::str::FromStr>(dir: &Path, name: &str) -> Option<T> {
+    let value = fs::read_to_string(dir.join(name)).ok()?;
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Returns the names of every entry in `dir`.
+fn read_dir_names(dir: &Path) -> io::Result<Vec<String>> {
+    fs::read_dir(dir)?.map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `value` to the attribute at `path` (relative to `root`), creating any missing directories.
+    fn write_attribute(root: &Path, path: &str, value: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{value}\n")).unwrap();
+    }
+
+    /// Creates a fake tree with an SSD that has 2 partitions, a read-only card reader, and an unused loop device.
+    fn create_fake_tree() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let root_path = root.path();
+        for (path, value) in [
+            ("sys/block/sda/size", "1953525168"),
+            ("sys/block/sda/ro", "0"),
+            ("sys/block/sda/removable", "0"),
+            ("sys/block/sda/queue/logical_block_size", "512"),
+            ("sys/block/sda/queue/physical_block_size", "4096"),
+            ("sys/block/sda/queue/rotational", "0"),
+            ("sys/block/sda/device/model", "Samsung SSD 860   "),
+            ("sys/block/sda/device/serial", "S3Z9NB0K"),
+            ("sys/block/sda/sda2/partition", "2"),
+            ("sys/block/sda/sda2/size", "1000000"),
+            ("sys/block/sda/sda2/ro", "0"),
+            ("sys/block/sda/sda1/partition", "1"),
+            ("sys/block/sda/sda1/size", "2048"),
+            ("sys/block/sda/sda1/ro", "1"),
+            ("sys/block/sdb/size", "62333952"),
+            ("sys/block/sdb/ro", "1"),
+            ("sys/block/sdb/removable", "1"),
+            ("sys/block/sdb/queue/logical_block_size", "4096"),
+            ("sys/block/sdb/queue/rotational", "1"),
+            ("sys/block/loop0/size", "0"),
+        ] {
+            write_attribute(root_path, path, value);
+        }
+        // The tree also has directories which aren't partitions, like `queue` and `device`.
+        write_attribute(root_path, "sys/block/sda/power/control", "auto");
+        write_attribute(
+            root_path,
+            "proc/partitions",
+            "major minor  #blocks  name\n\n   8        0  976762584 sda\n   8        1       1024 sda1\n 254        0       4096 dm-0\n",
+        );
+        root
+    }
+
+    #[test]
+    fn disks_and_partitions_are_listed_in_order() {
+        let root = create_fake_tree();
+        let devices = list_block_devices(root.path()).unwrap();
+        let names = devices.iter().map(|device| device.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["sda", "sda1", "sda2", "sdb", "dm-0"]);
+    }
+
+    #[test]
+    fn disk_attributes_are_read() {
+        let root = create_fake_tree();
+        let devices = list_block_devices(root.path()).unwrap();
+        assert_eq!(devices[0], BlockDevice {
+            name: "sda".to_owned(),
+            path: PathBuf::from("/dev/sda"),
+            parent: None,
+            size: 1953525168 * 512,
+            logical_sector_size: 512,
+            physical_sector_size: 4096,
+            model: Some("Samsung SSD 860".to_owned()),
+            serial: Some("S3Z9NB0K".to_owned()),
+            rotational: Some(false),
+            removable: false,
+            read_only: false,
+        });
+
+        // Missing attributes fall back to sensible defaults.
+        let card_reader = &devices[3];
+        assert_eq!((card_reader.logical_sector_size, card_reader.physical_sector_size), (4096, 4096));
+        assert_eq!((card_reader.model.as_deref(), card_reader.rotational), (None, Some(true)));
+        assert!(card_reader.removable && card_reader.read_only);
+    }
+
+    #[test]
+    fn partitions_inherit_the_attributes_of_their_disk() {
+        let root = create_fake_tree();
+        let devices = list_block_devices(root.path()).unwrap();
+        let partition = &devices[1];
+        assert_eq!(partition.path, PathBuf::from("/dev/sda1"));
+        assert_eq!(partition.parent.as_deref(), Some("sda"));
+        assert_eq!(partition.size, 2048 * 512);
+        assert_eq!(partition.physical_sector_size, 4096);
+        assert_eq!(partition.model.as_deref(), Some("Samsung SSD 860"));
+        assert!(partition.read_only);
+    }
+
+    #[test]
+    fn devices_missing_from_sysfs_are_read_from_proc() {
+        let root = create_fake_tree();
+        let devices = list_block_devices(root.path()).unwrap();
+        assert_eq!(devices[4].path, PathBuf::from("/dev/dm-0"));
+        assert_eq!(devices[4].size, 4096 * 1024);
+
+        // Without sysfs, every device comes from `/proc/partitions`.
+        fs::remove_dir_all(root.path().join("sys")).unwrap();
+        let devices = list_block_devices(root.path()).unwrap();
+        assert_eq!(devices.iter().map(|device| device.name.as_str()).collect::<Vec<_>>(), ["sda", "sda1", "dm-0"]);
+
+        fs::remove_dir_all(root.path().join("proc")).unwrap();
+        assert!(list_block_devices(root.path()).is_err());
+    }
+
+    #[test]
+    fn read_only_flags_are_looked_up_by_device_number() {
+        let root = create_fake_tree();
+        write_attribute(root.path(), "sys/dev/block/8:1/ro", "1");
+        write_attribute(root.path(), "sys/dev/block/8:2/ro", "0");
+        assert_eq!(is_read_only(root.path(), 8, 1), Some(true));
+        assert_eq!(is_read_only(root.path(), 8, 2), Some(false));
+        assert_eq!(is_read_only(root.path(), 8, 3), None);
+    }
+
+    #[test]
+    fn logical_block_sizes_are_looked_up_by_device_number() {
+        let root = create_fake_tree();
+        // Each entry in `/sys/dev/block` links to the device's directory under `/sys/block`.
+        fs::create_dir_all(root.path().join("sys/dev/block")).unwrap();
+        for (number, target) in [("8:0", "sda"), ("8:1", "sda/sda1"), ("8:16", "sdb")] {
+            std::os::unix::fs::symlink(root.path().join("sys/block").join(target), root.path().join("sys/dev/block").join(number)).unwrap();
+        }
+        assert_eq!(logical_block_size(root.path(), 8, 0), Some(512));
+        assert_eq!(logical_block_size(root.path(), 8, 1), Some(512));
+        assert_eq!(logical_block_size(root.path(), 8, 16), Some(4096));
+        assert_eq!(logical_block_size(root.path(), 8, 32), None);
+    }
+
+    #[test]
+    fn slashes_in_names_are_restored() {
+        assert_eq!(device_node_path("cciss!c0d0"), PathBuf::from("/dev/cciss/c0d0"));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::command::Help;
+use std::io::{self, Write};
+
+/// The help page for a single command (or mode of a command).
+pub struct HelpPage {
+    /// The syntax of the command, with each form of it on a separate line.
+    pub usage: &'static [&'static str],
+    /// A description of what the command does, and what its parameters mean.
+    pub description: &'static str,
+    /// Example commands, each paired with an explanation of what it does.
+    pub examples: &'static [(&'static str, &'static str)],
+}
+
+/// Every help topic, paired with the words which come after `help` to select it.
+pub const HELP_TOPICS: [(&[&str], Help); 14] = [
+    (&[], Help::None),
+    (&["seek"], Help::Seek),
+    (&["seek", "absolute"], Help::SeekAbsolute),
+    (&["seek", "relative"], Help::SeekRelative),
+    (&["find"], Help::Find),
+    (&["find", "nonzero"], Help::FindNonZero),
+    (&["find", "bytes"], Help::FindByte),
+    (&["find", "string"], Help::FindString),
+    (&["print"], Help::Print),
+    (&["bad-sectors"], Help::BadSectors),
+    (&["config"], Help::Config),
+    (&["source"], Help::Source),
+    (&["history"], Help::History),
+    (&["exit"], Help::Exit),
+];
+
+/// Returns the help page for `topic`.
+pub fn page_for(topic: Help) -> HelpPage {
+    match topic {
+        Help::None => HelpPage {
+            usage: &["help [COMMAND [MODE]]"],
+            description: "\
+Reads raw data from the selected file/device. The device is never written to.
+All commands operate relative to a cursor, which starts at the beginning of the device.
+
+Commands:
+    seek          Move the cursor to a new position.
+    print         Print the bytes at the cursor as a hexdump.
+    find          Search for data, starting at the cursor.
+    bad-sectors   List the sectors which couldn't be read.
+    config        View or change settings.
+    source        Run the commands in a script file.
+    history       List or re-run previously entered commands.
+    help          Show help for a command.
+    exit          Exit the program.
+
+Parameters are separated by whitespace. Use quotes to pass a parameter that contains whitespace;
+escape sequences (like '\\x00', '\\n' and '\\u{e9}') are processed outside of single quotes.
+
+Press Ctrl-C to stop a running command (it reports how far it got), or to discard the line being entered.
+Press Ctrl-D at an empty prompt to exit.",
+            examples: &[("help find", "Show help for the 'find' command."), ("help find bytes", "Show help for searching for bytes.")],
+        },
+        Help::Seek => HelpPage {
+            usage: &["seek absolute POSITION", "seek relative OFFSET"],
+            description: "\
+Moves the cursor to a new position in the device. Positions are measured in bytes.
+
+Modes:
+    absolute    Move the cursor to POSITION, measured from the start of the device.
+    relative    Move the cursor by OFFSET bytes, which can be negative to move backwards.",
+            examples: &[("seek absolute 4096", "Move the cursor to the start of the 9th sector."), ("seek relative -16", "Move the cursor back by 16 bytes.")],
+        },
+        Help::SeekAbsolute => HelpPage {
+            usage: &["seek absolute POSITION"],
+            description: "Moves the cursor to POSITION bytes from the start of the device. POSITION can't be negative or past the end of the device.",
+            examples: &[("seek absolute 0", "Move the cursor to the start of the device.")],
+        },
+        Help::SeekRelative => HelpPage {
+            usage: &["seek relative OFFSET"],
+            description: "Moves the cursor forward by OFFSET bytes, or backwards if OFFSET is negative. The cursor can't move outside the device.",
+            examples: &[("seek relative 512", "Move the cursor forward by 1 sector."), ("seek relative -1", "Move the cursor back by 1 byte.")],
+        },
+        Help::Find => HelpPage {
+            usage: &["find nonzero [PATH]", "find bytes PATTERN", "find string [OPTIONS] REGEX"],
+            description: "\
+Searches the device for data, starting at the cursor, and moves the cursor to the first result.
+
+Modes:
+    nonzero    Find all the sectors which contain non-zero data.
+    bytes      Find a pattern of bytes, with support for wildcards and gaps.
+    string     Find text matching a regex, in one or more text encodings.",
+            examples: &[("find nonzero", "Find the next sector with data in it."), ("find bytes 50 4B 03 04", "Find the start of zip files.")],
+        },
+        Help::FindNonZero => HelpPage {
+            usage: &["find nonzero [PATH]"],
+            description: "\
+Scans the entire device for sectors which contain non-zero data, prints a summary of where they are,
+and moves the cursor to the first of them at or after the cursor's sector.
+If PATH is provided, a map of the non-zero sectors is saved to that file.",
+            examples: &[("find nonzero", "Find the next sector with data in it."), ("find nonzero disk.smap", "Also save a map of the sectors to 'disk.smap'.")],
+        },
+        Help::FindByte => HelpPage {
+            usage: &["find bytes PATTERN"],
+            description: "\
+Searches for a pattern of bytes, and prints the position of every match. PATTERN is made up of:
+    4D 5A or 0x4d5a      Hexadecimal bytes, which can optionally be separated by spaces or commas.
+    [77, 90]             A list of decimal bytes.
+    ??                   A wildcard, which matches any byte.
+    4? or ?D             A nibble wildcard, which matches any byte with the specified high or low nibble.
+    {4} or {2-8}         A gap, which skips over any bytes, with a length in the specified range.
+    4D|5A or (4D | 5A)   An alternation, which matches any of the specified bytes.
+    \"PK\\x03\\x04\"         A quoted string, whose bytes are matched exactly.
+Patterns can't start or end with a gap.",
+            examples: &[
+                ("find bytes FF D8 FF", "Find the start of JPEG images."),
+                ("find bytes \"PK\\x03\\x04\"", "Find the start of zip files, using a quoted string."),
+                ("find bytes 00 00 01 (B3 | BA)", "Find MPEG video headers."),
+            ],
+        },
+        Help::FindString => HelpPage {
+            usage: &["find string [OPTIONS] REGEX"],
+            description: "\
+Searches for text matching REGEX, and prints the position and encoding of every match.
+REGEX must be quoted if it contains whitespace or starts with '-'; use single quotes if it contains backslashes.
+
+Options:
+    -i, --ignore-case    Match letters regardless of their case.
+    --encoding E         The encoding of the text: utf8 (the default), utf16le, utf16be, latin1,
+                         or any to search all of these encodings at once.
+    --max-length N       The length (in bytes) of the longest match that's guaranteed to be found (1024 by default).",
+            examples: &[
+                ("find string -i password", "Find the word 'password' in any case."),
+                ("find string --encoding any 'user(name)?=\\w+'", "Find usernames in any supported encoding."),
+            ],
+        },
+        Help::Print => HelpPage {
+            usage: &["print COUNT"],
+            description: "\
+Prints COUNT bytes as a hexdump, starting at the cursor, then moves the cursor past the printed bytes.
+Runs of identical rows are collapsed into a single '*' line. Sectors which can't be read are printed as zeroes.",
+            examples: &[("print 512", "Print 1 sector.")],
+        },
+        Help::BadSectors => HelpPage {
+            usage: &["bad-sectors [PATH]", "bad-sectors --load PATH"],
+            description: "\
+Lists the sectors which couldn't be read since the device was opened. Reads which fail are retried one sector at a time,
+and any sectors which still can't be read are treated as zeroes, so that commands can carry on past them.
+If PATH is provided, a map of the unreadable sectors is saved to that file, in the same format as 'find nonzero' uses.
+Paths which start with '--' have to come after a '--' of their own, like 'bad-sectors -- --bad.smap'.
+
+With '--load', the sectors in a map saved by an earlier session are treated as unreadable without being read,
+since a damaged device can take several seconds to give up on each one. They're listed along with any new ones.",
+            examples: &[
+                ("bad-sectors", "List the sectors which couldn't be read."),
+                ("bad-sectors bad.smap", "Also save a map of them to 'bad.smap'."),
+                ("bad-sectors --load bad.smap", "Skip the sectors in 'bad.smap' from now on."),
+            ],
+        },
+        Help::Config => HelpPage {
+            usage: &["config show", "config set SETTING VALUE", "config reset [SETTING]", "config save [PATH]", "config load [PATH]"],
+            description: "\
+Views or changes the settings which control how the device is read, scanned, and displayed.
+
+Actions:
+    show     Print the current value of every setting.
+    set      Change SETTING to VALUE.
+    reset    Change SETTING back to its default value, or every setting if SETTING isn't provided.
+    save     Save every setting to the file at PATH.
+    load     Replace every setting with the ones in the file at PATH (settings missing from it use their defaults).
+
+If PATH isn't provided, the settings file in the config directory is used ('$XDG_CONFIG_HOME/raw-reader/config',
+or '~/.config/raw-reader/config'). This file is loaded at startup, unless a different file is passed with '--config'.
+Settings files contain 1 setting per line, written as 'name = value'. Lines starting with '#' are ignored.
+
+Settings:
+    sector-size         The number of bytes in a sector: a power of 2 between 512 and 65536 (512 by default).
+    chunk-size          The number of bytes read at once while scanning or printing: a multiple of 65536, up to 67108864.
+    worker-threads      The number of threads used for scanning (1 per CPU core by default).
+    print-width         The number of bytes in each row printed by 'print' (16 by default).
+    display-base        The base that offsets are displayed in: hex (the default) or dec.
+    search-direction    The direction that 'find' searches in from the cursor: forward (the default) or backward.
+    script-errors       Whether scripts stop at the first line which fails (stop, the default) or continue.
+    read-mode           Whether reads bypass the page cache (direct, the default) or go through it (buffered).
+                        Devices which don't support direct reads are always read through the page cache.",
+            examples: &[
+                ("config set sector-size 4096", "Use 4 KiB sectors."),
+                ("config set search-direction backward", "Search backwards from the cursor."),
+                ("config reset", "Change every setting back to its default value."),
+                ("config save nvme.conf", "Save the current settings as a profile in 'nvme.conf'."),
+            ],
+        },
+        Help::Source => HelpPage {
+            usage: &["source PATH"],
+            description: "\
+Runs the commands in the script file at PATH, in order. Each line of the script holds a single command, written
+the same way it would be at the prompt. Blank lines, and lines starting with '#', are ignored.
+Errors are reported with the line they occurred on. Depending on the 'script-errors' setting, the script either
+stops at the first line which fails, or reports the error and carries on. An 'exit' command stops the script.
+Scripts can also be run without an interactive session: 'raw-reader --device DEVICE --script PATH'.",
+            examples: &[("source triage.rr", "Run the commands in 'triage.rr'.")],
+        },
+        Help::History => HelpPage {
+            usage: &["history [NUMBER]"],
+            description: "\
+Lists the commands entered at the prompt, numbered from oldest to newest. With NUMBER, the command with that
+number is re-run instead (and recorded in the history in place of the 'history' command).
+The history is saved in '$XDG_STATE_HOME/raw-reader' (or '~/.local/state/raw-reader'), so it's kept between
+sessions. The paths entered when selecting a device are saved separately. Press the up and down arrows to move
+through the history, or Ctrl-R to search it.",
+            examples: &[("history", "List the previously entered commands."), ("history 3", "Re-run the 3rd command in the list.")],
+        },
+        Help::Exit => HelpPage {
+            usage: &["exit"],
+            description: "Exits the program. Pressing Ctrl-D at an empty prompt does the same. Inside a script, 'exit' only stops the script.",
+            examples: &[],
+        },
+    }
+}
+
+/// Prints the help page for `topic`.
+pub fn print_help(topic: Help, out: &mut impl Write) -> io::Result<()> {
+    let page = page_for(topic);
+
+    writeln!(out, "Usage:")?;
+    for usage in page.usage {
+        writeln!(out, "    {usage}")?;
+    }
+    writeln!(out)?;
+    writeln!(out, "{}", page.description)?;
+
+    if !page.examples.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "Examples:")?;
+        let width = page.examples.iter().map(|(example, _)| example.len()).max().unwrap_or(0);
+        for (example, explanation) in page.examples {
+            writeln!(out, "    {example:<width$}    {explanation}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+    use regex::Regex;
+
+    /// The source of every module which produces error messages for the user.
+    const SOURCES: [(&str, &str); 8] = [
+        ("main.rs", include_str!("main.rs")),
+        ("command.rs", include_str!("command.rs")),
+        ("config.rs", include_str!("config.rs")),
+        ("lexer.rs", include_str!("lexer.rs")),
+        ("reader.rs", include_str!("reader.rs")),
+        ("script.rs", include_str!("script.rs")),
+        ("pattern/mod.rs", include_str!("pattern/mod.rs")),
+        ("pattern/encoding.rs", include_str!("pattern/encoding.rs")),
+    ];
+
+    #[test]
+    fn every_referenced_help_command_has_a_page() {
+        let reference = Regex::new(r#"['"](help(?: [a-z]+)*)['"]"#).unwrap();
+        let mut reference_count = 0;
+        for (file, source) in SOURCES {
+            for captures in reference.captures_iter(source) {
+                let command = &captures[1];
+                reference_count += 1;
+                assert!(
+                    matches!(command.parse::<Command>(), Ok(Command::Help(_))),
+                    "'{}' (referenced in {}) isn't a valid help command", command, file,
+                );
+            }
+        }
+        assert!(reference_count > 10, "only found {} references to help commands", reference_count);
+    }
+
+    #[test]
+    fn every_topic_can_be_parsed_and_printed() {
+        for (words, topic) in HELP_TOPICS {
+            let command = format!("help {}", words.join(" ")).to_uppercase();
+            let Ok(Command::Help(parsed)) = command.parse::<Command>() else {
+                panic!("failed to parse '{}'", command);
+            };
+            assert_eq!(parsed, topic);
+
+            let mut output = Vec::new();
+            print_help(topic, &mut output).unwrap();
+            assert!(String::from_utf8(output).unwrap().starts_with("Usage:\n    "));
+        }
+    }
+
+    #[test]
+    fn unknown_topics_are_rejected() {
+        for command in ["help fnd", "help find everything", "help seek absolute 0"] {
+            let error = command.parse::<Command>().unwrap_err();
+            assert!(error.contains("Unknown help topic"), "'{}' produced the wrong error: {}", command, error);
+        }
+    }
+
+    #[test]
+    fn pages_are_printed_with_usage_description_and_examples() {
+        let mut output = Vec::new();
+        print_help(Help::SeekRelative, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Usage:\n\
+             \x20   seek relative OFFSET\n\
+             \n\
+             Moves the cursor forward by OFFSET bytes, or backwards if OFFSET is negative. The cursor can't move outside the device.\n\
+             \n\
+             Examples:\n\
+             \x20   seek relative 512    Move the cursor forward by 1 sector.\n\
+             \x20   seek relative -1     Move the cursor back by 1 byte.\n",
+        );
+    }
+}
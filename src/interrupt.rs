@@ -0,0 +1,80 @@
+//! Lets Ctrl-C cancel the command that's running, instead of terminating the program.
+//!
+//! While the command prompt is waiting for input, the terminal is in raw mode, so Ctrl-C is read as a key press
+//! (which discards the line being edited). While a command is running, Ctrl-C instead sends `SIGINT`, which is caught
+//! here and used to set a flag. Long running operations check this flag regularly, and stop early once it's set.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// The flag which is set whenever `SIGINT` is received, once the handler has been installed.
+static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Installs a `SIGINT` handler which sets the returned flag, instead of terminating the program.
+/// The flag is never cleared by the handler; it's up to the caller to clear it before starting each operation.
+pub fn install_handler() -> io::Result<Arc<AtomicBool>> {
+    let flag = FLAG.get_or_init(|| Arc::new(AtomicBool::new(false))).clone();
+    register_handler()?;
+    Ok(flag)
+}
+
+#[cfg(unix)]
+fn register_handler() -> io::Result<()> {
+    extern "C" fn handle_sigint(_signal: libc::c_int) {
+        // Only async-signal-safe operations can be performed here; loading from a `OnceLock` and storing to an
+        // atomic are both lock-free.
+        if let Some(flag) = FLAG.get() {
+            flag.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // SAFETY: `action` is fully initialized before it's passed to `sigaction`, and the handler only performs
+    // async-signal-safe operations. `SA_RESTART` stops reads which are in progress from failing with `EINTR`.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn register_handler() -> io::Result<()> {
+    // Without a handler, Ctrl-C keeps its default behaviour of terminating the program.
+    Ok(())
+}
+
+/// Creates the error returned by operations which were stopped early, after `action` had been performed on
+/// `completed` of the `total` bytes the operation covers.
+pub fn interrupted_error(action: &str, completed: u64, total: u64) -> io::Error {
+    let percent = if total == 0 { 100.0 } else { (completed as f64) * 100.0 / (total as f64) };
+    let message = format!("Interrupted by Ctrl-C after {action} {completed} of {total} bytes ({percent:.1}%).");
+    io::Error::new(io::ErrorKind::Interrupted, message)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sigint_sets_the_flag() {
+        let flag = install_handler().unwrap();
+        flag.store(false, Ordering::SeqCst);
+        // SAFETY: raising a signal which has a handler installed is always safe.
+        assert_eq!(unsafe { libc::raise(libc::SIGINT) }, 0);
+        assert!(flag.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn progress_is_described_as_a_percentage() {
+        let err = interrupted_error("scanning", 512, 2048);
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(err.to_string(), "Interrupted by Ctrl-C after scanning 512 of 2048 bytes (25.0%).");
+        assert_eq!(interrupted_error("printing", 0, 0).to_string(), "Interrupted by Ctrl-C after printing 0 of 0 bytes (100.0%).");
+    }
+}
@@ -0,0 +1,247 @@
+//! Splits lines of user input into tokens, handling quoting and escape sequences.
+//!
+//! Tokens are separated by whitespace. Each token is made up of one or more adjacent parts, where each part is:
+//! - bare text, where escape sequences are processed (see below), and whitespace ends the token.
+//! - text wrapped in double quotes (`"`), where escape sequences are processed, and whitespace is kept.
+//! - text wrapped in single quotes (`'`), which is kept exactly as written; no escape sequences are processed.
+//!
+//! The supported escape sequences are `\\`, `\"`, `\'`, `\ ` (a space), `\n`, `\r`, `\t`, `\0`, `\xHH` (a single
+//! byte, written as 2 hexadecimal digits), and `\u{H...}` (a Unicode character, written as 1-6 hexadecimal digits).
+//! A backslash followed by any other character is kept as-is, so regexes like `\w+` can be entered without quotes.
+
+use std::ops::Range;
+
+/// A single token from a line of input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Token {
+    /// The token's value, after removing any quotes and processing any escape sequences.
+    /// This is stored as bytes, since `\xHH` escapes can produce bytes which aren't valid UTF-8.
+    pub value: Vec<u8>,
+    /// Whether any part of the token was wrapped in quotes.
+    pub quoted: bool,
+    /// The byte range that the token spans in the original input (including any quotes).
+    pub span: Range<usize>,
+}
+
+impl Token {
+    /// Returns the token's value as a string, or an error if its escape sequences produced invalid UTF-8.
+    pub fn text(&self, input: &str) -> Result<&str, String> {
+        std::str::from_utf8(&self.value).map_err(|_| {
+            format!(
+                "The parameter at column {} isn't valid UTF-8. Only '\\xHH' escapes below '\\x80' can be used here.",
+                column_of(input, self.span.start),
+            )
+        })
+    }
+
+    /// Returns the text that the token was lexed from, exactly as it appears in the input.
+    pub fn source<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.span.clone()]
+    }
+}
+
+/// Types which can be parsed from the tokens of a line of input.
+/// This is the token based equivalent of `FromStr`, and is used to parse the parameters of commands.
+pub trait FromTokens: Sized {
+    /// Parses a value from `tokens`, where `input` is the line of input which the tokens were lexed from.
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String>;
+}
+
+/// Splits `input` into tokens. See the module documentation for the syntax.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    loop {
+        // Skip any whitespace before the next token, and stop once we reach the end of the input.
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(start, _)) = chars.peek() else {
+            return Ok(tokens);
+        };
+
+        // Read parts until we hit whitespace or the end of the input, which marks the end of the token.
+        let mut value = Vec::new();
+        let mut quoted = false;
+        let mut quote_start = None;
+        let mut end = input.len();
+        while let Some((position, c)) = chars.next() {
+            match (quote_start, c) {
+                // Text inside of single quotes is taken as-is, up until the closing quote.
+                (Some((_, '\'')), '\'') | (Some((_, '"')), '"') => quote_start = None,
+                (Some((_, '\'')), c) => push_char(&mut value, c),
+
+                // Outside of single quotes, backslashes start escape sequences.
+                (_, '\\') => {
+                    let Some((_, escaped)) = chars.next() else {
+                        return Err(error_at(input, position, "the input ends with an incomplete escape sequence ('\\')"));
+                    };
+                    match escaped {
+                        'n' => value.push(b'\n'),
+                        'r' => value.push(b'\r'),
+                        't' => value.push(b'\t'),
+                        '0' => value.push(0),
+                        '\\' | '"' | '\'' | ' ' => push_char(&mut value, escaped),
+                        'x' => {
+                            let digits = (0..2).filter_map(|_| chars.next_if(|(_, c)| c.is_ascii_hexdigit()));
+                            let digits = digits.map(|(_, c)| c).collect::<String>();
+                            if digits.len() != 2 {
+                                return Err(error_at(input, position, "'\\x' escapes must be followed by 2 hexadecimal digits"));
+                            }
+                            value.push(u8::from_str_radix(&digits, 16).unwrap());
+                        }
+                        'u' => {
+                            let escaped = parse_unicode_escape(&mut chars)
+                                .ok_or_else(|| error_at(input, position, "'\\u' escapes must be of the form '\\u{H...}', with 1-6 hexadecimal digits that form a valid Unicode character"))?;
+                            push_char(&mut value, escaped);
+                        }
+                        other => {
+                            value.push(b'\\');
+                            push_char(&mut value, other);
+                        }
+                    }
+                }
+                (Some(_), c) => push_char(&mut value, c),
+
+                // Outside of quotes, whitespace ends the token, and quotes start a new quoted part.
+                (None, c) if c.is_whitespace() => {
+                    end = position;
+                    break;
+                }
+                (None, '"' | '\'') => {
+                    quoted = true;
+                    quote_start = Some((position, c));
+                }
+                (None, c) => push_char(&mut value, c),
+            }
+        }
+
+        if let Some((position, quote)) = quote_start {
+            return Err(error_at(input, position, &format!("missing closing quote ({quote})")));
+        }
+
+        tokens.push(Token { value, quoted, span: start..end });
+    }
+}
+
+/// Returns an error describing the first of `remainder` if it isn't empty, where `help` is the help command
+/// which explains the parameters that were expected.
+pub fn reject_additional_tokens(input: &str, remainder: &[Token], help: &str) -> Result<(), String> {
+    if let Some(extra) = remainder.first() {
+        let column = column_of(input, extra.span.start);
+        Err(format!("Unexpected extra parameter at column {column}: '{}'. Enter '{help}' for an explanation.", extra.source(input)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the (1 based) column of the character at `position` (a byte index) in `input`.
+pub fn column_of(input: &str, position: usize) -> usize {
+    input[..position].chars().count() + 1
+}
+
+/// Parses the `{H...}` part of a `\u{H...}` escape, returning `None` if it's malformed.
+fn parse_unicode_escape(chars: &mut std::iter::Peekable<std::str::CharIndices>) -> Option<char> {
+    chars.next_if(|&(_, c)| c == '{')?;
+    let mut digits = String::new();
+    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+        digits.push(c);
+    }
+    chars.next_if(|&(_, c)| c == '}')?;
+
+    if digits.is_empty() || digits.len() > 6 {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+}
+
+/// Appends the UTF-8 encoding of `c` to `value`.
+fn push_char(value: &mut Vec<u8>, c: char) {
+    value.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// Creates an error message for an error at the specified byte index in the input.
+fn error_at(input: &str, position: usize, message: &str) -> String {
+    format!("Invalid input at column {}: {message}.", column_of(input, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokenizes `input` and returns the value of each token as a string.
+    fn values(input: &str) -> Vec<String> {
+        tokenize(input).unwrap().into_iter().map(|token| String::from_utf8(token.value).unwrap()).collect()
+    }
+
+    #[test]
+    fn tokens_are_split_on_whitespace() {
+        assert_eq!(values("  find   bytes\tFF D8 "), vec!["find", "bytes", "FF", "D8"]);
+        assert_eq!(values(""), Vec::<String>::new());
+        assert_eq!(values(" \t "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn quotes_keep_whitespace_together() {
+        assert_eq!(values(r#"find string "hello world" 'a  b'"#), vec!["find", "string", "hello world", "a  b"]);
+        assert_eq!(values(r#"ab"c d"'e'f"#), vec!["abc def"]);
+        assert_eq!(values(r#""" ''"#), vec!["", ""]);
+        assert_eq!(values(r#""it's" 'say "hi"'"#), vec!["it's", "say \"hi\""]);
+    }
+
+    #[test]
+    fn tokens_record_whether_they_were_quoted_and_their_span() {
+        let input = r#"seek  "abs"olute 10"#;
+        let tokens = tokenize(input).unwrap();
+        assert_eq!(tokens.iter().map(|token| token.quoted).collect::<Vec<_>>(), vec![false, true, false]);
+        assert_eq!(tokens.iter().map(|token| token.source(input)).collect::<Vec<_>>(), vec!["seek", r#""abs"olute"#, "10"]);
+    }
+
+    #[test]
+    fn escape_sequences_are_processed() {
+        let tokens = tokenize(r#"a\x00\xFFb "\n\r\t\0\\\"\'" \u{1F600}\u{e9} a\ b"#).unwrap();
+        let values = tokens.into_iter().map(|token| token.value).collect::<Vec<_>>();
+        assert_eq!(values, vec![
+            b"a\x00\xFFb".to_vec(),
+            b"\n\r\t\0\\\"'".to_vec(),
+            "\u{1F600}\u{e9}".as_bytes().to_vec(),
+            b"a b".to_vec(),
+        ]);
+    }
+
+    #[test]
+    fn single_quotes_are_taken_literally() {
+        assert_eq!(values(r"'\x00\n\\'"), vec![r"\x00\n\\"]);
+    }
+
+    #[test]
+    fn unknown_escape_sequences_are_kept() {
+        assert_eq!(values(r#"user(name)?=\w+ "\d{3}""#), vec![r"user(name)?=\w+", r"\d{3}"]);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected_with_its_position() {
+        let test_cases = [
+            (r#"find string "abc"#, "column 13: missing closing quote (\")"),
+            ("find string 'abc", "column 13: missing closing quote (')"),
+            (r"find string \x4", "column 13: '\\x' escapes must be followed by 2 hexadecimal digits"),
+            (r"find string \xZZ", "column 13: '\\x' escapes"),
+            (r"é \u{D800}", "column 3: '\\u' escapes"),
+            (r"\u{1234567}", "column 1: '\\u' escapes"),
+            (r"\u1234", "column 1: '\\u' escapes"),
+            (r"\u{12", "column 1: '\\u' escapes"),
+            ("abc \\", "column 5: the input ends with an incomplete escape sequence"),
+        ];
+        for (input, expected_error) in test_cases {
+            let error = tokenize(input).unwrap_err();
+            assert!(error.contains(expected_error), "input '{}' produced the wrong error: {}", input, error);
+        }
+    }
+
+    #[test]
+    fn non_utf8_tokens_are_rejected_as_text() {
+        let input = r"abc \xFF";
+        let tokens = tokenize(input).unwrap();
+        assert_eq!(tokens[0].text(input), Ok("abc"));
+        assert!(tokens[1].text(input).unwrap_err().contains("column 5 isn't valid UTF-8"));
+    }
+}
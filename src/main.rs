@@ -4,95 +4,257 @@ mod math_util;
 
 mod command;
 mod command_line;
+mod config;
 mod data;
+mod device;
 mod disk_info;
+mod help;
+mod interrupt;
+mod lexer;
 mod pattern;
+mod pipeline;
+mod reader;
+mod script;
+#[cfg(test)]
+mod test_util;
+
+use command_line::args::{Arguments, USAGE};
+use command_line::handle::CommandInputHandler;
+use config::Settings;
+use device::{ReadOnlyDevice, WriteProtection};
+use reader::{DeviceReader, OutputFormat};
+use rustyline::error::ReadlineError;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// The exit codes that raw-reader can return. These are described in `USAGE`.
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_NOT_FOUND: i32 = 1;
+const EXIT_INVALID_INPUT: i32 = 2;
+const EXIT_DEVICE_ERROR: i32 = 3;
+const EXIT_TERMINAL_ERROR: i32 = 4;
+const EXIT_INTERRUPTED: i32 = 130;
 
 fn main() {
-    command_line::output::print_disk_selection_introduction();
-    let disk_info = disk_info::get_disk_info();
-    command_line::output::print_disk_info(&disk_info);
-    let file = command_line::input::get_user_disk_selection(&disk_info[1]);
-    command_line::output::print_disk_selection_complete();
-
-    let mut input_handler = command_line::handle::CommandInputHandler::new();
-    loop {
-        match input_handler.prompt("\n> ").parse::<command::Command>() {
-            Ok(command) => process_command(command),
-            Err(err) => eprintln!("error: {err}"),
+    let arguments = match Arguments::parse(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(EXIT_INVALID_INPUT);
         }
+    };
+    if arguments.help {
+        // The usage message is often piped into a pager, so a closed pipe isn't worth reporting.
+        let _ = writeln!(std::io::stdout(), "{USAGE}");
+        return;
     }
+    let settings = load_startup_settings(&arguments);
+
+    // Ctrl-C stops the running command instead of killing the program. If the handler can't be installed, Ctrl-C
+    // still works, it just exits the program instead.
+    let interrupt_flag = interrupt::install_handler().unwrap_or_else(|err| {
+        eprintln!("warning: Failed to install the Ctrl-C handler: {err}");
+        Arc::default()
+    });
+
+    let exit_code = match arguments.command {
+        Some(command) => {
+            // A device is always present alongside a command, since `Arguments::parse` checks for one.
+            let device = arguments.device.expect("commands can't be run without a device");
+            let mut reader = open_device(&device, settings, interrupt_flag);
+            reader.set_output_format(OutputFormat::Lines);
+            run_non_interactive(&mut reader, command)
+        }
+        None => run_interactive(arguments.device.as_deref(), settings, interrupt_flag),
+    };
+    std::process::exit(exit_code);
 }
 
-fn process_command(command: command::Command) {
-    match command {
-        
+/// Opens the file/device at `path`, or exits if it can't be opened.
+fn open_device(path: &Path, settings: Settings, interrupt_flag: Arc<AtomicBool>) -> DeviceReader {
+    match ReadOnlyDevice::open(path) {
+        Ok(device) => create_reader(device, settings, interrupt_flag),
+        Err(err) => {
+            eprintln!("error: Failed to open '{}': {err}", path.display());
+            std::process::exit(EXIT_DEVICE_ERROR);
+        }
     }
 }
 
-struct Test<'a, const N: usize> {
-    staging_buffer: data::aligned_buffer::AlignedBuffer<N>,
-    worker_buffer: data::aligned_buffer::AlignedBuffer<N>,
-
-    worker_channels: std::sync::mpsc::Sender<&'a data::aligned_buffer::AlignedBuffer<N>>,
-    completed_workers: std::sync::atomic::AtomicUsize,
+/// Creates a reader for `device` which uses `settings`, and is stopped early by `interrupt_flag`.
+/// Warns if the kernel allows the device to be written to, and exits if the length of the device can't be determined.
+fn create_reader(device: ReadOnlyDevice, settings: Settings, interrupt_flag: Arc<AtomicBool>) -> DeviceReader {
+    if device.write_protection() == WriteProtection::Writable {
+        eprintln!(
+            "warning: The kernel hasn't marked '{0}' as read-only. raw-reader never writes to it, but other programs \
+            still can. To prevent that, use a hardware write blocker, or run 'blockdev --setro {0}'.",
+            device.path().display(),
+        );
+    }
+    match DeviceReader::new(device) {
+        Ok(mut reader) => {
+            *reader.settings_mut() = settings;
+            reader.set_interrupt_flag(interrupt_flag);
+            reader
+        }
+        Err(err) => {
+            eprintln!("error: Failed to open the file/device: {err}");
+            std::process::exit(EXIT_DEVICE_ERROR);
+        }
+    }
 }
 
-impl<'a, const N: usize> Test<'a, N> {
-
+/// Runs a single command against the device, and returns the exit code that describes its outcome.
+fn run_non_interactive(reader: &mut DeviceReader, command: command::Command) -> i32 {
+    match process_command(reader, command) {
+        Ok(true) => EXIT_SUCCESS,
+        Ok(false) => EXIT_NOT_FOUND,
+        Err(err) => {
+            eprintln!("error: {err}");
+            if reader.is_interrupted() { EXIT_INTERRUPTED } else { EXIT_DEVICE_ERROR }
+        }
+    }
 }
 
+/// Prompts the user to select a device (unless one was already provided), then runs commands entered at the
+/// prompt until the user exits with `exit` or Ctrl-D. Returns the exit code to exit with.
+///
+/// Ctrl-C discards the line being entered, or stops the command that's running, but never exits the program.
+fn run_interactive(device: Option<&Path>, settings: Settings, interrupt_flag: Arc<AtomicBool>) -> i32 {
+    let mut reader = match device {
+        Some(path) => open_device(path, settings, interrupt_flag),
+        None => {
+            command_line::output::print_disk_selection_introduction();
+            let disks = disk_info::get_disks();
+            if let Err(err) = command_line::output::print_disks(&disks, &mut std::io::stdout().lock()) {
+                eprintln!("error: {err}");
+            }
+            match command_line::input::get_user_disk_selection(&disks) {
+                Ok(device) => create_reader(device, settings, interrupt_flag),
+                // Cancelling the selection isn't an error, since nothing has been done yet.
+                Err(ReadlineError::Interrupted | ReadlineError::Eof) => return EXIT_SUCCESS,
+                Err(err) => return report_terminal_error(err),
+            }
+        }
+    };
+    let direct_io = reader.apply_read_mode();
+    command_line::output::print_disk_selection_complete(reader.length(), reader.device().write_protection(), direct_io);
+
+    let history_path = command_line::history::history_path(command_line::history::COMMAND_HISTORY_FILE);
+    let mut input_handler = match CommandInputHandler::new(history_path) {
+        Ok(input_handler) => input_handler,
+        Err(err) => return report_terminal_error(err),
+    };
+    loop {
+        let line = match input_handler.prompt("\n> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return EXIT_SUCCESS,
+            Err(err) => return report_terminal_error(err),
+        };
+        let (line, parsed) = resolve_history_command(&input_handler, line);
+        input_handler.add_history_entry(&line);
+
+        match parsed {
+            Ok(command::Command::Exit) => return EXIT_SUCCESS,
+            Ok(command::Command::History(command::History::List)) => {
+                let result = command_line::history::print_history(input_handler.history(), &mut std::io::stdout().lock());
+                if let Err(err) = result {
+                    eprintln!("error: {err}");
+                }
+            }
+            Ok(command) => {
+                // A Ctrl-C which arrived between commands shouldn't stop the next one.
+                reader.clear_interrupt();
+                if let Err(err) = process_command(&mut reader, command) {
+                    eprintln!("error: {err}");
+                }
+            }
+            Err(err) => eprintln!("error: {err}"),
+        }
+    }
+}
 
+/// Parses `line` as a command. If it's `history NUMBER`, it's replaced by the command it re-runs, which is printed
+/// so the user can see what's being run. Returns the line that was run (which is what gets recorded in the history),
+/// and the parsed command.
+fn resolve_history_command(input_handler: &CommandInputHandler, line: String) -> (String, Result<command::Command, String>) {
+    let number = match line.parse::<command::Command>() {
+        Ok(command::Command::History(command::History::Run(number))) => number,
+        parsed => return (line, parsed),
+    };
+    let entry = match command_line::history::entry_numbered(input_handler.history(), number) {
+        Ok(entry) => entry.clone(),
+        Err(err) => return (line, Err(err)),
+    };
+
+    // Successful re-runs are recorded as the command they ran, but failed ones are recorded as-is. Those aren't
+    // followed, so that entries can't re-run each other forever.
+    match entry.parse::<command::Command>() {
+        Ok(command::Command::History(command::History::Run(_))) => {
+            (line, Err(format!("Command {number} re-runs another command, so it can't be re-run itself.")))
+        }
+        parsed => {
+            println!("{entry}");
+            (entry, parsed)
+        }
+    }
+}
 
+/// Prints an error that occurred while reading from the terminal, and returns the exit code to exit with.
+fn report_terminal_error(err: ReadlineError) -> i32 {
+    eprintln!("error: Failed to read from the terminal: {err}");
+    EXIT_TERMINAL_ERROR
+}
 
+/// Loads the settings file passed with `--config`, or the default settings file if there is one.
+/// A `--config` file which can't be loaded is a fatal error, but problems with the default file only produce a warning.
+fn load_startup_settings(arguments: &Arguments) -> Settings {
+    if let Some(path) = &arguments.config {
+        return Settings::load(path).unwrap_or_else(|err| {
+            eprintln!("error: {err}");
+            std::process::exit(EXIT_INVALID_INPUT);
+        });
+    }
 
-// so it has 2 buffers, it reads into one, while workers access the other buffer.
-// Before it can do a buffer swap it needs to make sure that everyone is done with everything.
-
-/*
-buffer1 = empty
-buffer2 = empty
-
-read-data-into-buffer1-during-initialization
-buffer1 = data0
-buffer2 = empty
-
-swap-buffers-during-initiliazation
-buffer1 = empty
-buffer2 = data0
-
-signal-to-workers-that-data-is-ready
-buffer1 = empty
-buffer2 = data0
-
-read-data-into-buffer1
-buffer1 = data1
-buffer2 = data0
-
-wait-for-workers-to-check-in
-buffer1 = data1
-buffer2 = data0
-
-swap-the-buffers
-buffer1 = OLD
-buffer2 = data1
-
-
-staging_buffer: AlignedBuffer<N>
-worker_buffer: AlignedBuffer<N>
-
-worker_channels: 
-
-
- */
+    match config::default_config_path() {
+        Some(path) if path.exists() => Settings::load(&path).unwrap_or_else(|err| {
+            eprintln!("warning: {err}. Using the default settings instead.");
+            Settings::default()
+        }),
+        _ => Settings::default(),
+    }
+}
 
+/// Executes the provided command against the selected file/device, printing any output to `stdout`.
+/// Returns `false` if the command was a search which didn't find anything, and `true` otherwise.
+fn process_command(reader: &mut DeviceReader, command: command::Command) -> Result<bool, String> {
+    let mut stdout = std::io::stdout().lock();
+    match command {
+        command::Command::Seek(seek) => reader.seek(&seek).map(|_| true),
+        command::Command::Print(print) => reader.print(&print, &mut stdout).map(|_| true),
+        command::Command::Find(find) => reader.find(&find, &mut stdout),
+        command::Command::BadSectors(command::BadSectors::List(path)) => {
+            reader.list_bad_sectors(path.as_deref(), &mut stdout).map(|_| true)
+        }
+        command::Command::BadSectors(command::BadSectors::Load(path)) => reader.load_bad_sectors(&path, &mut stdout).map(|_| true),
+        command::Command::Config(config) => reader.configure(&config, &mut stdout).map(|_| true),
+        command::Command::Source(path) => {
+            // The script's commands lock `stdout` themselves.
+            drop(stdout);
+            script::run_script(reader, &path, &mut process_command).map(|_| true)
+        }
+        command::Command::History(_) => Err("The command history can only be used at the interactive prompt.".to_owned()),
+        command::Command::Help(topic) => help::print_help(topic, &mut stdout).map(|_| true).map_err(|err| err.to_string()),
+        command::Command::Exit | command::Command::None => Ok(true),
+    }
+}
 
 // WORK ON COMMAND AND PATTERN!
 
 
-// TODO the command line gives us escaped strings but we don't use escapes!!
-
-
 // Open a device
 // Seek in the device
 // Print data from the device
@@ -113,92 +275,4 @@ worker_channels:
 // exit
 // help
 
-// config 
-
-
-
-
-
-// If a sector has data, and a section isn't open:
-//     write the address of the sector in 8 bytes
-
-
-
-
-// sector map
-// 8 sectors can be stored in a single byte, the bits of that byte correspond to the sector.
-//
-// 4 bytes can store 32 sectors
-// 5 bytes can store 40 sectors
-// 6 bytes can store 48 sectors
-// 8 bytes can store 64 sectors
-
-
-
-
-
-// address compression
-// Addresses are stored as sector addresses, so the exact byte location they point to depends on
-// the configured sector size.
-// These addresses use a compressed format, with the first 2 bits specifying the number of bytes
-// the address is encoded on (including those 2 bits). These are described below:
-
-
-// FOR A SECTOR SIZE OF 1
-// 2 bit header & 4 bytes       lets us store addresses up to (1 GB)
-// 2 bit header & 5 bytes       lets us store addresses up to (256 GB)
-// 2 bit header & 6 bytes       lets us store addresses up to (64 TB)
-// 2 bit header & 8 bytes       lets us store addresses up to (4096 PB)
-
-
-// FOR A SECTOR SIZE OF 1K
-// 2 bit header & 4 bytes       lets us store addresses up to (1 TB)
-// 2 bit header & 5 bytes       lets us store addresses up to (256 TB)
-// 2 bit header & 6 bytes       lets us store addresses up to (64 PB)
-// 2 bit header & 8 bytes       lets us store addresses up to (4096 EB)
-
-
-// FOR A SECTOR SIZE OF 4K
-// 2 bit header & 4 bytes       lets us store addresses up to (4 TB)
-// 2 bit header & 5 bytes       lets us store addresses up to (1 PB)
-// 2 bit header & 6 bytes       lets us store addresses up to (256 PB)
-// 2 bit header & 8 bytes       lets us store addresses up to (16384 EB)
-
-
-// FOR A SECTOR SIZE OF 16K
-// 2 bit header & 4 bytes       lets us store addresses up to (16 TB)
-// 2 bit header & 5 bytes       lets us store addresses up to (4 PB)
-// 2 bit header & 6 bytes       lets us store addresses up to (1 EB)
-// 2 bit header & 8 bytes       lets us store addresses up to (65536 EB)
-
-
-//the largest address we can store for X bytes with a 2bit header (2^((8*X)-2))
-
-
-
-
-
-// Segment lengths are always stored on 3 bytes.
-// If we hit the max value for this, we have to open a new segment, even if we theoretically didn't need to.
-// 3 bytes lets us store (2^24)*(8) =    134,217,728 sectors per segment        512GB for 4k sectors
-
-
-
-
-
-// sector maps are always of the form:
-// [address][segment_length][segment_map]
-// Where `address` is the compressed sector address that this segment starts at.
-// `segment_length` is the number of bytes stored in this segment (or -1 if this segment goes to EOF)
-// `segment_map` is a massive bit vector storing whether the sectors were empty or not.
-
-
-
-
-
-// Length of 
-
-
-// Things we want to accomplish:
-// - keep the number of addresses small (this makes it easier to search through)
-// - keep the file itself small too (makes it easier to store)
\ No newline at end of file
+// config
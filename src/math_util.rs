@@ -2,9 +2,11 @@
 /// Computes `dividend / divisor`, rounded up to the next integer.
 /// This only works for positive numbers, it must NOT be called with possible negative values.
 macro_rules! ceil_divide {
-    ($dividend:expr, $divisor:expr) => {
-        ($dividend + $divisor - 1) / $divisor
-    };
+    ($dividend:expr, $divisor:expr) => {{
+        #[allow(clippy::manual_div_ceil)]
+        let quotient = ($dividend + $divisor - 1) / $divisor;
+        quotient
+    }};
 }
 
 #[cfg(test)]
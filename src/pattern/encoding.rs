@@ -0,0 +1,309 @@
+//! Translates regexes so they match text stored in encodings other than UTF-8.
+//!
+//! Regexes are first parsed into a high-level intermediate representation (HIR), where every literal and class
+//! describes Unicode characters. Each of these is then re-encoded into the bytes it would be stored as in the
+//! target encoding, producing a byte-oriented regex which can run directly over the raw data of a device.
+
+use regex::bytes::{Regex, RegexBuilder};
+use regex_syntax::hir::{Capture, Class, ClassBytes, ClassBytesRange, ClassUnicode, ClassUnicodeRange, Hir, HirKind, Look, Repetition};
+use regex_syntax::ParserBuilder;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// The text encodings that string patterns can be searched for in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+/// Every supported encoding, along with the name used to refer to it on the command line.
+/// This is also the order encodings are searched in (and reported in) when searching in `any` encoding.
+pub const ENCODINGS: [(&str, Encoding); 4] = [
+    ("utf8", Encoding::Utf8),
+    ("utf16le", Encoding::Utf16Le),
+    ("utf16be", Encoding::Utf16Be),
+    ("latin1", Encoding::Latin1),
+];
+
+/// The name used to search for a pattern in every supported encoding at once.
+pub const ANY_ENCODING: &str = "any";
+
+impl Encoding {
+    /// Returns the name used to refer to this encoding on the command line.
+    pub fn name(self) -> &'static str {
+        ENCODINGS.iter().find(|(_, encoding)| *encoding == self).map(|(name, _)| *name).unwrap()
+    }
+
+    /// Compiles `pattern` into a byte-oriented regex which matches text stored in this encoding.
+    pub fn compile(self, pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+        // UTF-8 is what the regex engine natively matches, so the pattern can be compiled as-is.
+        if self == Encoding::Utf8 {
+            return RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|err| format!("Invalid regex: {err}"));
+        }
+
+        // Otherwise, parse the pattern, translate it to the target encoding, and compile the translated pattern.
+        // Case folding is applied while parsing, so the translated pattern is always case sensitive.
+        let hir = ParserBuilder::new()
+            .case_insensitive(ignore_case)
+            .utf8(false)
+            .build()
+            .parse(pattern)
+            .map_err(|err| format!("Invalid regex: {err}"))?;
+        let translated = translate(&hir, self);
+        RegexBuilder::new(&translated.to_string())
+            .unicode(false)
+            .build()
+            .map_err(|err| format!("Failed to compile the regex for {}: {err}", self.name()))
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        ENCODINGS.iter().find(|(name, _)| *name == lowercase).map(|(_, encoding)| *encoding).ok_or_else(|| {
+            let names = ENCODINGS.iter().map(|(name, _)| *name).chain([ANY_ENCODING]).collect::<Vec<_>>();
+            format!("Unknown encoding: '{s}'. Supported encodings are: {}.", names.join(", "))
+        })
+    }
+}
+
+/// Translates a UTF-8 based HIR into one that matches the same text stored in `encoding`.
+fn translate(hir: &Hir, encoding: Encoding) -> Hir {
+    match hir.kind() {
+        HirKind::Empty => Hir::empty(),
+        HirKind::Literal(literal) => match std::str::from_utf8(&literal.0) {
+            Ok(text) => Hir::concat(text.chars().map(|c| encode_char(c, encoding)).collect()),
+            // Literals that aren't valid UTF-8 were explicitly written as raw bytes, so we leave them as-is.
+            Err(_) => hir.clone(),
+        },
+        HirKind::Class(Class::Unicode(class)) => {
+            let ranges = class.ranges().iter().map(|range| (range.start() as u32, range.end() as u32));
+            Hir::alternation(ranges.flat_map(|(start, end)| encode_range(start, end, encoding)).collect())
+        }
+        HirKind::Class(Class::Bytes(_)) => hir.clone(),
+        HirKind::Look(look) => Hir::look(translate_look(*look)),
+        HirKind::Repetition(repetition) => Hir::repetition(Repetition {
+            min: repetition.min,
+            max: repetition.max,
+            greedy: repetition.greedy,
+            sub: Box::new(translate(&repetition.sub, encoding)),
+        }),
+        HirKind::Capture(capture) => Hir::capture(Capture {
+            index: capture.index,
+            name: capture.name.clone(),
+            sub: Box::new(translate(&capture.sub, encoding)),
+        }),
+        HirKind::Concat(subs) => Hir::concat(subs.iter().map(|sub| translate(sub, encoding)).collect()),
+        HirKind::Alternation(subs) => Hir::alternation(subs.iter().map(|sub| translate(sub, encoding)).collect()),
+    }
+}
+
+/// Unicode word boundaries can only be checked on UTF-8 text, so we fall back to checking ASCII word boundaries.
+/// For UTF-16 this is only an approximation, since the zero bytes between characters aren't word characters.
+fn translate_look(look: Look) -> Look {
+    match look {
+        Look::WordUnicode => Look::WordAscii,
+        Look::WordUnicodeNegate => Look::WordAsciiNegate,
+        Look::WordStartUnicode => Look::WordStartAscii,
+        Look::WordEndUnicode => Look::WordEndAscii,
+        Look::WordStartHalfUnicode => Look::WordStartHalfAscii,
+        Look::WordEndHalfUnicode => Look::WordEndHalfAscii,
+        other => other,
+    }
+}
+
+/// Returns an HIR which matches the bytes of `c` when stored in `encoding`.
+/// Characters that can't be stored in the encoding produce an HIR which never matches.
+fn encode_char(c: char, encoding: Encoding) -> Hir {
+    match encoding {
+        Encoding::Utf8 => Hir::literal(c.to_string().into_bytes()),
+        Encoding::Latin1 => u8::try_from(c as u32).map_or_else(|_| Hir::fail(), |byte| Hir::literal(vec![byte])),
+        Encoding::Utf16Le => Hir::literal(c.encode_utf16(&mut [0; 2]).iter().flat_map(|unit| unit.to_le_bytes()).collect::<Vec<_>>()),
+        Encoding::Utf16Be => Hir::literal(c.encode_utf16(&mut [0; 2]).iter().flat_map(|unit| unit.to_be_bytes()).collect::<Vec<_>>()),
+    }
+}
+
+/// Returns a list of alternatives which together match any character between `start` and `end` (inclusive)
+/// when stored in `encoding`. Both bounds are Unicode scalar values, so the range never starts or ends on a
+/// surrogate (but it can contain them).
+fn encode_range(start: u32, end: u32, encoding: Encoding) -> Vec<Hir> {
+    match encoding {
+        Encoding::Utf8 => {
+            let start = char::from_u32(start).unwrap();
+            let end = char::from_u32(end).unwrap();
+            vec![Hir::class(Class::Unicode(ClassUnicode::new([ClassUnicodeRange::new(start, end)])))]
+        }
+        Encoding::Latin1 => {
+            if start > 0xFF {
+                return vec![];
+            }
+            vec![byte_class(start as u8, end.min(0xFF) as u8)]
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let little_endian = encoding == Encoding::Utf16Le;
+            let mut alternatives = Vec::new();
+
+            // Characters in the basic multilingual plane are stored as a single code unit, but we have to
+            // make sure we don't match any surrogates that fall in the range, since they aren't characters.
+            for (bmp_start, bmp_end) in [(start, end.min(0xD7FF)), (start.max(0xE000), end.min(0xFFFF))] {
+                if bmp_start <= bmp_end {
+                    alternatives.extend(encode_unit_range(bmp_start as u16, bmp_end as u16, little_endian));
+                }
+            }
+
+            // Characters outside the basic multilingual plane are stored as a pair of surrogates. We split the
+            // range into pieces where either the high surrogate is fixed, or the low surrogate can be anything.
+            if end >= 0x10000 {
+                let (start, end) = (start.max(0x10000) - 0x10000, end - 0x10000);
+                let high = |value: u32| (0xD800 + (value >> 10)) as u16;
+                let low = |value: u32| (0xDC00 + (value & 0x3FF)) as u16;
+
+                let mut pieces = Vec::new();
+                if high(start) == high(end) {
+                    pieces.push((high(start), high(end), low(start), low(end)));
+                } else {
+                    pieces.push((high(start), high(start), low(start), 0xDFFF));
+                    if high(start) + 1 < high(end) {
+                        pieces.push((high(start) + 1, high(end) - 1, 0xDC00, 0xDFFF));
+                    }
+                    pieces.push((high(end), high(end), 0xDC00, low(end)));
+                }
+
+                for (high_start, high_end, low_start, low_end) in pieces {
+                    alternatives.push(Hir::concat(vec![
+                        Hir::alternation(encode_unit_range(high_start, high_end, little_endian)),
+                        Hir::alternation(encode_unit_range(low_start, low_end, little_endian)),
+                    ]));
+                }
+            }
+            alternatives
+        }
+    }
+}
+
+/// Returns a list of alternatives which together match any UTF-16 code unit between `start` and `end` (inclusive).
+/// Each alternative is a pair of byte classes (the low and high bytes of the code unit, in the stored order).
+fn encode_unit_range(start: u16, end: u16, little_endian: bool) -> Vec<Hir> {
+    let [start_high, start_low] = start.to_be_bytes();
+    let [end_high, end_low] = end.to_be_bytes();
+
+    // Split the range into pieces where each byte of the code unit can vary independently.
+    let mut pieces = Vec::new();
+    if start_high == end_high {
+        pieces.push((start_high, end_high, start_low, end_low));
+    } else {
+        pieces.push((start_high, start_high, start_low, 0xFF));
+        if start_high + 1 < end_high {
+            pieces.push((start_high + 1, end_high - 1, 0x00, 0xFF));
+        }
+        pieces.push((end_high, end_high, 0x00, end_low));
+    }
+
+    pieces.into_iter().map(|(high_start, high_end, low_start, low_end)| {
+        let high = byte_class(high_start, high_end);
+        let low = byte_class(low_start, low_end);
+        Hir::concat(if little_endian { vec![low, high] } else { vec![high, low] })
+    }).collect()
+}
+
+/// Returns an HIR which matches any single byte between `start` and `end` (inclusive).
+fn byte_class(start: u8, end: u8) -> Hir {
+    Hir::class(Class::Bytes(ClassBytes::new([ClassBytesRange::new(start, end)])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes `text` as UTF-16 with the specified byte order.
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() }).collect()
+    }
+
+    /// Compiles `pattern` for `encoding`, and returns the `(start, end)` of every match in `data`.
+    fn find_all(pattern: &str, ignore_case: bool, encoding: Encoding, data: &[u8]) -> Vec<(usize, usize)> {
+        let regex = encoding.compile(pattern, ignore_case).unwrap();
+        regex.find_iter(data).map(|found| (found.start(), found.end())).collect()
+    }
+
+    #[test]
+    fn encodings_are_parsed_from_their_names() {
+        for (name, encoding) in ENCODINGS {
+            assert_eq!(name.parse::<Encoding>(), Ok(encoding));
+            assert_eq!(name.to_uppercase().parse::<Encoding>(), Ok(encoding));
+            assert_eq!(encoding.to_string(), name);
+        }
+        assert!("utf32".parse::<Encoding>().unwrap_err().contains("utf8, utf16le, utf16be, latin1, any"));
+    }
+
+    #[test]
+    fn literals_are_encoded() {
+        for little_endian in [true, false] {
+            let encoding = if little_endian { Encoding::Utf16Le } else { Encoding::Utf16Be };
+            let mut data = b"password ".to_vec();
+            data.extend(utf16("my password", little_endian));
+            assert_eq!(find_all("password", false, encoding, &data), vec![(15, 31)]);
+        }
+
+        let data = b"caf\xE9 caf\xC3\xA9";
+        assert_eq!(find_all("café", false, Encoding::Latin1, data), vec![(0, 4)]);
+        assert_eq!(find_all("café", false, Encoding::Utf8, data), vec![(5, 10)]);
+    }
+
+    #[test]
+    fn case_insensitive_patterns_are_folded_before_encoding() {
+        let data = utf16("PassWord", true);
+        assert_eq!(find_all("password", true, Encoding::Utf16Le, &data), vec![(0, 16)]);
+        assert_eq!(find_all("password", false, Encoding::Utf16Le, &data), vec![]);
+
+        let data = b"\xC9T\xC9";
+        assert_eq!(find_all("été", true, Encoding::Latin1, data), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn classes_and_repetitions_are_encoded() {
+        let data = utf16("id=12345;", false);
+        assert_eq!(find_all("[a-z]+=[0-9]{3,}", false, Encoding::Utf16Be, &data), vec![(0, 16)]);
+
+        // Ranges spanning multiple high bytes must match every code unit in between, but nothing outside them.
+        let data = utf16("\u{00FF}\u{0100}\u{0234}\u{03FF}\u{0400}", true);
+        assert_eq!(find_all("[\\x{00FF}-\\x{03FF}]+", false, Encoding::Utf16Le, &data), vec![(0, 8)]);
+    }
+
+    #[test]
+    fn characters_outside_the_basic_multilingual_plane_are_encoded_as_surrogate_pairs() {
+        let data = utf16("a\u{1F600}b\u{10FFFF}", true);
+        assert_eq!(find_all("\\x{1F600}", false, Encoding::Utf16Le, &data), vec![(2, 6)]);
+        assert_eq!(find_all("[\\x{10000}-\\x{10FFFF}]", false, Encoding::Utf16Le, &data), vec![(2, 6), (8, 12)]);
+    }
+
+    #[test]
+    fn characters_outside_latin1_never_match() {
+        let data = b"abc";
+        assert_eq!(find_all("a|\\x{3042}", false, Encoding::Latin1, data), vec![(0, 1)]);
+        assert_eq!(find_all("b\\x{3042}", false, Encoding::Latin1, data), vec![]);
+        assert_eq!(find_all("[\\x{100}-\\x{3042}]", false, Encoding::Latin1, data), vec![]);
+    }
+
+    #[test]
+    fn invalid_regexes_are_rejected() {
+        for (_, encoding) in ENCODINGS {
+            assert!(encoding.compile("(abc", false).unwrap_err().contains("Invalid regex"));
+        }
+    }
+}
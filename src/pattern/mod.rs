@@ -0,0 +1,977 @@
+use crate::command::lookup;
+use crate::data::dyn_aligned_buffer::DynAlignedBuffer;
+use crate::lexer::{self, reject_additional_tokens, FromTokens, Token};
+use crate::pipeline::{Region, Scanner};
+use regex::bytes::Regex;
+use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::{CharIndices, FromStr};
+
+mod encoding;
+pub use encoding::{Encoding, ANY_ENCODING, ENCODINGS};
+
+/// The largest number of bytes that a single gap in a byte pattern can skip over.
+pub const MAX_GAP_LENGTH: usize = 0x10000;
+
+/// The default length of the longest match that a string pattern is guaranteed to find.
+pub const DEFAULT_MAX_MATCH_LENGTH: usize = 1024;
+
+/// The options which can come before the regex of a string pattern.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StringOption {
+    IgnoreCase,
+    Encoding,
+    MaxLength,
+}
+
+/// Every string pattern option, paired with the name used to pass it.
+pub const STRING_OPTIONS: [(&str, StringOption); 4] = [
+    ("-i", StringOption::IgnoreCase),
+    ("--ignore-case", StringOption::IgnoreCase),
+    ("--encoding", StringOption::Encoding),
+    ("--max-length", StringOption::MaxLength),
+];
+
+/// A single match found while searching a device.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Match {
+    /// The absolute byte offset (in the device) where the match starts.
+    pub offset: u64,
+    /// The number of bytes in the match.
+    pub length: usize,
+    /// The text encoding that the match was found in, or `None` if the pattern doesn't match text.
+    pub encoding: Option<Encoding>,
+}
+
+/// A set of byte values, stored as a 256 bit mask.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ByteSet([u64; 4]);
+
+impl ByteSet {
+    /// Returns a set containing only the specified byte.
+    pub fn single(byte: u8) -> Self {
+        let mut set = ByteSet::default();
+        set.insert(byte);
+        set
+    }
+
+    /// Adds the specified byte to this set.
+    pub fn insert(&mut self, byte: u8) {
+        self.0[(byte / 64) as usize] |= 1 << (byte % 64);
+    }
+
+    /// Adds all the bytes in `other` to this set.
+    pub fn union(&mut self, other: &ByteSet) {
+        for (bits, other_bits) in self.0.iter_mut().zip(other.0.iter()) {
+            *bits |= other_bits;
+        }
+    }
+
+    /// Returns whether this set contains the specified byte.
+    pub fn contains(&self, byte: u8) -> bool {
+        self.0[(byte / 64) as usize] & (1 << (byte % 64)) != 0
+    }
+
+    /// Returns the byte in this set if it only contains a single byte, otherwise returns `None`.
+    fn as_single(&self) -> Option<u8> {
+        let count: u32 = self.0.iter().map(|bits| bits.count_ones()).sum();
+        if count == 1 { (0..=255).find(|&byte| self.contains(byte)) } else { None }
+    }
+}
+
+/// A single element of a compiled byte pattern.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Element {
+    /// Matches exactly 1 byte, which must be in the set.
+    Byte(ByteSet),
+    /// Matches between `min` and `max` (inclusive) bytes of any value.
+    Gap { min: usize, max: usize },
+}
+
+/// A compiled pattern of bytes to search for, which is parsed from the following syntax:
+/// - `4D 5A`, `4D5A` or `0x4d5a`: hexadecimal bytes, which can optionally be separated by spaces or commas.
+/// - `[77, 90]`: a list of decimal bytes.
+/// - `??`: a wildcard which matches any single byte.
+/// - `4?` or `?D`: a nibble wildcard, which matches any byte with the specified high or low nibble.
+/// - `{4}` or `{2-8}`: a gap, which matches any sequence of bytes whose length is within the bounds.
+/// - `4D|5A` or `(4D | 5A | 6?)`: an alternation, which matches a single byte matching any of the options.
+/// - `"PK\x03\x04"` or `'MZ'`: a quoted string, whose bytes are matched exactly (after processing any escapes).
+///
+/// Patterns can't start or end with a gap, since the gap wouldn't affect what gets matched.
+#[derive(Debug)]
+pub struct BytePattern {
+    elements: Vec<Element>,
+    /// The first element of the pattern is always a byte, which we use to quickly skip over non-matches.
+    first: ByteSet,
+    /// The length of the longest sequence of bytes which could match this pattern.
+    max_length: usize,
+}
+
+impl BytePattern {
+    /// Checks whether this pattern matches `data` starting at `position`. If it does, this returns the length
+    /// of the shortest match, otherwise it returns `None`.
+    #[cfg(test)]
+    pub fn match_at(&self, data: &[u8], position: usize) -> Option<usize> {
+        let data = data.get(position..(position + self.max_length).min(data.len()))?;
+        self.shortest_matches(data, 1).first().map(|&(_, length)| length)
+    }
+
+    /// Finds the shortest match which starts at each position in `data` before `limit`, and returns the position and
+    /// length of each of them in ascending order. Matches can end anywhere in `data`, but not past its end.
+    ///
+    /// Rather than trying each length of each gap in turn (which takes exponential time with several wide gaps), or
+    /// matching the pattern at each position separately (which takes time proportional to the length of the longest
+    /// possible match), this works out where the shortest match of every suffix of the pattern ends, in a single
+    /// backward pass over `data`. Each element only has to look at the results of the element after it, either at the
+    /// next position (for bytes), or at the positions that a gap can skip to. The minimum of the latter is kept in a
+    /// sliding window, so every element takes constant time per position, however wide its gap.
+    fn shortest_matches(&self, data: &[u8], limit: usize) -> Vec<(usize, usize)> {
+        // `ends[index]` is where the shortest match of the elements from `index` onwards ends, when it starts at the
+        // current position (or `NO_MATCH`). `next_ends` holds the same for the position after the current one.
+        let count = self.elements.len();
+        let mut ends = vec![NO_MATCH; count + 1];
+        let mut next_ends = vec![NO_MATCH; count + 1];
+        let mut gaps: Vec<Option<SlidingMinimum>> = self.elements.iter().map(|element| match *element {
+            Element::Byte(_) => None,
+            Element::Gap { min, max } => Some(SlidingMinimum::new(min, max)),
+        }).collect();
+
+        let mut matches = Vec::new();
+        for position in (0..=data.len()).rev() {
+            ends[count] = position;
+            for (index, element) in self.elements.iter().enumerate().rev() {
+                ends[index] = match element {
+                    Element::Byte(set) if data.get(position).is_some_and(|&byte| set.contains(byte)) => next_ends[index + 1],
+                    Element::Byte(_) => NO_MATCH,
+                    Element::Gap { .. } => {
+                        let gap = gaps[index].as_mut().expect("every gap has a sliding window");
+                        gap.push(position, ends[index + 1])
+                    }
+                };
+            }
+            if position < limit && ends[0] != NO_MATCH {
+                matches.push((position, ends[0] - position));
+            }
+            std::mem::swap(&mut ends, &mut next_ends);
+        }
+        matches.reverse();
+        matches
+    }
+}
+
+/// Marks a position that a sequence of pattern elements doesn't match at.
+const NO_MATCH: usize = usize::MAX;
+
+/// Keeps track of the smallest value at the positions that a gap can skip to, as the position it starts at moves
+/// backward. Values are pushed for each position in turn, and only become part of the window once the gap is far
+/// enough behind them.
+struct SlidingMinimum {
+    min: usize,
+    max: usize,
+    /// Values which are too close to the start of the gap to be skipped to yet, in the order they were pushed.
+    pending: VecDeque<(usize, usize)>,
+    /// Values which are in the window (or have fallen out of it since the last push), in the order they were pushed.
+    /// Values are removed as soon as a smaller one is pushed after them, so the smallest one is always at the front.
+    window: VecDeque<(usize, usize)>,
+}
+
+impl SlidingMinimum {
+    /// Creates a window for a gap which skips between `min` and `max` bytes.
+    fn new(min: usize, max: usize) -> Self {
+        SlidingMinimum { min, max, pending: VecDeque::new(), window: VecDeque::new() }
+    }
+
+    /// Adds `value` for `position` (which must come before any position pushed so far), then returns the smallest
+    /// value at the positions which a gap starting at `position` can skip to, or `NO_MATCH` if there aren't any.
+    fn push(&mut self, position: usize, value: usize) -> usize {
+        if value != NO_MATCH {
+            self.pending.push_back((position, value));
+        }
+        while let Some(&(pending_position, pending_value)) = self.pending.front() {
+            if pending_position < position + self.min {
+                break;
+            }
+            self.pending.pop_front();
+            while self.window.back().is_some_and(|&(_, value)| value >= pending_value) {
+                self.window.pop_back();
+            }
+            self.window.push_back((pending_position, pending_value));
+        }
+        while self.window.front().is_some_and(|&(window_position, _)| window_position > position + self.max) {
+            self.window.pop_front();
+        }
+        self.window.front().map_or(NO_MATCH, |&(_, value)| value)
+    }
+}
+
+impl Scanner for BytePattern {
+    type Output = Vec<Match>;
+
+    fn overlap(&self) -> usize {
+        self.max_length - 1
+    }
+
+    fn scan(&self, chunk: &DynAlignedBuffer, region: &Region) -> Vec<Match> {
+        let window = &chunk[region.start..region.window_end];
+        let owned = region.end - region.start;
+
+        // Matches can only start where the first element matches, so we skip straight to those positions. If the
+        // pattern starts with a specific byte, we can just compare each byte with it.
+        let first_byte = self.first.as_single();
+        let mut starts = window[..owned].iter()
+            .enumerate()
+            .filter(|&(_, &byte)| first_byte.map_or_else(|| self.first.contains(byte), |first_byte| byte == first_byte))
+            .map(|(position, _)| position)
+            .peekable();
+
+        // Starts which are close enough for their matches to overlap are checked together, so that every byte in
+        // the window is only checked once.
+        let mut matches = Vec::new();
+        while let Some(start) = starts.next() {
+            let mut end = start + self.max_length;
+            while let Some(next) = starts.next_if(|&next| next < end) {
+                end = next + self.max_length;
+            }
+
+            let span = &window[start..end.min(window.len())];
+            for (position, length) in self.shortest_matches(span, owned - start) {
+                let offset = region.chunk_offset + (region.start + start + position) as u64;
+                matches.push(Match { offset, length, encoding: None });
+            }
+        }
+        matches
+    }
+}
+
+impl FromStr for BytePattern {
+    type Err = String;
+
+    /// Parses and compiles a byte pattern. See the documentation of `BytePattern` for the syntax.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BytePattern::from_tokens(s, &lexer::tokenize(s)?)
+    }
+}
+
+impl FromTokens for BytePattern {
+    /// Parses and compiles a byte pattern. See the documentation of `BytePattern` for the syntax.
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        let mut elements: Vec<Element> = Vec::new();
+        let mut remaining = tokens;
+        while let Some(token) = remaining.first() {
+            // Quoted tokens are matched exactly, byte for byte.
+            if token.quoted {
+                elements.extend(token.value.iter().map(|&byte| Element::Byte(ByteSet::single(byte))));
+                remaining = &remaining[1..];
+                continue;
+            }
+
+            // Runs of unquoted tokens are parsed straight from the input, since elements can contain whitespace.
+            let run_length = remaining.iter().take_while(|token| !token.quoted).count();
+            let (run, rest) = remaining.split_at(run_length);
+            let span = run[0].span.start..run[run_length - 1].span.end;
+            let mut parser = BytePatternParser::new(input, span);
+            while let Some(element) = parser.parse_next_element()? {
+                elements.extend(element);
+            }
+            remaining = rest;
+        }
+
+        // Merge consecutive gaps together, since they're equivalent to a single longer gap.
+        let mut merged: Vec<Element> = Vec::with_capacity(elements.len());
+        for element in elements {
+            match (merged.last_mut(), element) {
+                (Some(Element::Gap { min, max }), Element::Gap { min: next_min, max: next_max }) => {
+                    *min += next_min;
+                    *max += next_max;
+                }
+                (_, element) => merged.push(element),
+            }
+        }
+        let elements = merged;
+
+        // Check that the pattern is well formed.
+        let first = match elements.first() {
+            Some(Element::Byte(set)) => *set,
+            Some(Element::Gap { .. }) => return Err("A byte pattern can't start with a gap.".to_owned()),
+            None => return Err("Missing byte pattern to search for. Enter 'help find bytes' for an example.".to_owned()),
+        };
+        if let Some(Element::Gap { .. }) = elements.last() {
+            return Err("A byte pattern can't end with a gap.".to_owned());
+        }
+
+        let max_length = elements.iter().map(|element| match element {
+            Element::Byte(_) => 1,
+            Element::Gap { max, .. } => *max,
+        }).sum();
+        Ok(BytePattern { elements, first, max_length })
+    }
+}
+
+/// Parses the syntax described in `BytePattern` one element at a time.
+struct BytePatternParser<'a> {
+    /// The section of the line being parsed. All positions used by the parser are relative to this.
+    input: &'a str,
+    /// The entire line of input, and the byte offset of `input` within it. These are only used for error messages.
+    line: &'a str,
+    offset: usize,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> BytePatternParser<'a> {
+    /// Creates a parser for the section of `line` covered by `span`.
+    fn new(line: &'a str, span: Range<usize>) -> Self {
+        let input = &line[span.clone()];
+        BytePatternParser { input, line, offset: span.start, chars: input.char_indices().peekable() }
+    }
+
+    /// Parses the next group of elements from the input, or returns `None` if the end of the input was reached.
+    fn parse_next_element(&mut self) -> Result<Option<Vec<Element>>, String> {
+        self.skip_separators();
+        let Some(&(position, c)) = self.chars.peek() else {
+            return Ok(None);
+        };
+
+        match c {
+            '[' => {
+                self.chars.next();
+                self.parse_decimal_list().map(Some)
+            }
+            '{' => {
+                self.chars.next();
+                self.parse_gap().map(|gap| Some(vec![gap]))
+            }
+            '(' => {
+                self.chars.next();
+                self.parse_alternation().map(|set| Some(vec![Element::Byte(set)]))
+            }
+            c if c.is_ascii_hexdigit() || c == '?' => self.parse_hex_run().map(Some),
+            unexpected => Err(self.error_at(position, &format!("unexpected character '{unexpected}'"))),
+        }
+    }
+
+    /// Parses a run of hexadecimal bytes (optionally prefixed by `0x`), where bytes can be joined with `|`.
+    fn parse_hex_run(&mut self) -> Result<Vec<Element>, String> {
+        // Skip over the `0x` prefix if there is one.
+        let run_start = self.chars.peek().map(|&(position, _)| position).unwrap_or(self.input.len());
+        if self.input[run_start..].starts_with("0x") || self.input[run_start..].starts_with("0X") {
+            self.chars.next();
+            self.chars.next();
+        }
+
+        let mut elements = Vec::new();
+        while self.chars.peek().is_some_and(|&(_, c)| c.is_ascii_hexdigit() || c == '?') {
+            let mut set = self.parse_hex_byte()?;
+            while self.chars.peek().is_some_and(|&(_, c)| c == '|') {
+                self.chars.next();
+                set.union(&self.parse_hex_byte()?);
+            }
+            elements.push(Element::Byte(set));
+        }
+
+        if elements.is_empty() {
+            return Err(self.error_at(run_start, "expected a hexadecimal byte after '0x'"));
+        }
+        Ok(elements)
+    }
+
+    /// Parses a single hexadecimal byte, where either of the nibbles can be a `?` wildcard.
+    fn parse_hex_byte(&mut self) -> Result<ByteSet, String> {
+        let high = self.parse_hex_nibble()?;
+        let low = self.parse_hex_nibble()?;
+
+        let mut set = ByteSet::default();
+        for high_nibble in high.map_or(0..=15, |nibble| nibble..=nibble) {
+            for low_nibble in low.map_or(0..=15, |nibble| nibble..=nibble) {
+                set.insert((high_nibble << 4) | low_nibble);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Parses a single hexadecimal digit, returning `None` if it's a `?` wildcard.
+    fn parse_hex_nibble(&mut self) -> Result<Option<u8>, String> {
+        match self.chars.next() {
+            Some((_, '?')) => Ok(None),
+            Some((_, c)) if c.is_ascii_hexdigit() => Ok(Some(c.to_digit(16).unwrap() as u8)),
+            Some((position, c)) => Err(self.error_at(position, &format!("expected a hexadecimal digit, but found '{c}'"))),
+            None => Err(self.error_at(self.input.len(), "hexadecimal bytes must have 2 digits")),
+        }
+    }
+
+    /// Parses a bracketed list of decimal bytes, where the opening `[` has already been consumed.
+    /// Entries can be separated by spaces or commas, and can be `??` wildcards or alternations like `5|6`.
+    fn parse_decimal_list(&mut self) -> Result<Vec<Element>, String> {
+        let mut elements = Vec::new();
+        loop {
+            self.skip_separators();
+            match self.chars.peek() {
+                Some(&(_, ']')) => {
+                    self.chars.next();
+                    return Ok(elements);
+                }
+                Some(_) => {
+                    let mut set = self.parse_decimal_byte()?;
+                    while self.chars.peek().is_some_and(|&(_, c)| c == '|') {
+                        self.chars.next();
+                        set.union(&self.parse_decimal_byte()?);
+                    }
+                    elements.push(Element::Byte(set));
+                }
+                None => return Err(self.error_at(self.input.len(), "missing closing ']'")),
+            }
+        }
+    }
+
+    /// Parses a single decimal byte, or a `??` wildcard.
+    fn parse_decimal_byte(&mut self) -> Result<ByteSet, String> {
+        let start = self.chars.peek().map_or(self.input.len(), |&(position, _)| position);
+        if self.input[start..].starts_with("??") {
+            self.chars.next();
+            self.chars.next();
+            return Ok(ByteSet([u64::MAX; 4]));
+        }
+
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        match digits.parse::<u8>() {
+            Ok(byte) => Ok(ByteSet::single(byte)),
+            Err(_) if digits.is_empty() => Err(self.error_at(start, "expected a decimal byte")),
+            Err(_) => Err(self.error_at(start, &format!("'{digits}' is not a valid byte (it must be between 0 and 255)"))),
+        }
+    }
+
+    /// Parses a gap, where the opening `{` has already been consumed: either `{N}` or `{MIN-MAX}`.
+    fn parse_gap(&mut self) -> Result<Element, String> {
+        let start = self.chars.peek().map_or(self.input.len(), |&(position, _)| position);
+        let min = self.parse_gap_bound(start)?;
+        let max = if self.chars.peek().is_some_and(|&(_, c)| c == '-') {
+            self.chars.next();
+            self.parse_gap_bound(start)?
+        } else {
+            min
+        };
+
+        if !matches!(self.chars.next(), Some((_, '}'))) {
+            return Err(self.error_at(start, "gaps must be of the form '{N}' or '{MIN-MAX}'"));
+        }
+        if min > max {
+            return Err(self.error_at(start, "the minimum length of a gap can't be larger than its maximum length"));
+        }
+        if max > MAX_GAP_LENGTH {
+            return Err(self.error_at(start, &format!("gaps can't be longer than {MAX_GAP_LENGTH} bytes")));
+        }
+        Ok(Element::Gap { min, max })
+    }
+
+    /// Parses one of the bounds of a gap, which must be a decimal number.
+    fn parse_gap_bound(&mut self, gap_start: usize) -> Result<usize, String> {
+        self.take_while(char::is_whitespace);
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        self.take_while(char::is_whitespace);
+        digits.parse::<usize>().map_err(|_| self.error_at(gap_start, "gaps must be of the form '{N}' or '{MIN-MAX}'"))
+    }
+
+    /// Parses a parenthesized alternation of hexadecimal bytes, where the opening `(` has already been consumed.
+    fn parse_alternation(&mut self) -> Result<ByteSet, String> {
+        let mut set = ByteSet::default();
+        loop {
+            self.take_while(char::is_whitespace);
+            set.union(&self.parse_hex_byte()?);
+            self.take_while(char::is_whitespace);
+            match self.chars.next() {
+                Some((_, '|')) => continue,
+                Some((_, ')')) => return Ok(set),
+                Some((position, c)) => return Err(self.error_at(position, &format!("expected '|' or ')', but found '{c}'"))),
+                None => return Err(self.error_at(self.input.len(), "missing closing ')'")),
+            }
+        }
+    }
+
+    /// Skips over any whitespace or commas.
+    fn skip_separators(&mut self) {
+        self.take_while(|c| c.is_whitespace() || c == ',');
+    }
+
+    /// Consumes characters while they satisfy `predicate`, and returns the consumed characters.
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.chars.peek().map_or(self.input.len(), |&(position, _)| position);
+        let mut end = start;
+        while let Some(&(position, c)) = self.chars.peek() {
+            if !predicate(c) {
+                break;
+            }
+            end = position + c.len_utf8();
+            self.chars.next();
+        }
+        &self.input[start..end]
+    }
+
+    /// Creates an error message for an error at the specified byte index in the input.
+    fn error_at(&self, position: usize, message: &str) -> String {
+        let column = lexer::column_of(self.line, self.offset + position);
+        format!("Invalid byte pattern at column {column}: {message}. Enter 'help find bytes' for the pattern syntax.")
+    }
+}
+
+/// A regular expression to search for, which runs directly over the raw bytes of the device.
+///
+/// It's parsed from a (possibly quoted) regex, which can be preceded by the following options.
+/// Regexes that contain whitespace must be quoted, and single quotes should be used for regexes that contain
+/// backslashes, since escape sequences are processed in unquoted and double quoted text.
+/// - `-i` or `--ignore-case`: match letters regardless of their case.
+/// - `--encoding E`: the encoding of the text to search for; one of `utf8` (the default), `utf16le`, `utf16be`,
+///   `latin1`, or `any` to search for text in all of these encodings at once.
+/// - `--max-length N`: the length (in bytes) of the longest match that's guaranteed to be found (defaults to 1024).
+///   Matches can be found in chunks of data which overlap by this much, so longer matches may be cut short
+///   or missed if they cross the boundary between 2 chunks.
+#[derive(Debug)]
+pub struct StringPattern {
+    /// The regex to search with for each encoding, with the regex already translated into that encoding.
+    regexes: Vec<(Encoding, Regex)>,
+    max_length: usize,
+}
+
+impl StringPattern {
+    /// Finds all the matches for `regex` that start in the `owned` range of `window`, and appends them to `matches`.
+    /// UTF-16 text is only matched if it starts on an even offset, otherwise it'd be possible to match the
+    /// second byte of one character along with the first byte of the next.
+    ///
+    /// The search starts at the beginning of the window rather than the owned range, so that the end of a match which
+    /// starts before the owned range (and is reported by the previous region) isn't reported again as a match of its own.
+    fn find_in(encoding: Encoding, regex: &Regex, window: &[u8], owned: Range<usize>, window_offset: u64, matches: &mut Vec<Match>) {
+        let aligned = matches!(encoding, Encoding::Utf16Le | Encoding::Utf16Be);
+        let mut position = 0;
+        while position < owned.end {
+            let Some(found) = regex.find_at(window, position) else {
+                break;
+            };
+            if found.start() >= owned.end {
+                break;
+            }
+
+            let offset = window_offset + found.start() as u64;
+            if aligned && !offset.is_multiple_of(2) {
+                position = found.start() + 1;
+                continue;
+            }
+            if found.start() >= owned.start {
+                matches.push(Match { offset, length: found.len(), encoding: Some(encoding) });
+            }
+
+            // Empty matches would leave us stuck in place, so always make some progress.
+            position = if found.is_empty() { found.end() + 1 } else { found.end() };
+        }
+    }
+}
+
+impl Scanner for StringPattern {
+    type Output = Vec<Match>;
+
+    fn overlap(&self) -> usize {
+        self.max_length - 1
+    }
+
+    fn look_behind(&self) -> usize {
+        self.max_length - 1
+    }
+
+    fn scan(&self, chunk: &DynAlignedBuffer, region: &Region) -> Vec<Match> {
+        let window = &chunk[region.window_start..region.window_end];
+        let owned = region.start - region.window_start..region.end - region.window_start;
+        let window_offset = region.chunk_offset + region.window_start as u64;
+
+        // Only report matches which start inside this region; the rest are found by the next region.
+        let mut matches = Vec::new();
+        for (encoding, regex) in &self.regexes {
+            Self::find_in(*encoding, regex, window, owned.clone(), window_offset, &mut matches);
+        }
+
+        // When searching multiple encodings, put the matches back in order, and drop any duplicates.
+        // Pure ASCII text is identical in UTF-8 and Latin-1, so it's only reported for the first encoding.
+        if self.regexes.len() > 1 {
+            matches.sort_by_key(|found| (found.offset, found.length));
+            matches.dedup_by_key(|found| (found.offset, found.length));
+        }
+        matches
+    }
+}
+
+impl FromStr for StringPattern {
+    type Err = String;
+
+    /// Parses the options and regex of a string pattern. See the documentation of `StringPattern` for the syntax.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StringPattern::from_tokens(s, &lexer::tokenize(s)?)
+    }
+}
+
+impl FromTokens for StringPattern {
+    /// Parses the options and regex of a string pattern. See the documentation of `StringPattern` for the syntax.
+    fn from_tokens(input: &str, tokens: &[Token]) -> Result<Self, String> {
+        let mut remaining = tokens.iter();
+        let mut ignore_case = false;
+        let mut encodings = vec![Encoding::Utf8];
+        let mut max_length = DEFAULT_MAX_MATCH_LENGTH;
+
+        // Parse any options that come before the regex. Quoted tokens are never options,
+        // which lets regexes that start with '-' be entered by quoting them.
+        let regex = loop {
+            let Some(token) = remaining.next() else {
+                return Err("Missing regex to search for. Enter 'help find string' for an example.".to_owned());
+            };
+            let text = token.text(input)?;
+            if token.quoted || !text.starts_with('-') {
+                break text;
+            }
+
+            match lookup(&STRING_OPTIONS, text) {
+                Some(StringOption::IgnoreCase) => ignore_case = true,
+                Some(StringOption::Encoding) => {
+                    let Some(raw_encoding) = remaining.next() else {
+                        return Err("Missing encoding after '--encoding'.".to_owned());
+                    };
+                    encodings = match raw_encoding.text(input)? {
+                        ANY_ENCODING => ENCODINGS.iter().map(|(_, encoding)| *encoding).collect(),
+                        raw_encoding => vec![raw_encoding.parse::<Encoding>()?],
+                    };
+                }
+                Some(StringOption::MaxLength) => {
+                    let raw_length = remaining.next().map_or(Ok(""), |token| token.text(input))?;
+                    max_length = raw_length.parse::<usize>().ok().filter(|&length| length > 0).ok_or_else(|| {
+                        format!("Invalid maximum match length: '{raw_length}'. It must be a positive number.")
+                    })?;
+                }
+                None => return Err(format!("Unknown option: '{text}'. Enter 'help find string' for a list of options.")),
+            }
+        };
+
+        // The regex must be the last token; regexes containing whitespace must be quoted.
+        reject_additional_tokens(input, remaining.as_slice(), "help find string")?;
+
+        let regexes = encodings.into_iter()
+            .map(|encoding| encoding.compile(regex, ignore_case).map(|compiled| (encoding, compiled)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(StringPattern { regexes, max_length })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a pattern and returns the bytes matched by each of its elements (or the bounds of each gap).
+    fn parse(pattern: &str) -> Vec<Element> {
+        pattern.parse::<BytePattern>().unwrap().elements
+    }
+
+    fn byte(value: u8) -> Element {
+        Element::Byte(ByteSet::single(value))
+    }
+
+    fn bytes(values: impl IntoIterator<Item = u8>) -> Element {
+        let mut set = ByteSet::default();
+        values.into_iter().for_each(|value| set.insert(value));
+        Element::Byte(set)
+    }
+
+    #[test]
+    fn hex_bytes_can_be_written_in_several_ways() {
+        let expected = vec![byte(0x4D), byte(0x5A), byte(0x90)];
+        assert_eq!(parse("4D 5A 90"), expected);
+        assert_eq!(parse("4d5a90"), expected);
+        assert_eq!(parse("0x4d5a90"), expected);
+        assert_eq!(parse("0X4D 0x5A, 90"), expected);
+    }
+
+    #[test]
+    fn decimal_lists_are_parsed_as_bytes() {
+        assert_eq!(parse("[0, 5, 3]"), vec![byte(0), byte(5), byte(3)]);
+        assert_eq!(parse("[255 ?? 1|2]"), vec![byte(255), bytes(0..=255), bytes([1, 2])]);
+        assert_eq!(parse("FF [10] FF"), vec![byte(0xFF), byte(10), byte(0xFF)]);
+    }
+
+    #[test]
+    fn wildcards_match_the_correct_bytes() {
+        assert_eq!(parse("??"), vec![bytes(0..=255)]);
+        assert_eq!(parse("4?"), vec![bytes(0x40..=0x4F)]);
+        assert_eq!(parse("?d"), vec![bytes((0..16).map(|high| (high << 4) | 0xD))]);
+    }
+
+    #[test]
+    fn alternations_combine_bytes_into_a_set() {
+        assert_eq!(parse("4D|5A"), vec![bytes([0x4D, 0x5A])]);
+        assert_eq!(parse("00 (4D | 5A | 6?) 00"), vec![byte(0), bytes(vec![0x4D, 0x5A].into_iter().chain(0x60..=0x6F)), byte(0)]);
+    }
+
+    #[test]
+    fn gaps_are_parsed_and_merged() {
+        assert_eq!(parse("01 {4} 02"), vec![byte(1), Element::Gap { min: 4, max: 4 }, byte(2)]);
+        assert_eq!(parse("01 { 2 - 8 } 02"), vec![byte(1), Element::Gap { min: 2, max: 8 }, byte(2)]);
+        assert_eq!(parse("01 {1-2}{3} 02"), vec![byte(1), Element::Gap { min: 4, max: 5 }, byte(2)]);
+    }
+
+    #[test]
+    fn quoted_strings_are_matched_exactly() {
+        assert_eq!(parse("\"PK\\x03\\x04\""), vec![byte(b'P'), byte(b'K'), byte(3), byte(4)]);
+        assert_eq!(parse("'MZ' {2} 00 \"\\0\""), vec![byte(b'M'), byte(b'Z'), Element::Gap { min: 2, max: 2 }, byte(0), byte(0)]);
+        assert_eq!(parse("'a b'"), vec![byte(b'a'), byte(b' '), byte(b'b')]);
+    }
+
+    #[test]
+    fn max_length_includes_the_longest_gaps() {
+        assert_eq!("4D 5A".parse::<BytePattern>().unwrap().max_length, 2);
+        assert_eq!("4D {2-8} 5A {3} 00".parse::<BytePattern>().unwrap().max_length, 14);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected_with_their_position() {
+        let test_cases = [
+            ("", "Missing byte pattern"),
+            ("   ", "Missing byte pattern"),
+            ("4D 5", "column 5: hexadecimal bytes must have 2 digits"),
+            ("4D 5G", "column 5: expected a hexadecimal digit, but found 'G'"),
+            ("4D zz", "column 4: unexpected character 'z'"),
+            ("[1, 256]", "column 5: '256' is not a valid byte"),
+            ("[1, 2", "column 6: missing closing ']'"),
+            ("01 {8-2} 02", "column 5: the minimum length of a gap can't be larger"),
+            ("01 {2-} 02", "column 5: gaps must be of the form"),
+            ("01 {100000} 02", "column 5: gaps can't be longer than"),
+            ("(4D 5A)", "column 5: expected '|' or ')', but found '5'"),
+            ("(4D|5A", "column 7: missing closing ')'"),
+            ("0x", "column 1: expected a hexadecimal byte after '0x'"),
+            ("{2} 4D", "can't start with a gap"),
+            ("4D {2}", "can't end with a gap"),
+            ("'MZ' 4D 5", "column 10: hexadecimal bytes must have 2 digits"),
+            ("\"4D", "column 1: missing closing quote"),
+            ("\"\"", "Missing byte pattern"),
+        ];
+        for (pattern, expected_error) in test_cases {
+            let error = pattern.parse::<BytePattern>().unwrap_err();
+            assert!(error.contains(expected_error), "pattern '{}' produced the wrong error: {}", pattern, error);
+        }
+    }
+
+    #[test]
+    fn match_at_returns_the_shortest_match() {
+        let pattern = "4D {1-4} 5A".parse::<BytePattern>().unwrap();
+        let data = [0x4D, 0x00, 0x5A, 0x5A, 0x00];
+        assert_eq!(pattern.match_at(&data, 0), Some(3));
+        assert_eq!(pattern.match_at(&data, 1), None);
+
+        // Gaps which would extend past the end of the data don't match.
+        let data = [0x4D, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5A];
+        assert_eq!(pattern.match_at(&data, 0), None);
+        assert_eq!(pattern.match_at(&data[..6], 0), None);
+    }
+
+    #[test]
+    fn patterns_with_several_wide_gaps_are_matched_quickly() {
+        // Every byte matches the start of each gap, so trying each length of each gap in turn would never finish.
+        let pattern = "00 {0-65536} 00 {0-65536} 00 {0-65536} 00 {0-65536} 01".parse::<BytePattern>().unwrap();
+        let mut data = vec![0; 300_000];
+        assert_eq!(pattern.match_at(&data, 0), None);
+
+        data[200_000] = 0x01;
+        assert_eq!(pattern.match_at(&data, 0), Some(200_001));
+        assert_eq!(pattern.match_at(&data, 199_996), Some(5));
+        assert_eq!(pattern.match_at(&data, 199_997), None);
+    }
+
+    #[test]
+    fn string_patterns_can_be_quoted() {
+        for pattern in ["pass(word)?", "\"pass(word)?\"", "'pass(word)?'"] {
+            let pattern = pattern.parse::<StringPattern>().unwrap();
+            assert_eq!(pattern.regexes[0].1.as_str(), "pass(word)?");
+        }
+
+        // Quotes preserve leading whitespace and dashes.
+        assert_eq!(" ' -a' ".parse::<StringPattern>().unwrap().regexes[0].1.as_str(), " -a");
+
+        // Escape sequences are processed in unquoted and double quoted regexes, but not single quoted ones.
+        let pattern = "\"a b\\x21\\u{e9}\\w\"".parse::<StringPattern>().unwrap();
+        assert_eq!(pattern.regexes[0].1.as_str(), "a b!\u{e9}\\w");
+        let pattern = "'a\\x21'".parse::<StringPattern>().unwrap();
+        assert_eq!(pattern.regexes[0].1.as_str(), "a\\x21");
+    }
+
+    #[test]
+    fn string_pattern_options_are_parsed() {
+        let pattern = "abc".parse::<StringPattern>().unwrap();
+        assert!(!pattern.regexes[0].1.is_match(b"ABC"));
+        assert_eq!(pattern.max_length, DEFAULT_MAX_MATCH_LENGTH);
+
+        let pattern = "-i --max-length 64 \"abc\"".parse::<StringPattern>().unwrap();
+        assert!(pattern.regexes[0].1.is_match(b"xAbCx"));
+        assert_eq!(pattern.max_length, 64);
+
+        let pattern = "--ignore-case abc".parse::<StringPattern>().unwrap();
+        assert!(pattern.regexes[0].1.is_match(b"ABC"));
+
+        let pattern = "--encoding utf16be abc".parse::<StringPattern>().unwrap();
+        assert_eq!(pattern.regexes.len(), 1);
+        assert_eq!(pattern.regexes[0].0, Encoding::Utf16Be);
+        assert!(pattern.regexes[0].1.is_match(b"\x00a\x00b\x00c"));
+
+        let pattern = "--encoding any abc".parse::<StringPattern>().unwrap();
+        let encodings = pattern.regexes.iter().map(|(encoding, _)| *encoding).collect::<Vec<_>>();
+        assert_eq!(encodings, vec![Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Latin1]);
+    }
+
+    #[test]
+    fn invalid_string_patterns_are_rejected() {
+        let test_cases = [
+            ("", "Missing regex"),
+            ("-i", "Missing regex"),
+            ("--max-length 0 abc", "Invalid maximum match length: '0'"),
+            ("--max-length abc", "Invalid maximum match length: 'abc'"),
+            ("--unknown abc", "Unknown option: '--unknown'"),
+            ("\"abc", "missing closing quote"),
+            ("'", "missing closing quote"),
+            ("abc def", "Unexpected extra parameter at column 5: 'def'"),
+            ("\\xFF", "isn't valid UTF-8"),
+            ("(abc", "Invalid regex"),
+            ("--encoding", "Missing encoding"),
+            ("--encoding utf32 abc", "Unknown encoding: 'utf32'"),
+        ];
+        for (pattern, expected_error) in test_cases {
+            let error = pattern.parse::<StringPattern>().unwrap_err();
+            assert!(error.contains(expected_error), "pattern '{}' produced the wrong error: {}", pattern, error);
+        }
+    }
+
+    #[test]
+    fn string_patterns_match_raw_bytes() {
+        let pattern = "-i \"user(name)?=\\w+\"".parse::<StringPattern>().unwrap();
+        let mut chunk = DynAlignedBuffer::new(64, 16);
+        let data = b"\xFF\x00USER=root\x00\xFEusername=admin\x00";
+        chunk[..data.len()].copy_from_slice(data);
+
+        let region = Region { chunk_offset: 512, window_start: 0, start: 0, end: 16, window_end: 64 };
+        assert_eq!(pattern.scan(&chunk, &region), vec![
+            Match { offset: 514, length: 9, encoding: Some(Encoding::Utf8) },
+            Match { offset: 525, length: 14, encoding: Some(Encoding::Utf8) },
+        ]);
+
+        // The second match starts in the next region, so it shouldn't be reported by this one.
+        let region = Region { chunk_offset: 512, window_start: 0, start: 0, end: 12, window_end: 64 };
+        assert_eq!(pattern.scan(&chunk, &region), vec![Match { offset: 514, length: 9, encoding: Some(Encoding::Utf8) }]);
+    }
+
+    #[test]
+    fn scan_only_reports_matches_that_start_in_the_region() {
+        let pattern = "AB ?? CD".parse::<BytePattern>().unwrap();
+        let mut chunk = DynAlignedBuffer::new(64, 16);
+        chunk[10..13].copy_from_slice(&[0xAB, 0x00, 0xCD]);
+        chunk[30..33].copy_from_slice(&[0xAB, 0xFF, 0xCD]);
+        chunk[31..34].copy_from_slice(&[0xAB, 0x11, 0xCD]);
+
+        let region = Region { chunk_offset: 1000, window_start: 0, start: 0, end: 31, window_end: 33 };
+        assert_eq!(pattern.scan(&chunk, &region), vec![
+            Match { offset: 1010, length: 3, encoding: None },
+        ]);
+
+        let region = Region { chunk_offset: 1000, window_start: 0, start: 0, end: 32, window_end: 34 };
+        assert_eq!(pattern.scan(&chunk, &region), vec![
+            Match { offset: 1010, length: 3, encoding: None },
+            Match { offset: 1031, length: 3, encoding: None },
+        ]);
+    }
+
+    #[test]
+    fn scanning_a_full_chunk_with_several_wide_gaps_is_quick() {
+        // Every zero byte could start a match, and each of them could be up to 262,149 bytes long, so matching at each
+        // position separately would never finish.
+        let pattern = "00 {0-65536} 00 {0-65536} 00 {0-65536} 00 {0-65536} 01".parse::<BytePattern>().unwrap();
+        let mut chunk = DynAlignedBuffer::new(1 << 20, 16);
+        chunk[900_000] = 0x01;
+        chunk[1_000_000] = 0x01;
+
+        let region = Region { chunk_offset: 0, window_start: 0, start: 0, end: 1 << 20, window_end: 1 << 20 };
+        let matches = pattern.scan(&chunk, &region);
+        assert_eq!(matches.len(), 262_145 + 3 + 99_996);
+        assert_eq!(matches[0], Match { offset: 637_852, length: 262_149, encoding: None });
+        assert_eq!(matches[262_144], Match { offset: 899_996, length: 5, encoding: None });
+
+        // Starts which are too close to the first 01 to fit the rest of the zeroes skip past it to the second one.
+        assert_eq!(matches[262_145], Match { offset: 899_997, length: 100_004, encoding: None });
+        assert_eq!(matches[262_148], Match { offset: 900_001, length: 100_000, encoding: None });
+        assert_eq!(matches.last(), Some(&Match { offset: 999_996, length: 5, encoding: None }));
+    }
+
+    /// Returns the end of the shortest match of `elements` in `data` at `position`, by trying every length of every gap.
+    fn shortest_match_by_brute_force(elements: &[Element], data: &[u8], position: usize) -> Option<usize> {
+        let Some((element, rest)) = elements.split_first() else {
+            return Some(position);
+        };
+        match element {
+            Element::Byte(set) => data.get(position).filter(|&&byte| set.contains(byte))
+                .and_then(|_| shortest_match_by_brute_force(rest, data, position + 1)),
+            Element::Gap { min, max } => (position + min..=position + max)
+                .filter_map(|end| shortest_match_by_brute_force(rest, data, end))
+                .min(),
+        }
+    }
+
+    #[test]
+    fn scan_finds_the_same_matches_as_a_brute_force_search() {
+        // Only a few byte values are used, so that the pattern matches often, and in several different ways.
+        let mut chunk = DynAlignedBuffer::new(4096, 16);
+        let mut state = 12345_u32;
+        for byte in chunk.iter_mut() {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            *byte = [0x0D, 0x1D, 0xAB, 0xCD][(state >> 16) as usize % 4];
+        }
+
+        for pattern in ["AB {2-3} ?D {0-4} CD", "(AB|CD) {1} 1D {0-2} {1-2} AB", "0D 0D"] {
+            let pattern = pattern.parse::<BytePattern>().unwrap();
+            let region = Region { chunk_offset: 0, window_start: 0, start: 0, end: 4000, window_end: 4096 };
+            let expected = (0..4000).filter_map(|position| {
+                let end = shortest_match_by_brute_force(&pattern.elements, &chunk, position)?;
+                Some(Match { offset: position as u64, length: end - position, encoding: None })
+            }).collect::<Vec<_>>();
+            assert!(!expected.is_empty());
+            assert_eq!(pattern.scan(&chunk, &region), expected);
+        }
+    }
+
+    #[test]
+    fn string_patterns_report_the_encoding_of_each_match() {
+        let pattern = "--encoding any -i \"caf\u{E9}\"".parse::<StringPattern>().unwrap();
+        let mut chunk = DynAlignedBuffer::new(64, 16);
+        let data = b"CAF\xC3\x89.caf\xE9..\x00c\x00a\x00f\x00\xE9c\x00a\x00f\x00\xE9\x00.";
+        chunk[..data.len()].copy_from_slice(data);
+
+        let region = Region { chunk_offset: 0, window_start: 0, start: 0, end: 64, window_end: 64 };
+        assert_eq!(pattern.scan(&chunk, &region), vec![
+            Match { offset: 0, length: 5, encoding: Some(Encoding::Utf8) },
+            Match { offset: 6, length: 4, encoding: Some(Encoding::Latin1) },
+            Match { offset: 12, length: 8, encoding: Some(Encoding::Utf16Be) },
+            Match { offset: 20, length: 8, encoding: Some(Encoding::Utf16Le) },
+        ]);
+    }
+
+    #[test]
+    fn utf16_matches_must_be_aligned() {
+        let pattern = "--encoding utf16le \"[^a-z]+\"".parse::<StringPattern>().unwrap();
+        let mut chunk = DynAlignedBuffer::new(16, 16);
+        chunk[..8].copy_from_slice(b"a\x00\x3D\xD8\x00\xDEb\x00");
+
+        // Starting at offset 1 would match the bytes "00 3D" as a single character.
+        let region = Region { chunk_offset: 0, window_start: 0, start: 0, end: 8, window_end: 8 };
+        assert_eq!(pattern.scan(&chunk, &region), vec![
+            Match { offset: 2, length: 4, encoding: Some(Encoding::Utf16Le) },
+        ]);
+    }
+
+    #[test]
+    fn ascii_matches_are_only_reported_once_in_any_encoding() {
+        let pattern = "--encoding any root".parse::<StringPattern>().unwrap();
+        let mut chunk = DynAlignedBuffer::new(16, 16);
+        chunk[..6].copy_from_slice(b"-root-");
+
+        let region = Region { chunk_offset: 0, window_start: 0, start: 0, end: 16, window_end: 16 };
+        assert_eq!(pattern.scan(&chunk, &region), vec![
+            Match { offset: 1, length: 4, encoding: Some(Encoding::Utf8) },
+        ]);
+    }
+}
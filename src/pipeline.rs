@@ -0,0 +1,512 @@
+use crate::config::SearchDirection;
+use crate::data::dyn_aligned_buffer::{DynAlignedBuffer, PAGE_SIZE};
+use crate::device::ReadAt;
+use crate::interrupt;
+use crate::reader::DeviceReader;
+use std::io;
+use std::ops::ControlFlow;
+use std::sync::mpsc;
+use std::sync::Arc;
+
+/// Describes the portion of a chunk that a single worker is responsible for scanning.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    /// The absolute byte offset (in the device) of the first byte in the chunk.
+    pub chunk_offset: u64,
+    /// The index (in the chunk) of the first byte the scanner is allowed to look at. This comes before `start` by the
+    /// scanner's look-behind (if the data is available), so scanners can tell whether a match that would start inside
+    /// this region is actually part of a match that starts before it.
+    pub window_start: usize,
+    /// The index (in the chunk) of the first byte this region is responsible for.
+    pub start: usize,
+    /// The index (in the chunk) one past the last byte this region is responsible for.
+    /// Only matches which start before this index should be reported.
+    pub end: usize,
+    /// The index (in the chunk) one past the last byte the scanner is allowed to look at.
+    /// This extends past `end` by the scanner's overlap (if the data is available), so matches which start
+    /// inside this region but end outside of it can still be found.
+    pub window_end: usize,
+}
+
+/// Something that can search through the data read by a `ScanPipeline`.
+///
+/// Scanners are shared between all the worker threads, so they must be `Sync`, and can't hold any mutable
+/// state. Any results should instead be returned as the `Output` of `scan`.
+pub trait Scanner: Sync {
+    /// The result of scanning a single region.
+    type Output: Send;
+
+    /// Returns the number of bytes that must be visible past the end of a region, in order to find every
+    /// match that starts inside it. This should be the length of the longest possible match, minus 1.
+    fn overlap(&self) -> usize {
+        0
+    }
+
+    /// Returns the number of bytes that must be visible before the start of a region, in order to tell where the
+    /// matches that end inside it begin. Scanners whose matches can start at any position don't need any.
+    fn look_behind(&self) -> usize {
+        0
+    }
+
+    /// Scans the specified region of `chunk`. Only bytes in `region.window_start..region.window_end` may be
+    /// looked at, and only matches which start in `region.start..region.end` should be reported.
+    fn scan(&self, chunk: &DynAlignedBuffer, region: &Region) -> Self::Output;
+}
+
+/// A unit of work sent from the pipeline to a worker thread.
+struct Job {
+    chunk: Arc<DynAlignedBuffer>,
+    region: Region,
+}
+
+/// Reads data from a device and scans it on a pool of worker threads, using 2 buffers that are swapped back
+/// and forth so that reading and scanning can happen at the same time.
+///
+/// While the workers are scanning the `worker_buffer`, the pipeline reads the next chunk of data into the
+/// `staging_buffer`. Once every worker has checked in, the buffers are swapped, and the workers are sent the
+/// newly read chunk while the pipeline starts reading the next one into the old buffer:
+///
+/// ```text
+/// read chunk 0 into the staging buffer              staging = chunk 0    worker = empty
+/// swap the buffers                                  staging = empty      worker = chunk 0
+/// signal the workers that chunk 0 is ready          staging = empty      worker = chunk 0
+/// read chunk 1 into the staging buffer              staging = chunk 1    worker = chunk 0
+/// wait for every worker to check in                 staging = chunk 1    worker = chunk 0
+/// swap the buffers                                  staging = chunk 0    worker = chunk 1
+/// signal the workers that chunk 1 is ready          ...
+/// ```
+///
+/// Consecutive chunks overlap by the scanner's `overlap` (rounded up to a whole number of sectors), so that
+/// matches which straddle the boundary between 2 chunks are still found. Each worker is also given access to
+/// the data past the end of its region for the same reason. Similarly, every chunk after the first starts the
+/// scanner's `look_behind` (also rounded up to a whole number of sectors) before the data it owns, and each worker
+/// is given access to the data before its region.
+///
+/// Regions are always aligned to the reader's sector size, which must evenly divide the chunk size.
+/// Both buffers are aligned to `PAGE_SIZE`, so that direct reads can go straight into them.
+pub struct ScanPipeline {
+    staging_buffer: Arc<DynAlignedBuffer>,
+    worker_buffer: Arc<DynAlignedBuffer>,
+    chunk_size: usize,
+    worker_count: usize,
+}
+
+impl ScanPipeline {
+    /// Creates a new pipeline that scans data in chunks of `chunk_size` bytes, using `worker_count` threads.
+    ///
+    /// # Panics
+    ///
+    /// If `worker_count` is 0.
+    pub fn new(chunk_size: usize, worker_count: usize) -> Self {
+        assert!(worker_count > 0, "pipeline must have at least 1 worker");
+
+        ScanPipeline {
+            staging_buffer: Arc::new(DynAlignedBuffer::new(chunk_size, PAGE_SIZE)),
+            worker_buffer: Arc::new(DynAlignedBuffer::new(chunk_size, PAGE_SIZE)),
+            chunk_size,
+            worker_count,
+        }
+    }
+
+    /// Scans the device from the start of `start_sector` to the byte offset `end` (or the end of the device if
+    /// that comes first) with `scanner`. The outputs of the scanner are passed to `sink` in the same order the
+    /// data appears in the device. If `sink` returns `ControlFlow::Break`, scanning is stopped early.
+    ///
+    /// The reader's interrupt flag is checked after each chunk. If it's set, scanning is stopped early, and an error
+    /// with the `Interrupted` kind (describing how much of the device was scanned) is returned.
+    pub fn run<D: ReadAt, S: Scanner>(
+        &mut self,
+        reader: &mut DeviceReader<D>,
+        start_sector: u64,
+        end: u64,
+        scanner: &S,
+        sink: impl FnMut(S::Output) -> ControlFlow<()>,
+    ) -> io::Result<()> {
+        self.run_in_direction(reader, start_sector, end, SearchDirection::Forward, scanner, sink)
+    }
+
+    /// Scans the device the same way as `run`, except that when scanning backward, the chunks (and the regions in
+    /// each chunk) are scanned from the end of the device to the start, and their outputs are passed to `sink` in
+    /// that order. Each individual output is left as-is.
+    pub fn run_in_direction<D: ReadAt, S: Scanner>(
+        &mut self,
+        reader: &mut DeviceReader<D>,
+        start_sector: u64,
+        end: u64,
+        direction: SearchDirection,
+        scanner: &S,
+        mut sink: impl FnMut(S::Output) -> ControlFlow<()>,
+    ) -> io::Result<()> {
+        let sector_size = reader.settings().sector_size as usize;
+        let chunk_size = self.chunk_size;
+        if !chunk_size.is_multiple_of(sector_size) {
+            let message = format!("the chunk size ({chunk_size} bytes) must be a multiple of the sector size ({sector_size} bytes)");
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        // Each chunk owns the `stride` bytes after the data owned by the previous one, leaving enough overlap between
+        // them that every match which starts in one chunk can be seen in its entirety by that chunk, and enough data
+        // before the start of each chunk's data for the scanner to look behind it.
+        let overlap = ceil_divide!(scanner.overlap(), sector_size) * sector_size;
+        let look_behind = ceil_divide!(scanner.look_behind(), sector_size) * sector_size;
+        if overlap + look_behind >= chunk_size {
+            let limit = chunk_size / if look_behind > 0 { 2 } else { 1 };
+            let message = format!("the search pattern is too long (it must be shorter than {limit} bytes)");
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        let stride = chunk_size - overlap - look_behind;
+        let start = start_sector * (sector_size as u64);
+        let end = end.min(reader.length());
+
+        // Work out where the data owned by each chunk starts. Every chunk is `chunk_size` bytes long, but only owns
+        // `stride` bytes of its data, except the last chunk, which owns everything up to `end`. Every chunk except the
+        // first is read from `look_behind` bytes before the data it owns.
+        let chunk_count = if start >= end {
+            0
+        } else {
+            1 + ceil_divide!((end - start).saturating_sub((chunk_size - look_behind) as u64), stride as u64)
+        };
+        let last_chunk_offset = start + chunk_count.saturating_sub(1) * (stride as u64);
+        let chunk_offsets = (0..chunk_count).map(move |index| {
+            let owned_offset = start + index * (stride as u64);
+            let chunk_offset = if index == 0 { start } else { owned_offset - look_behind as u64 };
+            (owned_offset, chunk_offset)
+        });
+        let mut chunk_offsets: Box<dyn Iterator<Item = (u64, u64)>> = match direction {
+            SearchDirection::Forward => Box::new(chunk_offsets),
+            SearchDirection::Backward => Box::new(chunk_offsets.rev()),
+        };
+
+        std::thread::scope(|scope| {
+            // Spawn the worker threads. Each worker has its own channel for receiving jobs, and they all share a
+            // single channel for checking in with their results once they've finished with their job.
+            let (result_sender, result_receiver) = mpsc::channel::<(usize, S::Output)>();
+            let job_senders = (0..self.worker_count).map(|index| {
+                let (job_sender, job_receiver) = mpsc::channel::<Job>();
+                let result_sender = result_sender.clone();
+                scope.spawn(move || {
+                    for job in job_receiver {
+                        let output = scanner.scan(&job.chunk, &job.region);
+                        // Release our handle to the chunk before checking in, so that once every worker has
+                        // checked in, the pipeline is guaranteed to have exclusive access to the buffer again.
+                        drop(job);
+                        if result_sender.send((index, output)).is_err() {
+                            break;
+                        }
+                    }
+                });
+                job_sender
+            }).collect::<Vec<_>>();
+
+            // Read the first chunk of data before starting the main loop.
+            let mut next_chunk = chunk_offsets.next().map(|(owned_offset, chunk_offset)| {
+                (owned_offset, chunk_offset, self.read_into_staging(reader, chunk_offset, end))
+            });
+            let mut outputs = Vec::with_capacity(self.worker_count);
+            let mut scanned = 0;
+
+            while let Some((owned_offset, chunk_offset, filled)) = next_chunk.take() {
+                let filled = filled?;
+                let owned_start = (owned_offset - chunk_offset) as usize;
+                if filled <= owned_start {
+                    break;
+                }
+                std::mem::swap(&mut self.staging_buffer, &mut self.worker_buffer);
+
+                // The last chunk owns all of its data, every other chunk leaves its overlap for the next chunk.
+                let available = filled - owned_start;
+                let owned = if owned_offset == last_chunk_offset { available } else { stride.min(available) };
+
+                // Split the chunk's owned data into sector-aligned regions, and send 1 region to each worker.
+                let region_length = ceil_divide!(ceil_divide!(owned, self.worker_count), sector_size) * sector_size;
+                let mut completed_workers = 0;
+                let mut dispatched_workers = 0;
+                for (index, job_sender) in job_senders.iter().enumerate() {
+                    let start = owned_start + index * region_length;
+                    if start >= owned_start + owned {
+                        break;
+                    }
+                    let region_end = (start + region_length).min(owned_start + owned);
+                    let region = Region {
+                        chunk_offset,
+                        window_start: start.saturating_sub(scanner.look_behind()),
+                        start,
+                        end: region_end,
+                        window_end: (region_end + scanner.overlap()).min(filled),
+                    };
+                    let chunk = Arc::clone(&self.worker_buffer);
+                    job_sender.send(Job { chunk, region }).expect("scan worker exited unexpectedly");
+                    dispatched_workers += 1;
+                }
+
+                // Read the next chunk while the workers are busy scanning this one.
+                next_chunk = chunk_offsets.next().map(|(owned_offset, chunk_offset)| {
+                    (owned_offset, chunk_offset, self.read_into_staging(reader, chunk_offset, end))
+                });
+
+                // Wait for every worker to check in, then pass their outputs to the sink in order.
+                outputs.clear();
+                outputs.resize_with(dispatched_workers, || None);
+                while completed_workers < dispatched_workers {
+                    let (index, output) = result_receiver.recv().expect("scan worker exited unexpectedly");
+                    outputs[index] = Some(output);
+                    completed_workers += 1;
+                }
+                if direction == SearchDirection::Backward {
+                    outputs.reverse();
+                }
+                for output in outputs.drain(..) {
+                    if sink(output.expect("scan worker didn't report a result")).is_break() {
+                        return Ok(());
+                    }
+                }
+
+                // Only stop once the outputs of the whole chunk have been passed on, so nothing that was scanned is lost.
+                scanned += owned as u64;
+                if reader.is_interrupted() {
+                    return Err(interrupt::interrupted_error("scanning", scanned, end.saturating_sub(start)));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads the chunk that starts at `offset` into the staging buffer, ignoring any data at or past `end`.
+    /// Returns the number of bytes that were read.
+    fn read_into_staging<D: ReadAt>(&mut self, reader: &mut DeviceReader<D>, offset: u64, end: u64) -> io::Result<usize> {
+        if offset >= end {
+            return Ok(0);
+        }
+
+        // Every worker has already checked in (and released its handle) before we read into the staging buffer.
+        let buffer = Arc::get_mut(&mut self.staging_buffer).expect("staging buffer is still in use by a worker");
+        let filled = reader.read_chunk(offset / reader.settings().sector_size, buffer)?;
+        Ok(filled.min((end - offset) as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::StringPattern;
+    use crate::test_util::create_reader;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// A scanner which reports the absolute offset of every occurrence of a byte string.
+    struct NeedleScanner(&'static [u8]);
+
+    impl Scanner for NeedleScanner {
+        type Output = Vec<u64>;
+
+        fn overlap(&self) -> usize {
+            self.0.len() - 1
+        }
+
+        fn scan(&self, chunk: &DynAlignedBuffer, region: &Region) -> Vec<u64> {
+            let window = &chunk[region.start..region.window_end];
+            window.windows(self.0.len())
+                .enumerate()
+                .filter(|(index, candidate)| region.start + index < region.end && *candidate == self.0)
+                .map(|(index, _)| region.chunk_offset + (region.start + index) as u64)
+                .collect()
+        }
+    }
+
+    /// A scanner which reports the regions it was given.
+    struct RegionScanner;
+
+    impl Scanner for RegionScanner {
+        type Output = Region;
+
+        fn scan(&self, _chunk: &DynAlignedBuffer, region: &Region) -> Region {
+            region.clone()
+        }
+    }
+
+    /// Runs `scanner` over the entire device and collects all of its outputs.
+    fn run_to_completion<S: Scanner>(
+        pipeline: &mut ScanPipeline,
+        reader: &mut DeviceReader,
+        scanner: &S,
+    ) -> Vec<S::Output> {
+        let mut outputs = Vec::new();
+        pipeline.run(reader, 0, u64::MAX, scanner, |output| {
+            outputs.push(output);
+            ControlFlow::Continue(())
+        }).unwrap();
+        outputs
+    }
+
+    #[test]
+    fn regions_cover_the_device_exactly_once() {
+        let (_image, mut reader) = create_reader(&vec![0; 10_000]);
+        let mut pipeline = ScanPipeline::new(2048, 3);
+
+        let regions = run_to_completion(&mut pipeline, &mut reader, &RegionScanner);
+
+        // Every region should start where the previous one ended, and the last one should end with the device.
+        let mut expected_start = 0;
+        for region in &regions {
+            assert_eq!(region.chunk_offset + region.start as u64, expected_start);
+            assert_eq!(region.start % (reader.settings().sector_size as usize), 0);
+            assert!(region.end > region.start);
+            expected_start = region.chunk_offset + region.end as u64;
+        }
+        assert_eq!(expected_start, 10_000);
+    }
+
+    #[test]
+    fn matches_are_found_across_chunk_and_region_boundaries() {
+        let mut data = vec![0; 20_000];
+        let needle = b"needle";
+        // Place needles at the start, straddling every chunk and region boundary, and at the very end.
+        let positions = [0, 509, 1020, 2045, 4093, 6141, 8190, 12_285, 20_000 - needle.len()];
+        for &position in &positions {
+            data[position..position + needle.len()].copy_from_slice(needle);
+        }
+        let (_image, mut reader) = create_reader(&data);
+
+        for worker_count in 1..=4 {
+            let mut pipeline = ScanPipeline::new(4096, worker_count);
+            let outputs = run_to_completion(&mut pipeline, &mut reader, &NeedleScanner(needle));
+            let matches = outputs.into_iter().flatten().collect::<Vec<_>>();
+            assert_eq!(matches, positions.iter().map(|&p| p as u64).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn variable_length_matches_are_only_found_once_whatever_the_chunk_size() {
+        // Words of every length up to 40 bytes, so that some of them straddle every chunk and region boundary.
+        let mut data = Vec::new();
+        for length in (1..=40).cycle().take(1500) {
+            data.extend(std::iter::repeat_n(b'a' + (length % 26) as u8, length));
+            data.extend(b" ");
+        }
+        let (_image, mut reader) = create_reader(&data);
+        let pattern = "--max-length 64 \\w+".parse::<StringPattern>().unwrap();
+        let expected = regex::bytes::Regex::new(r"\w+").unwrap()
+            .find_iter(&data)
+            .map(|found| (found.start() as u64, found.len()))
+            .collect::<Vec<_>>();
+
+        for chunk_size in [2048, 4096, 8192] {
+            for worker_count in 1..=4 {
+                let mut pipeline = ScanPipeline::new(chunk_size, worker_count);
+                let outputs = run_to_completion(&mut pipeline, &mut reader, &pattern);
+                let matches = outputs.into_iter().flatten().map(|found| (found.offset, found.length)).collect::<Vec<_>>();
+                assert_eq!(matches, expected, "chunk size {chunk_size} with {worker_count} worker(s)");
+
+                let mut matches = Vec::new();
+                pipeline.run_in_direction(&mut reader, 0, u64::MAX, SearchDirection::Backward, &pattern, |output| {
+                    matches.extend(output.into_iter().rev().map(|found| (found.offset, found.length)));
+                    ControlFlow::Continue(())
+                }).unwrap();
+                matches.reverse();
+                assert_eq!(matches, expected, "chunk size {chunk_size} with {worker_count} worker(s), backward");
+            }
+        }
+    }
+
+    #[test]
+    fn scanning_backward_reverses_the_order_of_the_outputs() {
+        let mut data = vec![0; 20_000];
+        let positions = [0, 1020, 2045, 4093, 8190, 12_285, 19_999];
+        for &position in &positions {
+            data[position] = b'x';
+        }
+        let (_image, mut reader) = create_reader(&data);
+
+        for worker_count in 1..=3 {
+            let mut pipeline = ScanPipeline::new(2048, worker_count);
+            let mut matches = Vec::new();
+            pipeline.run_in_direction(&mut reader, 0, u64::MAX, SearchDirection::Backward, &NeedleScanner(b"x"), |output| {
+                matches.extend(output.into_iter().rev());
+                ControlFlow::Continue(())
+            }).unwrap();
+            assert_eq!(matches, positions.iter().rev().map(|&p| p as u64).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn regions_are_aligned_to_the_sector_size() {
+        let (_image, mut reader) = create_reader(&vec![0; 50_000]);
+        reader.settings_mut().sector_size = 4096;
+        let mut pipeline = ScanPipeline::new(16384, 3);
+
+        let regions = run_to_completion(&mut pipeline, &mut reader, &RegionScanner);
+        let mut expected_start = 0;
+        for region in &regions {
+            assert_eq!(region.chunk_offset + region.start as u64, expected_start);
+            assert_eq!(region.start % 4096, 0);
+            expected_start = region.chunk_offset + region.end as u64;
+        }
+        assert_eq!(expected_start, 50_000);
+
+        // Chunks which aren't a whole number of sectors can't be used.
+        let mut pipeline = ScanPipeline::new(2048, 1);
+        assert!(pipeline.run(&mut reader, 0, u64::MAX, &RegionScanner, |_| ControlFlow::Continue(())).is_err());
+    }
+
+    #[test]
+    fn scanning_starts_at_the_requested_sector_and_stops_at_the_end() {
+        let mut data = vec![0; 8192];
+        for position in [100, 600, 3000, 5000] {
+            data[position] = b'x';
+        }
+        let (_image, mut reader) = create_reader(&data);
+        let mut pipeline = ScanPipeline::new(1024, 2);
+
+        let mut matches = Vec::new();
+        pipeline.run(&mut reader, 1, 4096, &NeedleScanner(b"x"), |output| {
+            matches.extend(output);
+            ControlFlow::Continue(())
+        }).unwrap();
+        assert_eq!(matches, vec![600, 3000]);
+    }
+
+    #[test]
+    fn breaking_from_the_sink_stops_the_scan() {
+        let (_image, mut reader) = create_reader(&vec![b'x'; 64 * 1024]);
+        let mut pipeline = ScanPipeline::new(4096, 2);
+
+        let mut calls = 0;
+        pipeline.run(&mut reader, 0, u64::MAX, &NeedleScanner(b"x"), |_| {
+            calls += 1;
+            ControlFlow::Break(())
+        }).unwrap();
+        assert_eq!(calls, 1);
+
+        // The pipeline should still be usable after stopping early.
+        let outputs = run_to_completion(&mut pipeline, &mut reader, &NeedleScanner(b"x"));
+        assert_eq!(outputs.into_iter().flatten().count(), 64 * 1024);
+    }
+
+    #[test]
+    fn interrupting_the_reader_stops_the_scan_after_the_current_chunk() {
+        let (_image, mut reader) = create_reader(&vec![b'x'; 16 * 1024]);
+        let flag = Arc::new(AtomicBool::new(false));
+        reader.set_interrupt_flag(Arc::clone(&flag));
+        let mut pipeline = ScanPipeline::new(4096, 2);
+
+        let mut found = 0;
+        let err = pipeline.run(&mut reader, 0, u64::MAX, &NeedleScanner(b"x"), |output| {
+            found += output.len();
+            flag.store(true, Ordering::SeqCst);
+            ControlFlow::Continue(())
+        }).unwrap_err();
+
+        // Every output of the first chunk is still passed to the sink.
+        assert_eq!(found, 4096);
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(err.to_string(), "Interrupted by Ctrl-C after scanning 4096 of 16384 bytes (25.0%).");
+    }
+
+    #[test]
+    fn patterns_longer_than_a_chunk_are_rejected() {
+        let (_image, mut reader) = create_reader(&[0; 4096]);
+        let mut pipeline = ScanPipeline::new(1024, 1);
+
+        let needle = NeedleScanner(&[0; 1500]);
+        assert!(pipeline.run(&mut reader, 0, u64::MAX, &needle, |_| ControlFlow::Continue(())).is_err());
+    }
+}
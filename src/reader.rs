@@ -0,0 +1,1261 @@
+use crate::command::{Config, Find, Print, Seek};
+use crate::command_line::hexdump::HexDump;
+use crate::config::{DisplayBase, ReadMode, SearchDirection, Settings};
+use crate::data::bad_sectors::BadSectorLog;
+use crate::data::dyn_aligned_buffer::{DynAlignedBuffer, PAGE_SIZE};
+use crate::data::sector_map::{SectorMap, SectorMapBuilder};
+use crate::device::{self, DirectIo, FileId, ReadAt, ReadOnlyDevice};
+use crate::disk_info::format_byte_count;
+use crate::interrupt;
+use crate::pattern::Match;
+use crate::pipeline::{Region, ScanPipeline, Scanner};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::io::SeekFrom;
+use std::ops::{ControlFlow, Range};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Owns a read-only handle to the file/device being inspected, and tracks the current cursor position in it.
+///
+/// All reads performed by the reader start on a sector boundary and are performed in chunks of the configured
+/// `chunk_size` (except the final chunk, which only covers the sectors that are needed, and may be
+/// shorter still if the device ends mid-chunk).
+/// Data from these chunks is then copied out as necessary to satisfy unaligned requests.
+///
+/// Long running operations regularly check the reader's interrupt flag, and stop early once it's set.
+///
+/// Damaged devices fail any read which touches a bad sector, so chunks which can't be read are re-read one sector at
+/// a time. Sectors which still can't be read are treated as zeroes (so operations can carry on past them), and are
+/// recorded in the reader's bad-sector log. Operations report how many sectors they couldn't read once they finish.
+/// A map of bad sectors saved by an earlier session can also be loaded, so those sectors are skipped without being read.
+pub struct DeviceReader<D = ReadOnlyDevice> {
+    file: D,
+    length: u64,
+    position: u64,
+    settings: Settings,
+    interrupted: Arc<AtomicBool>,
+    /// Every sector which couldn't be read since the device was opened.
+    bad_sectors: BadSectorLog,
+    /// The sectors which couldn't be read since the current operation started.
+    recent_bad_sectors: BadSectorLog,
+    /// A map of sectors which are already known to be unreadable, so they're treated as zeroes without being read.
+    known_bad_sectors: Option<SectorMap>,
+    /// How the results of searches are written.
+    output_format: OutputFormat,
+}
+
+/// How the results of searches are written to the output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// Results are listed in columns, alongside a summary of them and where the cursor was moved to.
+    Text,
+    /// Each result is written on its own line, as tab separated fields (described in `USAGE`), without anything else.
+    /// Messages about sectors which couldn't be read are written to stderr instead.
+    Lines,
+}
+
+impl<D: ReadAt> DeviceReader<D> {
+    /// Creates a new reader for the provided file/device, with its cursor at the start of the device.
+    ///
+    /// The length of the device is determined by seeking to its end, since the file metadata of
+    /// block devices always reports a length of 0.
+    pub fn new(mut file: D) -> io::Result<Self> where D: io::Seek {
+        let length = file.seek(SeekFrom::End(0))?;
+        Ok(DeviceReader {
+            file,
+            length,
+            position: 0,
+            settings: Settings::default(),
+            interrupted: Arc::default(),
+            bad_sectors: BadSectorLog::default(),
+            recent_bad_sectors: BadSectorLog::default(),
+            known_bad_sectors: None,
+            output_format: OutputFormat::Text,
+        })
+    }
+
+    /// Returns the file/device being read from.
+    pub fn device(&self) -> &D {
+        &self.file
+    }
+
+    /// Returns the total number of bytes in the device.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Executes a `config` command against the reader's settings, printing any output to `out`.
+    pub fn configure(&mut self, config: &Config, out: &mut impl Write) -> Result<(), String> {
+        self.settings.configure(config, self.file.file_id(), out)
+    }
+
+    /// Returns the settings which control how the device is read, scanned, and displayed.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Returns a mutable reference to the reader's settings, so they can be changed.
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    /// Replaces the flag which is checked to see whether the current operation should be stopped early.
+    /// This is normally the flag set by the Ctrl-C handler.
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupted = flag;
+    }
+
+    /// Changes how the results of searches are written, which is `OutputFormat::Text` by default.
+    pub fn set_output_format(&mut self, format: OutputFormat) {
+        self.output_format = format;
+    }
+
+    /// Checks whether the current operation has been asked to stop early.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    /// Clears the interrupt flag, so that the next operation can run to completion.
+    pub fn clear_interrupt(&self) {
+        self.interrupted.store(false, Ordering::SeqCst);
+    }
+
+    /// Moves the cursor to the position described by `seek`, and returns the new position.
+    ///
+    /// Returns an error if the new position would be before the start, or past the end of the device.
+    /// In that case, the cursor isn't moved.
+    pub fn seek(&mut self, seek: &Seek) -> Result<u64, String> {
+        // Compute the new position of the cursor, checking that it doesn't go before the start of the device.
+        let new_position = match *seek {
+            Seek::Absolute(position) => u64::try_from(position).ok(),
+            Seek::Relative(offset) => {
+                if offset < 0 {
+                    self.position.checked_sub(offset.unsigned_abs())
+                } else {
+                    self.position.checked_add(offset as u64)
+                }
+            }
+        }.ok_or_else(|| "Cannot seek before the start of the device.".to_owned())?;
+
+        // Check that the new position doesn't go past the end of the device.
+        if new_position > self.length {
+            return Err(format!(
+                "Cannot seek to {new_position}, it is past the end of the device ({} bytes long).",
+                self.length,
+            ));
+        }
+
+        self.position = new_position;
+        Ok(new_position)
+    }
+
+    /// Turns direct I/O on or off to match the `read_mode` setting, and returns whether reads now bypass the
+    /// page cache. This happens automatically before every read, so it only needs to be called to find out
+    /// whether the device supports direct I/O.
+    pub fn apply_read_mode(&mut self) -> DirectIo {
+        self.file.set_direct_io(self.settings.read_mode == ReadMode::Direct);
+        self.file.direct_io()
+    }
+
+    /// Reads the chunk of data that starts at the specified sector into `buffer`.
+    ///
+    /// Returns the number of bytes that were read into the buffer. This is always the full length of the
+    /// buffer, unless the end of the device was reached, in which case the remainder of the buffer is zeroed.
+    ///
+    /// The device is read in the configured `read_mode`. Direct reads are fastest when `buffer` is aligned to
+    /// `PAGE_SIZE`, since otherwise they have to be copied through a bounce buffer.
+    ///
+    /// If the chunk can't be read because the device's media is damaged, it's re-read one sector at a time, and any
+    /// sectors which still can't be read are zeroed and recorded as bad sectors. Any other error (or one with the
+    /// `Interrupted` kind, if the re-reading is interrupted) is returned.
+    /// Chunks which contain any known bad sectors are read one sector at a time straight away.
+    pub fn read_chunk(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<usize> {
+        self.apply_read_mode();
+        let offset = sector * self.settings.sector_size;
+        let filled = if self.is_known_bad(offset..offset + buffer.len() as u64) {
+            self.read_sectors_individually(offset, buffer)?
+        } else {
+            match self.file.read_at(offset, buffer) {
+                Ok(filled) => filled,
+                Err(err) if device::is_media_error(&err) => self.read_sectors_individually(offset, buffer)?,
+                Err(err) => return Err(err),
+            }
+        };
+
+        // Zero out any stale data left in the buffer past the end of the device.
+        buffer[filled..].fill(0);
+        Ok(filled)
+    }
+
+    /// Reads the data starting at `offset` into `buffer` one sector at a time, zeroing and recording any sectors
+    /// which can't be read because of damaged media. Returns the number of bytes that were filled, which stops at the
+    /// end of the device. Any other error is returned straight away.
+    ///
+    /// The device can take several seconds to give up on each bad sector, so the interrupt flag is checked before
+    /// every sector. If it's set, an error with the `Interrupted` kind is returned.
+    fn read_sectors_individually(&mut self, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+        let sector_size = self.settings.sector_size as usize;
+        let length = self.length.saturating_sub(offset).min(buffer.len() as u64) as usize;
+
+        // Direct reads are widened to whole blocks, so if the device's blocks are larger than a sector, every sector
+        // which shares a block with a bad one would fail too. Those sectors are re-read through the page cache instead,
+        // and the next call to `read_chunk` turns direct I/O back on.
+        if self.file.direct_io() == DirectIo::On && self.file.block_size() > sector_size {
+            self.file.set_direct_io(false);
+        }
+        let mut filled = 0;
+        while filled < length {
+            if self.is_interrupted() {
+                return Err(interrupt::interrupted_error("re-reading", filled as u64, length as u64));
+            }
+
+            let sector_offset = offset + filled as u64;
+            let sector = &mut buffer[filled..(filled + sector_size).min(length)];
+            let range = sector_offset..sector_offset + sector.len() as u64;
+            if self.is_known_bad(range.clone()) {
+                sector.fill(0);
+                let err = io::Error::other("the sector was marked as unreadable by a loaded sector map");
+                self.bad_sectors.record(range.clone(), &err);
+                self.recent_bad_sectors.record(range, &err);
+                filled += sector.len();
+                continue;
+            }
+            match self.file.read_at(sector_offset, sector) {
+                Ok(count) if count == sector.len() => {}
+                // The device ended sooner than expected, so there's nothing left to read.
+                Ok(count) => return Ok(filled + count),
+                Err(err) if device::is_media_error(&err) => {
+                    sector.fill(0);
+                    self.bad_sectors.record(range.clone(), &err);
+                    self.recent_bad_sectors.record(range, &err);
+                }
+                Err(err) => return Err(err),
+            }
+            filled += sector.len();
+        }
+        Ok(filled)
+    }
+
+    /// Returns whether any of the bytes in `range` are in a sector which a loaded sector map marks as unreadable.
+    /// The map's sectors can be a different size to the current `sector_size`.
+    fn is_known_bad(&self, range: Range<u64>) -> bool {
+        let Some(map) = &self.known_bad_sectors else {
+            return false;
+        };
+        let sector_size = u64::from(map.sector_size());
+        (range.start / sector_size..ceil_divide!(range.end, sector_size)).any(|sector| map.contains(sector))
+    }
+
+    /// Reads up to `count` bytes, starting at the cursor's position. Fewer bytes are returned if the
+    /// end of the device is reached first. This doesn't move the cursor.
+    pub fn read(&mut self, count: u64) -> io::Result<Vec<u8>> {
+        // Clamp the number of bytes to read so we don't try to read past the end of the device.
+        let end = self.position.saturating_add(count).min(self.length);
+        let mut data = Vec::with_capacity((end - self.position) as usize);
+
+        // Start reading from the sector that contains the cursor, and skip over any bytes in that sector
+        // which come before the cursor. Every chunk after the first one is then copied in its entirety.
+        // Small reads only need a buffer that's large enough to hold the sectors they cover.
+        let sector_size = self.settings.sector_size;
+        let mut sector = self.position / sector_size;
+        let mut skip = (self.position % sector_size) as usize;
+        let chunk_size = (skip as u64 + (end - self.position)).next_multiple_of(sector_size).min(self.settings.chunk_size as u64) as usize;
+        let mut buffer = DynAlignedBuffer::new(chunk_size, PAGE_SIZE);
+        while self.position + (data.len() as u64) < end {
+            // Only the sectors which contain requested bytes are read, so that sectors past the end of the request
+            // are never reported as bad.
+            let remaining = (end - self.position) as usize - data.len();
+            let needed = (skip + remaining).next_multiple_of(sector_size as usize).min(chunk_size);
+            let filled = self.read_chunk(sector, &mut buffer[..needed])?;
+            if filled <= skip {
+                break;
+            }
+
+            let available = &buffer[skip..filled];
+            data.extend_from_slice(&available[..remaining.min(available.len())]);
+
+            sector += (chunk_size as u64) / sector_size;
+            skip = 0;
+        }
+        Ok(data)
+    }
+
+    /// Scans the entire device, and returns a map of which sectors contain non-zero data.
+    pub fn map_nonzero(&mut self) -> io::Result<SectorMap> {
+        let scanner = NonZeroScanner { sector_size: self.settings.sector_size as usize };
+        let mut builder = SectorMapBuilder::new(self.settings.sector_size as u32);
+        let mut pipeline = ScanPipeline::new(self.settings.chunk_size, self.settings.worker_threads);
+        pipeline.run(self, 0, u64::MAX, &scanner, |runs| {
+            // The outputs arrive in order, so we can push them straight into the builder.
+            for (count, occupied) in runs {
+                builder.push_run(count, occupied);
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok(builder.build())
+    }
+
+    /// Prints a hexdump of the bytes requested by `print`, starting at the cursor's position,
+    /// then moves the cursor to the end of the printed bytes.
+    ///
+    /// If the reader is interrupted, the bytes printed so far are left in place (and the cursor is moved past them),
+    /// and an error describing how many were printed is returned.
+    pub fn print(&mut self, print: &Print, out: &mut impl Write) -> Result<(), String> {
+        self.recent_bad_sectors = BadSectorLog::default();
+        let start = self.position;
+        let end = self.position.saturating_add(print.0).min(self.length);
+        let mut hexdump = HexDump::new(&mut *out, self.position, self.settings.print_width, self.settings.display_base);
+
+        // Read and print the data a chunk at a time, so large dumps don't need to be held in memory.
+        // The cursor is advanced past each chunk once it's been printed.
+        while self.position < end {
+            let count = (end - self.position).min(self.settings.chunk_size as u64);
+            let data = self.read(count).map_err(describe_read_error)?;
+            if data.is_empty() {
+                break;
+            }
+            hexdump.write(&data).map_err(|err| err.to_string())?;
+            self.position += data.len() as u64;
+
+            if self.is_interrupted() && self.position < end {
+                hexdump.finish().map_err(|err| err.to_string())?;
+                self.report_bad_sectors(out).map_err(|err| err.to_string())?;
+                return Err(interrupt::interrupted_error("printing", self.position - start, end - start).to_string());
+            }
+        }
+        hexdump.finish().map_err(|err| err.to_string())?;
+        self.report_bad_sectors(out).map_err(|err| err.to_string())
+    }
+
+    /// Searches the device for the data described by `find`, and prints a summary of the results.
+    /// Returns whether anything was found.
+    pub fn find(&mut self, find: &Find, out: &mut impl Write) -> Result<bool, String> {
+        self.recent_bad_sectors = BadSectorLog::default();
+        match find {
+            Find::NonZero(path) => self.find_nonzero(path.as_deref(), out),
+            Find::Byte(pattern) => self.find_matches(pattern, out),
+            Find::String(pattern) => self.find_matches(pattern, out),
+        }
+    }
+
+    /// Maps out which sectors of the device contain non-zero data, then prints a summary of the non-empty
+    /// extents, and saves the map to `path` (if one was provided). The cursor is then moved to the start of the
+    /// nearest non-empty sector in the search direction, starting with the cursor's own sector.
+    fn find_nonzero(&mut self, path: Option<&Path>, out: &mut impl Write) -> Result<bool, String> {
+        let sector_size = self.settings.sector_size;
+        let map = self.map_nonzero();
+        self.report_bad_sectors(out).map_err(|err| err.to_string())?;
+        let map = map.map_err(describe_read_error)?;
+        let total_sectors = ceil_divide!(self.length, sector_size);
+        let description = "contain non-zero data";
+        match self.output_format {
+            OutputFormat::Text => print_sector_map_summary(&map, total_sectors, description, self.settings.display_base, out),
+            OutputFormat::Lines => map.extents().iter().try_for_each(|extent| {
+                let offset = extent.start * sector_size;
+                writeln!(out, "{offset}\t{}\t{}", extent.start, (extent.end - extent.start) * sector_size)
+            }),
+        }.map_err(|err| err.to_string())?;
+
+        if let Some(path) = path {
+            match self.output_format {
+                OutputFormat::Text => save_sector_map(&map, path, self.file.file_id(), out)?,
+                OutputFormat::Lines => save_sector_map(&map, path, self.file.file_id(), &mut io::sink())?,
+            }
+        }
+
+        // Move the cursor to the start of the nearest non-empty sector, either at or after it, or at or before it.
+        let cursor_sector = self.position / sector_size;
+        let extents = map.extents();
+        let nearest_sector = match self.settings.search_direction {
+            SearchDirection::Forward => extents.iter()
+                .find(|extent| extent.end > cursor_sector)
+                .map(|extent| extent.start.max(cursor_sector)),
+            SearchDirection::Backward => extents.iter()
+                .rev()
+                .find(|extent| extent.start <= cursor_sector)
+                .map(|extent| (extent.end - 1).min(cursor_sector)),
+        };
+        if let Some(sector) = nearest_sector {
+            self.position = sector * sector_size;
+            if self.output_format == OutputFormat::Text {
+                writeln!(out, "Moved the cursor to offset {} (sector {sector}).", self.position).map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(nearest_sector.is_some())
+    }
+
+    /// Searches the device for matches with `scanner`, starting at the cursor's position and moving in the search
+    /// direction, and prints each match as it's found. If any matches are found, the cursor is moved to the start
+    /// of the first one.
+    fn find_matches<S: Scanner<Output = Vec<Match>>>(&mut self, scanner: &S, out: &mut impl Write) -> Result<bool, String> {
+        let start = self.position;
+        let sector_size = self.settings.sector_size;
+        let direction = self.settings.search_direction;
+        let display_base = self.settings.display_base;
+        let output_format = self.output_format;
+        let mut first_match = None;
+        let mut match_count = 0u64;
+        let mut write_result = Ok(());
+
+        // Forward searches start at the beginning of the cursor's sector, so skip over any matches before the cursor.
+        // Backward searches scan everything before the cursor (plus enough data to see the entirety of any matches
+        // that start before it), so skip over any matches at or after the cursor.
+        let (start_sector, end) = match direction {
+            SearchDirection::Forward => (start / sector_size, u64::MAX),
+            SearchDirection::Backward => (0, start.saturating_add(scanner.overlap() as u64)),
+        };
+        let mut sink = |matches: Vec<Match>| {
+            let matches: Box<dyn Iterator<Item = Match>> = match direction {
+                SearchDirection::Forward => Box::new(matches.into_iter().filter(|found| found.offset >= start)),
+                SearchDirection::Backward => Box::new(matches.into_iter().rev().filter(|found| found.offset < start)),
+            };
+            for found in matches {
+                first_match.get_or_insert(found.offset);
+                match_count += 1;
+                // Matches found by string patterns also list the encoding they were found in.
+                write_result = match output_format {
+                    OutputFormat::Text => writeln!(
+                        out,
+                        "    offset {}    sector {:>12}    {} byte(s){}",
+                        display_base.format_offset(found.offset),
+                        found.offset / sector_size,
+                        found.length,
+                        found.encoding.map(|encoding| format!("    {encoding}")).unwrap_or_default(),
+                    ),
+                    OutputFormat::Lines => writeln!(
+                        out,
+                        "{}\t{}\t{}\t{}",
+                        found.offset,
+                        found.offset / sector_size,
+                        found.length,
+                        found.encoding.map_or_else(|| "-".to_owned(), |encoding| encoding.to_string()),
+                    ),
+                };
+                if write_result.is_err() {
+                    return ControlFlow::Break(());
+                }
+            }
+            ControlFlow::Continue(())
+        };
+
+        let mut pipeline = ScanPipeline::new(self.settings.chunk_size, self.settings.worker_threads);
+        let result = pipeline.run_in_direction(self, start_sector, end, direction, scanner, &mut sink);
+        write_result.map_err(|err| err.to_string())?;
+        self.report_bad_sectors(out).map_err(|err| err.to_string())?;
+        result.map_err(describe_read_error)?;
+
+        if let Some(offset) = first_match {
+            self.position = offset;
+        }
+        if output_format == OutputFormat::Text {
+            match first_match {
+                Some(offset) => writeln!(out, "Found {match_count} match(es). Moved the cursor to offset {offset} (sector {}).", offset / sector_size),
+                None => writeln!(out, "No matches found."),
+            }.map_err(|err| err.to_string())?;
+        }
+        Ok(first_match.is_some())
+    }
+
+    /// Prints a summary of every sector which couldn't be read since the device was opened, and saves a map of them
+    /// to `path` (if one was provided). The map has the same format as the ones saved by `find nonzero`, except that
+    /// the sectors it marks as occupied are the unreadable ones.
+    pub fn list_bad_sectors(&self, path: Option<&Path>, out: &mut impl Write) -> Result<(), String> {
+        let map = self.bad_sectors.to_sector_map(self.settings.sector_size as u32);
+        let total_sectors = ceil_divide!(self.length, self.settings.sector_size);
+        let description = "couldn't be read";
+        print_sector_map_summary(&map, total_sectors, description, self.settings.display_base, out).map_err(|err| err.to_string())?;
+        if let Some(error) = self.bad_sectors.last_error() {
+            writeln!(out, "The last read error was: {error}").map_err(|err| err.to_string())?;
+        }
+
+        match path {
+            Some(path) => save_sector_map(&map, path, self.file.file_id(), out),
+            None => Ok(()),
+        }
+    }
+
+    /// Loads a map of sectors which couldn't be read (like the ones saved by `bad-sectors`) from `path`. From then on,
+    /// those sectors are treated as unreadable without being read, which replaces any map that was loaded before.
+    pub fn load_bad_sectors(&mut self, path: &Path, out: &mut impl Write) -> Result<(), String> {
+        let map = File::open(path)
+            .and_then(|file| SectorMap::read_from(&mut BufReader::new(file)))
+            .map_err(|err| format!("Failed to load the sector map from '{}': {err}", path.display()))?;
+        writeln!(
+            out,
+            "Loaded {} bad sector(s) of {} bytes from '{}'. They'll be treated as zeroes without being read.",
+            map.occupied_count(),
+            map.sector_size(),
+            path.display(),
+        ).map_err(|err| err.to_string())?;
+        self.known_bad_sectors = Some(map);
+        Ok(())
+    }
+
+    /// Prints how many sectors couldn't be read since the current operation started (if any), and the last error
+    /// that was encountered while reading them. When results are written as lines, this is printed to stderr instead,
+    /// so that the output only contains results.
+    fn report_bad_sectors(&self, out: &mut impl Write) -> io::Result<()> {
+        let log = &self.recent_bad_sectors;
+        let Some(error) = log.last_error().filter(|_| !log.is_empty()) else {
+            return Ok(());
+        };
+        let message = format!(
+            "Couldn't read {} sector(s) ({}), which were treated as zeroes. The last error was: {error}",
+            log.to_sector_map(self.settings.sector_size as u32).occupied_count(),
+            format_byte_count(log.byte_count()),
+        );
+        match self.output_format {
+            OutputFormat::Text => {
+                writeln!(out, "{message}")?;
+                writeln!(out, "Enter 'bad-sectors' to list every sector which couldn't be read.")
+            }
+            OutputFormat::Lines => writeln!(io::stderr(), "warning: {message}"),
+        }
+    }
+}
+
+/// Describes an error which stopped an operation from reading the device. Operations which were interrupted already
+/// describe how far they got, so only genuine read errors are attributed to the device.
+fn describe_read_error(err: io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::Interrupted => err.to_string(),
+        _ => format!("Failed to read from the device: {err}"),
+    }
+}
+
+/// The largest number of extents that are listed in a sector map summary.
+const MAX_LISTED_EXTENTS: usize = 32;
+
+/// Prints a summary of the occupied extents stored in `map`, for a device that is `total_sectors` long.
+/// `description` says what the occupied sectors have in common, and offsets are printed in `display_base`.
+fn print_sector_map_summary(
+    map: &SectorMap,
+    total_sectors: u64,
+    description: &str,
+    display_base: DisplayBase,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let sector_size = map.sector_size() as u64;
+    let extents = map.extents();
+    let occupied = map.occupied_count();
+    let percentage = if total_sectors == 0 { 0.0 } else { (occupied as f64) * 100.0 / (total_sectors as f64) };
+
+    writeln!(
+        out,
+        "{occupied} of {total_sectors} sectors ({percentage:.2}%) {description}, in {} extent(s).",
+        extents.len(),
+    )?;
+
+    // List each extent's sectors, byte offsets, and size. Only the first few are printed, since a fragmented
+    // device could have millions of extents. The full list can be obtained by saving the map to a file.
+    for extent in extents.iter().take(MAX_LISTED_EXTENTS) {
+        writeln!(
+            out,
+            "    sectors {:>12} ~ {:<12}    offsets {} ~ {}    {:>10}",
+            extent.start,
+            extent.end - 1,
+            display_base.format_offset(extent.start * sector_size),
+            display_base.format_offset(extent.end * sector_size - 1),
+            format_byte_count((extent.end - extent.start) * sector_size),
+        )?;
+    }
+    if extents.len() > MAX_LISTED_EXTENTS {
+        writeln!(out, "    ... and {} more extent(s).", extents.len() - MAX_LISTED_EXTENTS)?;
+    }
+    Ok(())
+}
+
+/// Writes `map` to a new file at `path` (overwriting any existing file), then prints where it was saved.
+/// Refuses to write to the file/device identified by `device`, or any other device.
+fn save_sector_map(map: &SectorMap, path: &Path, device: Option<&FileId>, out: &mut impl Write) -> Result<(), String> {
+    let result = device::check_output_path(path, device).and_then(|_| File::create(path)).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        map.write_to(&mut writer)?;
+        writer.flush()
+    });
+    result.map_err(|err| format!("Failed to save the sector map to '{}': {err}", path.display()))?;
+    writeln!(out, "Saved the sector map to '{}'.", path.display()).map_err(|err| err.to_string())
+}
+
+/// A scanner which classifies every sector as either all-zero or non-zero. Its outputs are runs of
+/// consecutive sectors that are either all occupied or all empty, stored as `(sector_count, occupied)`.
+struct NonZeroScanner {
+    sector_size: usize,
+}
+
+impl Scanner for NonZeroScanner {
+    type Output = Vec<(u64, bool)>;
+
+    fn scan(&self, chunk: &DynAlignedBuffer, region: &Region) -> Vec<(u64, bool)> {
+        let u128s_per_sector = self.sector_size / 16;
+
+        // Check the region one sector at a time, viewing it as `u128`s so we can check 16 bytes at once.
+        // Any bytes past the end of the device were zeroed by `read_chunk`, so they can't mark a sector as occupied.
+        let sector_count = ceil_divide!(region.end - region.start, self.sector_size);
+        let first_u128 = region.start / 16;
+        let region_data = &chunk.view_as::<u128>()[first_u128..first_u128 + sector_count * u128s_per_sector];
+
+        let mut runs: Vec<(u64, bool)> = Vec::new();
+        for sector_data in region_data.chunks(u128s_per_sector) {
+            let occupied = sector_data.iter().any(|&value| value != 0);
+            match runs.last_mut() {
+                Some((count, run_occupied)) if *run_occupied == occupied => *count += 1,
+                _ => runs.push((1, occupied)),
+            }
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DEFAULT_CHUNK_SIZE, DEFAULT_SECTOR_SIZE};
+    use crate::test_util::{create_reader, test_data};
+    use std::ops::Range;
+
+    /// A device which fails every read that touches one of its bad byte ranges with `error`, which is the same error
+    /// that a damaged disk fails with unless it's changed.
+    /// While direct I/O is on, reads are widened to whole blocks (just like `ReadOnlyDevice` aligns them), so they
+    /// also fail if a bad range shares a block with any of the requested bytes.
+    struct FaultyDevice {
+        device: ReadOnlyDevice,
+        bad_ranges: Vec<Range<u64>>,
+        block_size: u64,
+        direct_io: bool,
+        error: fn() -> io::Error,
+    }
+
+    impl ReadAt for FaultyDevice {
+        fn read_at(&mut self, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+            let (mut start, mut end) = (offset, offset + buffer.len() as u64);
+            if self.direct_io {
+                start -= start % self.block_size;
+                end = end.next_multiple_of(self.block_size);
+            }
+            if self.bad_ranges.iter().any(|bad| bad.start < end && start < bad.end) {
+                return Err((self.error)());
+            }
+            self.device.read_at(offset, buffer)
+        }
+
+        fn direct_io(&self) -> DirectIo {
+            if self.direct_io { DirectIo::On } else { DirectIo::Off }
+        }
+
+        fn set_direct_io(&mut self, enabled: bool) {
+            self.direct_io = enabled;
+        }
+
+        fn block_size(&self) -> usize {
+            self.block_size as usize
+        }
+
+        fn file_id(&self) -> Option<&FileId> {
+            self.device.file_id()
+        }
+    }
+
+    impl io::Seek for FaultyDevice {
+        fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            self.device.seek(position)
+        }
+    }
+
+    /// Creates a temporary image file filled with the provided data, and opens a reader for it which can't read any
+    /// of the bytes in `bad_ranges`. The device has 512 byte blocks, like most disks.
+    fn create_faulty_reader(data: &[u8], bad_ranges: &[Range<u64>]) -> (tempfile::NamedTempFile, DeviceReader<FaultyDevice>) {
+        let (image, reader) = create_reader(data);
+        let device = FaultyDevice {
+            device: reader.file,
+            bad_ranges: bad_ranges.to_vec(),
+            block_size: 512,
+            direct_io: false,
+            error: || io::Error::from_raw_os_error(device::MEDIA_ERRORS[0]),
+        };
+        (image, DeviceReader::new(device).unwrap())
+    }
+
+    #[test]
+    fn length_matches_the_image_size() {
+        let (_image, reader) = create_reader(&test_data(3000));
+        assert_eq!(reader.length(), 3000);
+        assert_eq!(reader.position, 0);
+    }
+
+    #[test]
+    fn seek_moves_the_cursor() {
+        let (_image, mut reader) = create_reader(&test_data(4096));
+
+        assert_eq!(reader.seek(&Seek::Absolute(1000)), Ok(1000));
+        assert_eq!(reader.seek(&Seek::Relative(24)), Ok(1024));
+        assert_eq!(reader.seek(&Seek::Relative(-1024)), Ok(0));
+        assert_eq!(reader.seek(&Seek::Absolute(4096)), Ok(4096));
+    }
+
+    #[test]
+    fn seek_rejects_positions_outside_the_device() {
+        let (_image, mut reader) = create_reader(&test_data(4096));
+        reader.seek(&Seek::Absolute(100)).unwrap();
+
+        assert!(reader.seek(&Seek::Absolute(-1)).is_err());
+        assert!(reader.seek(&Seek::Absolute(4097)).is_err());
+        assert!(reader.seek(&Seek::Relative(-101)).is_err());
+        assert!(reader.seek(&Seek::Relative(3997)).is_err());
+
+        // Failed seeks shouldn't move the cursor.
+        assert_eq!(reader.position, 100);
+    }
+
+    #[test]
+    fn read_chunk_zeroes_the_buffer_past_the_end_of_the_device() {
+        let data = test_data(1000);
+        let (_image, mut reader) = create_reader(&data);
+
+        let mut buffer = DynAlignedBuffer::new(2048, 16);
+        buffer.fill(0xff);
+        assert_eq!(reader.read_chunk(0, &mut buffer).unwrap(), 1000);
+        assert_eq!(&buffer[..1000], &data[..]);
+        assert!(buffer[1000..].iter().all(|&byte| byte == 0));
+
+        // Reading a chunk that starts on the second sector should skip the first 512 bytes.
+        assert_eq!(reader.read_chunk(1, &mut buffer).unwrap(), 1000 - 512);
+        assert_eq!(&buffer[..1000 - 512], &data[512..]);
+    }
+
+    #[test]
+    fn read_chunk_zeroes_and_records_sectors_which_cant_be_read() {
+        let data = test_data(4000);
+        let (_image, mut reader) = create_faulty_reader(&data, &[1000..1001, 2100..2600]);
+
+        let mut buffer = DynAlignedBuffer::new(8192, PAGE_SIZE);
+        buffer.fill(0xff);
+        assert_eq!(reader.read_chunk(0, &mut buffer).unwrap(), 4000);
+        for (sector, sector_data) in buffer[..4000].chunks(512).enumerate() {
+            match sector {
+                1 | 4 | 5 => assert!(sector_data.iter().all(|&byte| byte == 0), "sector {} wasn't zeroed", sector),
+                _ => assert_eq!(sector_data, &data[sector * 512..(sector * 512 + 512).min(4000)], "sector {} is wrong", sector),
+            }
+        }
+        assert!(buffer[4000..].iter().all(|&byte| byte == 0));
+        assert_eq!(reader.bad_sectors.ranges().collect::<Vec<_>>(), vec![512..1024, 2048..3072]);
+        assert_eq!(reader.bad_sectors.last_error(), Some("Input/output error (os error 5)"));
+
+        // Re-reading stops as soon as the reader is interrupted, since every bad sector can take seconds to fail.
+        reader.set_interrupt_flag(Arc::new(AtomicBool::new(true)));
+        let err = reader.read_chunk(0, &mut buffer).unwrap_err();
+        assert_eq!(err.to_string(), "Interrupted by Ctrl-C after re-reading 0 of 4000 bytes (0.0%).");
+    }
+
+    #[test]
+    fn read_chunk_returns_errors_which_arent_caused_by_damaged_media() {
+        let (_image, mut reader) = create_faulty_reader(&test_data(4096), &[1000..1001, 2100..2600]);
+        reader.file.error = || io::Error::from(io::ErrorKind::PermissionDenied);
+
+        let mut buffer = DynAlignedBuffer::new(4096, PAGE_SIZE);
+        let err = reader.read_chunk(0, &mut buffer).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(reader.bad_sectors.is_empty());
+
+        // Operations stop with the error too, instead of carrying on past the sectors.
+        let err = reader.find(&Find::NonZero(None), &mut Vec::new()).unwrap_err();
+        assert_eq!(err, "Failed to read from the device: permission denied");
+        assert!(reader.bad_sectors.is_empty());
+    }
+
+    #[test]
+    fn only_bad_sectors_are_zeroed_when_blocks_are_larger_than_sectors() {
+        let data = test_data(4 * 4096);
+        let (_image, mut reader) = create_faulty_reader(&data, &[5120..5632, 12800..12801]);
+        reader.file.block_size = 4096;
+
+        // Any direct read from the second or fourth block fails, but only 1 sector in each of them is actually bad.
+        let mut buffer = DynAlignedBuffer::new(4 * 4096, PAGE_SIZE);
+        assert_eq!(reader.read_chunk(0, &mut buffer).unwrap(), 4 * 4096);
+        for (sector, sector_data) in buffer.chunks(512).enumerate() {
+            match sector {
+                10 | 25 => assert!(sector_data.iter().all(|&byte| byte == 0), "sector {} wasn't zeroed", sector),
+                _ => assert_eq!(sector_data, &data[sector * 512..sector * 512 + 512], "sector {} is wrong", sector),
+            }
+        }
+        assert_eq!(reader.bad_sectors.ranges().collect::<Vec<_>>(), vec![5120..5632, 12800..13312]);
+        assert_eq!(reader.bad_sectors.to_sector_map(512).extents(), vec![10..11, 25..26]);
+
+        // The next read goes back to using direct I/O.
+        reader.read_chunk(0, &mut buffer[..4096]).unwrap();
+        assert_eq!(reader.device().direct_io(), DirectIo::On);
+    }
+
+    #[test]
+    fn read_handles_unaligned_positions_and_multiple_chunks() {
+        let chunk_size = 64 * 1024;
+        let data = test_data(3 * chunk_size + 100);
+        let (_image, mut reader) = create_reader(&data);
+        reader.settings_mut().chunk_size = chunk_size;
+
+        reader.seek(&Seek::Absolute(700)).unwrap();
+        let length = 2 * chunk_size + 37;
+        assert_eq!(reader.read(length as u64).unwrap(), &data[700..700 + length]);
+
+        // Reading shouldn't move the cursor.
+        assert_eq!(reader.position, 700);
+    }
+
+    #[test]
+    fn read_stops_at_the_end_of_the_device() {
+        let chunk_size = 64 * 1024;
+        let data = test_data(chunk_size + 10);
+        let (_image, mut reader) = create_reader(&data);
+        reader.settings_mut().chunk_size = chunk_size;
+
+        reader.seek(&Seek::Absolute(chunk_size as i64 - 3)).unwrap();
+        assert_eq!(reader.read(1000).unwrap(), &data[chunk_size - 3..]);
+
+        reader.seek(&Seek::Absolute(data.len() as i64)).unwrap();
+        assert!(reader.read(1000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn map_nonzero_marks_every_sector_with_data() {
+        let mut data = vec![0; 3 * DEFAULT_CHUNK_SIZE + 700];
+        // Non-zero bytes at the start and end of sectors, on either side of a chunk boundary, and in the
+        // final partial sector of the device.
+        for position in [0, 511, 5000, 5001, DEFAULT_CHUNK_SIZE - 1, DEFAULT_CHUNK_SIZE, 3 * DEFAULT_CHUNK_SIZE + 699] {
+            data[position] = 0x80;
+        }
+        let (_image, mut reader) = create_reader(&data);
+
+        let map = reader.map_nonzero().unwrap();
+        let chunk_sector = (DEFAULT_CHUNK_SIZE as u64) / DEFAULT_SECTOR_SIZE;
+        assert_eq!(map.sector_size(), DEFAULT_SECTOR_SIZE as u32);
+        assert_eq!(map.extents(), vec![
+            0..1,
+            9..10,
+            chunk_sector - 1..chunk_sector + 1,
+            3 * chunk_sector + 1..3 * chunk_sector + 2,
+        ]);
+    }
+
+    #[test]
+    fn map_nonzero_of_an_empty_device_is_empty() {
+        let (_image, mut reader) = create_reader(&vec![0; 10_000]);
+        assert!(reader.map_nonzero().unwrap().extents().is_empty());
+    }
+
+    #[test]
+    fn find_nonzero_prints_a_summary_and_moves_the_cursor() {
+        let mut data = vec![0; 4096];
+        data[1024..1536].fill(7);
+        data[3000] = 7;
+        let (_image, mut reader) = create_reader(&data);
+        reader.seek(&Seek::Absolute(1600)).unwrap();
+
+        let mut output = Vec::new();
+        reader.find(&Find::NonZero(None), &mut output).unwrap();
+        assert_eq!(reader.position, 2560);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "2 of 8 sectors (25.00%) contain non-zero data, in 2 extent(s).\n\
+             \x20   sectors            2 ~ 2               offsets 0x000000000400 ~ 0x0000000005ff         512 B\n\
+             \x20   sectors            5 ~ 5               offsets 0x000000000a00 ~ 0x000000000bff         512 B\n\
+             Moved the cursor to offset 2560 (sector 5).\n",
+        );
+    }
+
+    #[test]
+    fn find_nonzero_saves_the_sector_map() {
+        let mut data = vec![0; 8192];
+        data[4096] = 1;
+        let (_image, mut reader) = create_reader(&data);
+        let map_file = tempfile::NamedTempFile::new().unwrap();
+
+        let mut output = Vec::new();
+        reader.find(&Find::NonZero(Some(map_file.path().to_owned())), &mut output).unwrap();
+
+        let saved_map = SectorMap::read_from(&mut File::open(map_file.path()).unwrap()).unwrap();
+        assert_eq!(saved_map, reader.map_nonzero().unwrap());
+        assert_eq!(saved_map.extents(), vec![8..9]);
+    }
+
+    #[test]
+    fn find_bytes_reports_matches_after_the_cursor() {
+        let mut data = vec![0; 4096];
+        data[100..103].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+        data[1500..1504].copy_from_slice(&[0xFF, 0xD8, 0x00, 0xFF]);
+        data[3000..3003].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+        let (_image, mut reader) = create_reader(&data);
+        reader.seek(&Seek::Absolute(101)).unwrap();
+
+        let mut output = Vec::new();
+        assert!(reader.find(&Find::Byte("FF D8 {0-1} FF".parse().unwrap()), &mut output).unwrap());
+        assert_eq!(reader.position, 1500);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "    offset 0x0000000005dc    sector            2    4 byte(s)\n\
+             \x20   offset 0x000000000bb8    sector            5    3 byte(s)\n\
+             Found 2 match(es). Moved the cursor to offset 1500 (sector 2).\n",
+        );
+    }
+
+    #[test]
+    fn find_bytes_searches_backward_from_the_cursor() {
+        let mut data = vec![0; DEFAULT_CHUNK_SIZE + 4096];
+        let positions = [100, 1500, DEFAULT_CHUNK_SIZE - 1, DEFAULT_CHUNK_SIZE + 3000];
+        for &position in &positions {
+            data[position..position + 2].copy_from_slice(&[0xFF, 0xD8]);
+        }
+        let (_image, mut reader) = create_reader(&data);
+        reader.settings_mut().search_direction = SearchDirection::Backward;
+        reader.settings_mut().display_base = DisplayBase::Decimal;
+        reader.seek(&Seek::Absolute(DEFAULT_CHUNK_SIZE as i64 + 3000)).unwrap();
+
+        // Matches are listed nearest first, and the match at the cursor is excluded.
+        let mut output = Vec::new();
+        reader.find(&Find::Byte("FF D8".parse().unwrap()), &mut output).unwrap();
+        assert_eq!(reader.position, DEFAULT_CHUNK_SIZE as u64 - 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "    offset {:>14}    sector {:>12}    2 byte(s)\n\
+                 \x20   offset           1500    sector            2    2 byte(s)\n\
+                 \x20   offset            100    sector            0    2 byte(s)\n\
+                 Found 3 match(es). Moved the cursor to offset {} (sector {}).\n",
+                DEFAULT_CHUNK_SIZE - 1,
+                (DEFAULT_CHUNK_SIZE - 1) / 512,
+                DEFAULT_CHUNK_SIZE - 1,
+                (DEFAULT_CHUNK_SIZE - 1) / 512,
+            ),
+        );
+    }
+
+    #[test]
+    fn find_nonzero_uses_the_configured_sector_size_and_direction() {
+        let mut data = vec![0; 32768];
+        data[5000] = 1;
+        data[20000] = 1;
+        let (_image, mut reader) = create_reader(&data);
+        reader.settings_mut().sector_size = 4096;
+        reader.settings_mut().search_direction = SearchDirection::Backward;
+        reader.seek(&Seek::Absolute(16000)).unwrap();
+
+        let mut output = Vec::new();
+        reader.find(&Find::NonZero(None), &mut output).unwrap();
+        assert_eq!(reader.position, 4096);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("2 of 8 sectors (25.00%) contain non-zero data"), "unexpected output: {}", output);
+        assert!(output.ends_with("Moved the cursor to offset 4096 (sector 1).\n"), "unexpected output: {}", output);
+    }
+
+    #[test]
+    fn find_bytes_finds_matches_that_straddle_chunk_boundaries() {
+        let mut data = vec![0; 2 * DEFAULT_CHUNK_SIZE + 100];
+        let needle = [0x50, 0x4B, 0x03, 0x04];
+        let positions = [DEFAULT_CHUNK_SIZE - 2, 2 * DEFAULT_CHUNK_SIZE - 512 - 1, 2 * DEFAULT_CHUNK_SIZE + 96];
+        for &position in &positions {
+            data[position..position + needle.len()].copy_from_slice(&needle);
+        }
+        let (_image, mut reader) = create_reader(&data);
+
+        let mut output = Vec::new();
+        reader.find(&Find::Byte("50 4B 03 04".parse().unwrap()), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        for position in positions {
+            assert!(output.contains(&format!("offset {position:#014x}")), "missing match at {}: {}", position, output);
+        }
+        assert!(output.contains("Found 3 match(es)."));
+    }
+
+    #[test]
+    fn find_string_finds_matches_that_straddle_chunk_boundaries() {
+        let mut data = vec![0; 2 * DEFAULT_CHUNK_SIZE];
+        let positions = [5, DEFAULT_CHUNK_SIZE - 4, 2 * DEFAULT_CHUNK_SIZE - 1024 - 3];
+        for &position in &positions {
+            data[position..position + 8].copy_from_slice(b"Password");
+        }
+        let (_image, mut reader) = create_reader(&data);
+
+        let mut output = Vec::new();
+        reader.find(&Find::String("-i password".parse().unwrap()), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        for position in positions {
+            let expected = format!("offset {:#014x}    sector {:>12}    8 byte(s)    utf8", position, position / 512);
+            assert!(output.contains(&expected), "missing match at {}: {}", position, output);
+        }
+        assert!(output.contains("Found 3 match(es)."));
+    }
+
+    #[test]
+    fn find_string_reports_the_encoding_of_each_match() {
+        let mut data = vec![0; 4096];
+        data[1000..1016].copy_from_slice(&"password".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>());
+        data[2000..2008].copy_from_slice(b"password");
+        let (_image, mut reader) = create_reader(&data);
+
+        let mut output = Vec::new();
+        reader.find(&Find::String("--encoding any password".parse().unwrap()), &mut output).unwrap();
+        assert_eq!(reader.position, 1000);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "    offset 0x0000000003e8    sector            1    16 byte(s)    utf16le\n\
+             \x20   offset 0x0000000007d0    sector            3    8 byte(s)    utf8\n\
+             Found 2 match(es). Moved the cursor to offset 1000 (sector 1).\n",
+        );
+    }
+
+    #[test]
+    fn find_without_matches_leaves_the_cursor_alone() {
+        let (_image, mut reader) = create_reader(&vec![0; 4096]);
+        reader.seek(&Seek::Absolute(10)).unwrap();
+
+        let mut output = Vec::new();
+        assert!(!reader.find(&Find::Byte("01".parse().unwrap()), &mut output).unwrap());
+        assert_eq!(reader.position, 10);
+        assert_eq!(String::from_utf8(output).unwrap(), "No matches found.\n");
+    }
+
+    #[test]
+    fn find_results_can_be_written_as_lines() {
+        let mut data = vec![0; 4096];
+        data[1000..1016].copy_from_slice(&"password".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>());
+        data[2000..2008].copy_from_slice(b"password");
+        let (_image, mut reader) = create_reader(&data);
+        reader.set_output_format(OutputFormat::Lines);
+
+        let mut output = Vec::new();
+        assert!(reader.find(&Find::String("--encoding any password".parse().unwrap()), &mut output).unwrap());
+        assert!(reader.find(&Find::Byte("00 'pass'".parse().unwrap()), &mut output).unwrap());
+        assert!(!reader.find(&Find::Byte("01".parse().unwrap()), &mut output).unwrap());
+        assert_eq!(reader.position, 1999);
+        assert_eq!(String::from_utf8(output).unwrap(), "1000\t1\t16\tutf16le\n2000\t3\t8\tutf8\n1999\t3\t5\t-\n");
+
+        let map_file = tempfile::NamedTempFile::new().unwrap();
+        let mut output = Vec::new();
+        reader.seek(&Seek::Absolute(0)).unwrap();
+        assert!(reader.find(&Find::NonZero(Some(map_file.path().to_owned())), &mut output).unwrap());
+        assert_eq!(reader.position, 512);
+        assert_eq!(String::from_utf8(output).unwrap(), "512\t1\t512\n1536\t3\t512\n");
+        assert_eq!(SectorMap::read_from(&mut File::open(map_file.path()).unwrap()).unwrap().extents(), vec![1..2, 3..4]);
+    }
+
+    #[test]
+    fn print_writes_a_hexdump_and_advances_the_cursor() {
+        let data = test_data(64);
+        let (_image, mut reader) = create_reader(&data);
+        reader.seek(&Seek::Absolute(14)).unwrap();
+
+        let mut output = Vec::new();
+        reader.print(&Print(18), &mut output).unwrap();
+        assert_eq!(reader.position, 32);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "000000000000000e  0e 0f 10 11 12 13 14 15  16 17 18 19 1a 1b 1c 1d  |................|\n\
+             000000000000001e  1e 1f                                             |..|\n\
+             0000000000000020\n",
+        );
+    }
+
+    #[test]
+    fn print_uses_the_configured_width_and_base() {
+        let (_image, mut reader) = create_reader(&test_data(64));
+        reader.settings_mut().print_width = 4;
+        reader.settings_mut().display_base = DisplayBase::Decimal;
+        reader.seek(&Seek::Absolute(10)).unwrap();
+
+        let mut output = Vec::new();
+        reader.print(&Print(6), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "              10  0a 0b 0c 0d  |....|\n\
+             \x20             14  0e 0f        |..|\n\
+             \x20             16\n",
+        );
+    }
+
+    #[test]
+    fn print_stops_at_the_end_of_the_device() {
+        let chunk_size = 64 * 1024;
+        let (_image, mut reader) = create_reader(&vec![0xAB; 3 * chunk_size + 10]);
+        reader.settings_mut().chunk_size = chunk_size;
+        reader.seek(&Seek::Absolute(5)).unwrap();
+
+        let mut output = Vec::new();
+        reader.print(&Print(u64::MAX), &mut output).unwrap();
+        assert_eq!(reader.position, reader.length());
+
+        // Every row after the first is identical, so they should all be collapsed.
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4, "unexpected output: {}", output);
+        assert_eq!(lines[1], "*");
+        assert_eq!(lines[3], format!("{:016x}", 3 * chunk_size + 10));
+    }
+
+    #[test]
+    fn find_carries_on_past_sectors_which_cant_be_read() {
+        let mut data = vec![0; 4096];
+        data[100..103].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+        data[1100..1103].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+        data[3000..3003].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+        let (_image, mut reader) = create_faulty_reader(&data, &[1024..1536, 2048..2100]);
+
+        let mut output = Vec::new();
+        assert!(reader.find(&Find::Byte("FF D8 FF".parse().unwrap()), &mut output).unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "    offset 0x000000000064    sector            0    3 byte(s)\n\
+             \x20   offset 0x000000000bb8    sector            5    3 byte(s)\n\
+             Couldn't read 2 sector(s) (1.0 KB), which were treated as zeroes. The last error was: Input/output error (os error 5)\n\
+             Enter 'bad-sectors' to list every sector which couldn't be read.\n\
+             Found 2 match(es). Moved the cursor to offset 100 (sector 0).\n",
+        );
+
+        // Each operation only reports the sectors it couldn't read itself.
+        let mut output = Vec::new();
+        reader.find(&Find::NonZero(None), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Couldn't read 2 sector(s) (1.0 KB)"), "unexpected output: {}", output);
+        assert!(output.contains("2 of 8 sectors (25.00%) contain non-zero data, in 2 extent(s)."), "unexpected output: {}", output);
+    }
+
+    #[test]
+    fn print_shows_sectors_which_cant_be_read_as_zeroes() {
+        let (_image, mut reader) = create_faulty_reader(&test_data(1536), &[512..1024, 1100..1101]);
+        reader.seek(&Seek::Absolute(504)).unwrap();
+
+        let mut output = Vec::new();
+        reader.print(&Print(16), &mut output).unwrap();
+        assert_eq!(reader.position, 520);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "00000000000001f8  02 03 04 05 06 07 08 09  00 00 00 00 00 00 00 00  |................|\n\
+             0000000000000208\n\
+             Couldn't read 1 sector(s) (512 B), which were treated as zeroes. The last error was: Input/output error (os error 5)\n\
+             Enter 'bad-sectors' to list every sector which couldn't be read.\n",
+        );
+
+        // Only the sectors which were printed are read, so the bad sector after them isn't reported.
+        reader.seek(&Seek::Absolute(0)).unwrap();
+        let mut output = Vec::new();
+        reader.print(&Print(16), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn bad_sectors_are_listed_and_saved_as_a_sector_map() {
+        let (_image, mut reader) = create_faulty_reader(&vec![1; 8192], &[1024..2048, 7000..7001]);
+        let map_file = tempfile::NamedTempFile::new().unwrap();
+
+        let mut output = Vec::new();
+        reader.list_bad_sectors(None, &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "0 of 16 sectors (0.00%) couldn't be read, in 0 extent(s).\n");
+
+        reader.find(&Find::NonZero(None), &mut Vec::new()).unwrap();
+        let mut output = Vec::new();
+        reader.list_bad_sectors(Some(map_file.path()), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "3 of 16 sectors (18.75%) couldn't be read, in 2 extent(s).\n\
+                 \x20   sectors            2 ~ 3               offsets 0x000000000400 ~ 0x0000000007ff        1.0 KB\n\
+                 \x20   sectors           13 ~ 13              offsets 0x000000001a00 ~ 0x000000001bff         512 B\n\
+                 The last read error was: Input/output error (os error 5)\n\
+                 Saved the sector map to '{}'.\n",
+                map_file.path().display(),
+            ),
+        );
+
+        let saved_map = SectorMap::read_from(&mut File::open(map_file.path()).unwrap()).unwrap();
+        assert_eq!(saved_map, reader.bad_sectors.to_sector_map(512));
+        assert_eq!(saved_map.extents(), vec![2..4, 13..14]);
+    }
+
+    #[test]
+    fn sector_maps_are_never_saved_to_the_device_being_inspected() {
+        let data = test_data(4096);
+        let (image, mut reader) = create_faulty_reader(&data, &[1024..1536, 3000..3001]);
+        reader.find(&Find::NonZero(None), &mut Vec::new()).unwrap();
+
+        let expected = format!(
+            "Failed to save the sector map to '{}': it's the file/device being inspected, which is never written to",
+            image.path().display(),
+        );
+        assert_eq!(reader.list_bad_sectors(Some(image.path()), &mut Vec::new()), Err(expected.clone()));
+        assert_eq!(reader.find(&Find::NonZero(Some(image.path().to_owned())), &mut Vec::new()), Err(expected));
+        assert_eq!(std::fs::read(image.path()).unwrap(), data);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sector_maps_are_never_saved_to_a_device() {
+        let (_image, mut reader) = create_reader(&test_data(4096));
+        let expected = "Failed to save the sector map to '/dev/null': it's a device, which is never written to";
+        assert_eq!(reader.list_bad_sectors(Some(Path::new("/dev/null")), &mut Vec::new()), Err(expected.to_owned()));
+        assert_eq!(reader.find(&Find::NonZero(Some("/dev/null".into())), &mut Vec::new()), Err(expected.to_owned()));
+    }
+
+    #[test]
+    fn loaded_bad_sectors_are_zeroed_without_being_read() {
+        let (_image, mut reader) = create_faulty_reader(&vec![1; 8192], &[1024..2048, 6144..6656]);
+        reader.find(&Find::NonZero(None), &mut Vec::new()).unwrap();
+        let map_file = tempfile::NamedTempFile::new().unwrap();
+        reader.list_bad_sectors(Some(map_file.path()), &mut Vec::new()).unwrap();
+
+        // A new reader for the same device skips the sectors in the saved map, so none of its reads fail.
+        let (_image, mut reader) = create_faulty_reader(&vec![1; 8192], &[1024..2048, 6144..6656]);
+        let mut output = Vec::new();
+        reader.load_bad_sectors(map_file.path(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "Loaded 3 bad sector(s) of 512 bytes from '{}'. They'll be treated as zeroes without being read.\n",
+                map_file.path().display(),
+            ),
+        );
+        let data = reader.read(4096).unwrap();
+        assert!(data[1024..2048].iter().all(|&byte| byte == 0));
+        assert!(data[..1024].iter().chain(&data[2048..]).all(|&byte| byte == 1));
+        assert_eq!(reader.bad_sectors.ranges().collect::<Vec<_>>(), vec![1024..2048]);
+        assert_eq!(reader.bad_sectors.last_error(), Some("the sector was marked as unreadable by a loaded sector map"));
+
+        // With larger sectors, every sector which overlaps a known bad one is skipped.
+        reader.settings_mut().sector_size = 4096;
+        assert!(reader.read(8192).unwrap().iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn loading_bad_sectors_rejects_files_which_arent_sector_maps() {
+        let (_image, mut reader) = create_reader(&test_data(4096));
+        let not_a_map = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(not_a_map.path(), "sector-size = 512\n").unwrap();
+
+        let err = reader.load_bad_sectors(not_a_map.path(), &mut Vec::new()).unwrap_err();
+        assert_eq!(err, format!("Failed to load the sector map from '{}': file is not a sector map", not_a_map.path().display()));
+        assert!(reader.known_bad_sectors.is_none());
+    }
+
+    #[test]
+    fn interrupted_operations_report_how_far_they_got() {
+        let (_image, mut reader) = create_reader(&vec![0xAB; 1 << 18]);
+        reader.set_interrupt_flag(Arc::new(AtomicBool::new(true)));
+
+        // Printing stops after the first chunk, but the rows printed so far are kept.
+        reader.settings_mut().chunk_size = 1 << 17;
+        let mut output = Vec::new();
+        let err = reader.print(&Print(u64::MAX), &mut output).unwrap_err();
+        assert_eq!(err, "Interrupted by Ctrl-C after printing 131072 of 262144 bytes (50.0%).");
+        assert_eq!(reader.position, 1 << 17);
+        assert_eq!(String::from_utf8(output).unwrap().lines().last(), Some(format!("{:016x}", 1 << 17).as_str()));
+
+        reader.seek(&Seek::Absolute(0)).unwrap();
+        reader.settings_mut().chunk_size = 1 << 16;
+        let err = reader.find(&Find::NonZero(None), &mut Vec::new()).unwrap_err();
+        assert!(err.starts_with("Interrupted by Ctrl-C after scanning 65536 of 262144 bytes"), "wrong error: {}", err);
+
+        // Once the flag is cleared, operations run to completion again.
+        reader.clear_interrupt();
+        assert_eq!(reader.find(&Find::NonZero(None), &mut Vec::new()), Ok(true));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::command::Command;
+use crate::config::ErrorMode;
+use crate::reader::DeviceReader;
+use std::fs;
+use std::path::Path;
+
+/// The deepest that scripts can be nested inside each other with `source`.
+/// This stops scripts which (directly or indirectly) source themselves from recursing forever.
+const MAX_SCRIPT_DEPTH: usize = 16;
+
+/// Runs every command in the script at `path` against `reader`, passing each one to `execute`.
+///
+/// Each line of a script holds a single command, written the same way it would be at the prompt. Blank lines and
+/// lines starting with `#` are ignored, and an `exit` command stops the script (but not the program). `source`
+/// commands run the other script in place, instead of being passed to `execute`.
+///
+/// Errors are prefixed with the script's path and the line they occurred on. If the reader's `script_errors` setting
+/// is `Stop`, the script stops at the first error and returns it. Otherwise each error is printed as it occurs, and
+/// an error summarizing how many lines failed is returned once the script has finished. A script which is
+/// interrupted with Ctrl-C always stops, regardless of the setting.
+pub fn run_script<F>(reader: &mut DeviceReader, path: &Path, execute: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut DeviceReader, Command) -> Result<bool, String>,
+{
+    run_nested_script(reader, path, 0, execute)
+}
+
+/// Runs the script at `path`, which is nested inside `depth` other scripts.
+fn run_nested_script<F>(reader: &mut DeviceReader, path: &Path, depth: usize, execute: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut DeviceReader, Command) -> Result<bool, String>,
+{
+    if depth >= MAX_SCRIPT_DEPTH {
+        return Err(format!("Scripts can't be nested more than {MAX_SCRIPT_DEPTH} levels deep."));
+    }
+    let contents = fs::read_to_string(path).map_err(|err| format!("Failed to read the script '{}': {err}", path.display()))?;
+
+    let mut failed_lines = 0;
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if reader.is_interrupted() {
+            return Err(format!("{}:{}: Interrupted by Ctrl-C before this line was run.", path.display(), index + 1));
+        }
+
+        let result = line.parse::<Command>().and_then(|command| match command {
+            Command::Exit => Ok(false),
+            Command::Source(nested_path) => run_nested_script(reader, &nested_path, depth + 1, execute).map(|_| true),
+            command => execute(reader, command).map(|_| true),
+        });
+        match result {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => {
+                // The setting is checked for every error, since the script itself may have changed it.
+                let err = format!("{}:{}: {err}", path.display(), index + 1);
+                match reader.settings().script_errors {
+                    ErrorMode::Continue if !reader.is_interrupted() => {
+                        eprintln!("error: {err}");
+                        failed_lines += 1;
+                    }
+                    _ => return Err(err),
+                }
+            }
+        }
+    }
+
+    match failed_lines {
+        0 => Ok(()),
+        _ => Err(format!("{failed_lines} line(s) of '{}' failed.", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::ReadOnlyDevice;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Creates a temporary directory containing a small device image, and opens a reader for it.
+    fn create_reader() -> (tempfile::TempDir, DeviceReader) {
+        let directory = tempfile::tempdir().unwrap();
+        let image_path = directory.path().join("image.dd");
+        fs::write(&image_path, vec![0; 4096]).unwrap();
+        let reader = DeviceReader::new(ReadOnlyDevice::open(&image_path).unwrap()).unwrap();
+        (directory, reader)
+    }
+
+    /// Writes a script with the specified contents into `directory`, and returns its path.
+    fn write_script(directory: &tempfile::TempDir, name: &str, contents: &str) -> PathBuf {
+        let path = directory.path().join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Runs the script at `path`, and returns its result along with the debug representation of every executed command.
+    fn run(reader: &mut DeviceReader, path: &Path) -> (Result<(), String>, Vec<String>) {
+        let mut executed = Vec::new();
+        let result = run_script(reader, path, &mut |reader, command| {
+            executed.push(format!("{command:?}"));
+            match command {
+                Command::Seek(seek) => reader.seek(&seek).map(|_| true),
+                Command::Config(config) => reader.configure(&config, &mut Vec::new()).map(|_| true),
+                _ => Ok(true),
+            }
+        });
+        (result, executed)
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let (directory, mut reader) = create_reader();
+        let path = write_script(&directory, "script.rr", "# Jump to the 2nd sector\n\n   seek absolute 512\n\tprint 16  \n");
+
+        let (result, executed) = run(&mut reader, &path);
+        assert_eq!(result, Ok(()));
+        assert_eq!(executed, ["Seek(Absolute(512))", "Print(Print(16))"]);
+    }
+
+    #[test]
+    fn scripts_stop_at_the_first_error_by_default() {
+        let (directory, mut reader) = create_reader();
+        let path = write_script(&directory, "script.rr", "print 1\nseek absolute 99999\nprint 2\n");
+
+        let (result, executed) = run(&mut reader, &path);
+        assert_eq!(executed.len(), 2);
+        let error = result.unwrap_err();
+        assert!(error.starts_with(&format!("{}:2: ", path.display())), "wrong error: {}", error);
+    }
+
+    #[test]
+    fn scripts_can_continue_after_errors() {
+        let (directory, mut reader) = create_reader();
+        let path = write_script(&directory, "script.rr", "config set script-errors continue\nprnt 1\nprint 2\nseek x 1\n");
+
+        let (result, executed) = run(&mut reader, &path);
+        assert_eq!(executed.len(), 2);
+        assert_eq!(result, Err(format!("2 line(s) of '{}' failed.", path.display())));
+    }
+
+    #[test]
+    fn scripts_can_source_other_scripts_and_exit_early() {
+        let (directory, mut reader) = create_reader();
+        let nested_path = write_script(&directory, "nested.rr", "print 1\nexit\nprint 2\n");
+        let path = write_script(&directory, "script.rr", &format!("source '{}'\nprint 3\n", nested_path.display()));
+
+        let (result, executed) = run(&mut reader, &path);
+        assert_eq!(result, Ok(()));
+        assert_eq!(executed, ["Print(Print(1))", "Print(Print(3))"]);
+    }
+
+    #[test]
+    fn interrupted_scripts_stop_even_when_continuing_after_errors() {
+        let (directory, mut reader) = create_reader();
+        let path = write_script(&directory, "script.rr", "config set script-errors continue\nprint 1\nprint 2\n");
+        let flag = Arc::new(AtomicBool::new(false));
+        reader.set_interrupt_flag(Arc::clone(&flag));
+
+        // Simulate Ctrl-C being pressed while the 2nd line is running.
+        let mut executed = 0;
+        let result = run_script(&mut reader, &path, &mut |reader, command| {
+            executed += 1;
+            match command {
+                Command::Config(config) => reader.configure(&config, &mut Vec::new()).map(|_| true),
+                _ => {
+                    flag.store(true, Ordering::SeqCst);
+                    Err("Interrupted by Ctrl-C after printing 0 of 1 bytes (0.0%).".to_owned())
+                }
+            }
+        });
+        assert_eq!(executed, 2);
+        assert_eq!(result, Err(format!("{}:2: Interrupted by Ctrl-C after printing 0 of 1 bytes (0.0%).", path.display())));
+    }
+
+    #[test]
+    fn recursive_scripts_are_stopped() {
+        let (directory, mut reader) = create_reader();
+        let path = directory.path().join("script.rr");
+        fs::write(&path, format!("source '{}'\n", path.display())).unwrap();
+
+        let (result, executed) = run(&mut reader, &path);
+        assert!(executed.is_empty());
+        assert!(result.unwrap_err().ends_with("Scripts can't be nested more than 16 levels deep."));
+    }
+
+    #[test]
+    fn missing_scripts_are_reported() {
+        let (directory, mut reader) = create_reader();
+        let (result, _) = run(&mut reader, &directory.path().join("missing.rr"));
+        assert!(result.unwrap_err().starts_with("Failed to read the script"));
+    }
+}
@@ -0,0 +1,25 @@
+//! Device images shared by the tests of the modules which read them.
+
+use crate::device::ReadOnlyDevice;
+use crate::reader::DeviceReader;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+/// Returns `length` bytes of non-trivial test data, so that misplaced bytes can be detected.
+pub fn test_data(length: usize) -> Vec<u8> {
+    (0..length).map(|i| (i % 251) as u8).collect()
+}
+
+/// Creates a temporary image file filled with the provided data.
+pub fn create_image(data: &[u8]) -> NamedTempFile {
+    let mut image = NamedTempFile::new().unwrap();
+    image.write_all(data).unwrap();
+    image
+}
+
+/// Creates a temporary image file filled with the provided data, and opens a reader for it.
+pub fn create_reader(data: &[u8]) -> (NamedTempFile, DeviceReader) {
+    let image = create_image(data);
+    let reader = DeviceReader::new(ReadOnlyDevice::open(image.path()).unwrap()).unwrap();
+    (image, reader)
+}